@@ -6,9 +6,11 @@
 //! ## 节点序列化格式 (V4: 带 ID + 优先级)
 //!
 //! ```text
-//! [node_id: i64][x: f64][y: f64][ref_count: u16][_pad: u16][_pad2: u32] = 32 bytes per node
+//! [node_id: i64][x: f64][y: f64][ref_count: u16][flags: u16][_pad2: u32] = 32 bytes per node
 //! ```
 //!
+//! - `flags`: bit 0 = 已编辑 (NODE_FLAG_CHANGED)
+//!
 //! ## Way 几何序列化格式 (V3: 带 ID + RenderFeature + Z-Order)
 //!
 //! 专为前端 Canvas 渲染设计，**后端完成几何组装和 Z-Order 排序**。
@@ -21,6 +23,7 @@
 //!
 //! - `way_id`: 用于空间拾取后的高亮渲染
 //! - `render_feature`: 低 8 位 = BaseType, 高 8 位 = Flags
+//!   （已编辑的 Way 额外带 `flags::CHANGED`，包括节点被移动的 Way）
 //! - Ways 按 z_order 升序排列，确保正确的图层遮挡
 //!
 //! ## Polygon 几何序列化格式 (V1: 多环面)
@@ -36,11 +39,14 @@
 //!
 //! - 第一个 Ring 是 outer（外环），后续是 inner（洞）
 //! - 所有环必须闭合（首尾点相同）
+//! - 已编辑的面在 `render_feature` 中带 `flags::CHANGED`
+//! - 由 Relation 组装的面在 `render_feature` 中带 `flags::RELATION`，此时 ID 为 Relation ID
 
+use crate::changes::ChangedSet;
 use crate::osm_store::{OsmNode, OsmStore};
 use crate::polygon_assembler::AssembledPolygon;
 use crate::projection::lonlat_to_mercator;
-use crate::render_feature::calculate_z_order;
+use crate::render_feature::{calculate_z_order, flags};
 use crate::spatial_query::NodeWithPriority;
 use bytemuck::{Pod, Zeroable};
 
@@ -63,8 +69,11 @@ impl From<&OsmNode> for NodeBinary {
     }
 }
 
+/// 节点标记：已编辑（移动、修改标签或新建）
+pub const NODE_FLAG_CHANGED: u16 = 0x0001;
+
 /// 带优先级的节点二进制表示 (32 字节，内存对齐)
-/// 格式: [node_id: i64][x: f64][y: f64][ref_count: u16][_pad: u16][_pad2: u32]
+/// 格式: [node_id: i64][x: f64][y: f64][ref_count: u16][flags: u16][_pad2: u32]
 /// 注意：x, y 是 Web 墨卡托投影坐标（单位：米）
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
@@ -73,7 +82,7 @@ pub struct NodePriorityBinary {
     pub x: f64,         // 8 bytes - 墨卡托 X (米)
     pub y: f64,         // 8 bytes - 墨卡托 Y (米)
     pub ref_count: u16, // 2 bytes
-    pub _pad: u16,      // 2 bytes padding
+    pub flags: u16,     // 2 bytes
     pub _pad2: u32,     // 4 bytes padding (total = 32)
 }
 
@@ -86,7 +95,7 @@ impl From<&NodeWithPriority> for NodePriorityBinary {
             x,
             y,
             ref_count: node.ref_count,
-            _pad: 0,
+            flags: 0,
             _pad2: 0,
         }
     }
@@ -99,8 +108,17 @@ pub fn encode_nodes(nodes: &[OsmNode]) -> Vec<u8> {
 }
 
 /// 将带优先级的节点数组序列化为字节流
-pub fn encode_priority_nodes(nodes: &[NodeWithPriority]) -> Vec<u8> {
-    let binary_nodes: Vec<NodePriorityBinary> = nodes.iter().map(NodePriorityBinary::from).collect();
+pub fn encode_priority_nodes(nodes: &[NodeWithPriority], changed: &ChangedSet) -> Vec<u8> {
+    let binary_nodes: Vec<NodePriorityBinary> = nodes
+        .iter()
+        .map(|node| {
+            let mut binary = NodePriorityBinary::from(node);
+            if changed.nodes.contains(&node.id) {
+                binary.flags |= NODE_FLAG_CHANGED;
+            }
+            binary
+        })
+        .collect();
     bytemuck::cast_slice(&binary_nodes).to_vec()
 }
 
@@ -123,7 +141,7 @@ pub fn encode_coordinates(nodes: &[OsmNode]) -> Vec<u8> {
 /// 格式: [total_ways: u32][way_id: i64][render_feature: u16][point_count: u32][x,y coords...]...
 ///
 /// Z-Order 排序确保：隧道 < 水系 < 普通道路 < 桥梁
-pub fn encode_ways_geometry(store: &OsmStore, way_ids: &[i64]) -> Vec<u8> {
+pub fn encode_ways_geometry(store: &OsmStore, way_ids: &[i64], changed: &ChangedSet) -> Vec<u8> {
     // 第一步：收集所有有效的 Way 数据
     struct WayData {
         way_id: i64,
@@ -155,10 +173,14 @@ pub fn encode_ways_geometry(store: &OsmStore, way_ids: &[i64]) -> Vec<u8> {
         }
 
         let z_order = calculate_z_order(way.render_feature, way.layer);
+        let mut render_feature = way.render_feature;
+        if changed.way_changed(&way) {
+            render_feature |= flags::CHANGED;
+        }
 
         ways_data.push(WayData {
             way_id,
-            render_feature: way.render_feature,
+            render_feature,
             z_order,
             coords,
         });
@@ -201,7 +223,11 @@ pub fn encode_ways_geometry(store: &OsmStore, way_ids: &[i64]) -> Vec<u8> {
 ///       [point_count_ring2: u32][x,y coords...]...
 ///
 /// 支持 clip + 双倍线宽的内向描边效果
-pub fn encode_polygons_geometry(polygons: &[AssembledPolygon]) -> Vec<u8> {
+pub fn encode_polygons_geometry(
+    store: &OsmStore,
+    polygons: &[AssembledPolygon],
+    changed: &ChangedSet,
+) -> Vec<u8> {
     // 按 z_order 排序
     let mut sorted: Vec<&AssembledPolygon> = polygons.iter().collect();
     sorted.sort_by_key(|p| calculate_z_order(p.render_feature, p.layer));
@@ -224,8 +250,23 @@ pub fn encode_polygons_geometry(polygons: &[AssembledPolygon]) -> Vec<u8> {
         // 写入 Way ID (8 字节)
         buffer.extend_from_slice(&polygon.way_id.to_le_bytes());
 
-        // 写入 RenderFeature (2 字节)
-        buffer.extend_from_slice(&polygon.render_feature.to_le_bytes());
+        // 写入 RenderFeature (2 字节)，Relation 面的 way_id 为 relation_id
+        let polygon_changed = if polygon.from_relation {
+            changed.relation_changed(store, polygon.way_id)
+        } else {
+            store
+                .ways
+                .get(&polygon.way_id)
+                .is_some_and(|way| changed.way_changed(&way))
+        };
+        let mut render_feature = polygon.render_feature;
+        if polygon_changed {
+            render_feature |= flags::CHANGED;
+        }
+        if polygon.from_relation {
+            render_feature |= flags::RELATION;
+        }
+        buffer.extend_from_slice(&render_feature.to_le_bytes());
 
         // 写入 ring_count (2 字节)
         buffer.extend_from_slice(&(polygon.rings.len() as u16).to_le_bytes());
@@ -271,10 +312,11 @@ pub fn build_viewport_response_v4(
     way_ids: &[i64],
     polygons: &[AssembledPolygon],
     truncated: bool,
+    changed: &ChangedSet,
 ) -> Vec<u8> {
-    let way_data = encode_ways_geometry(store, way_ids);
-    let node_data = encode_priority_nodes(nodes);
-    let polygon_data = encode_polygons_geometry(polygons);
+    let way_data = encode_ways_geometry(store, way_ids, changed);
+    let node_data = encode_priority_nodes(nodes, changed);
+    let polygon_data = encode_polygons_geometry(store, polygons, changed);
 
     // 解析 way_data 获取实际的 way_count
     let actual_way_count = if way_data.len() >= 4 {
@@ -350,8 +392,30 @@ mod tests {
     #[test]
     fn test_encode_ways_geometry_empty() {
         let store = OsmStore::new();
-        let result = encode_ways_geometry(&store, &[]);
+        let result = encode_ways_geometry(&store, &[], &ChangedSet::default());
         assert_eq!(result.len(), 4);
         assert_eq!(u32::from_le_bytes([result[0], result[1], result[2], result[3]]), 0);
     }
+
+    #[test]
+    fn test_encode_polygons_marks_relations() {
+        let store = OsmStore::new();
+        let polygon = |from_relation| AssembledPolygon {
+            way_id: 7,
+            from_relation,
+            render_feature: 1,
+            layer: 0,
+            rings: vec![],
+        };
+        let result = encode_polygons_geometry(
+            &store,
+            &[polygon(false), polygon(true)],
+            &ChangedSet::default(),
+        );
+        // 每个面 12 字节：[id: i64][render_feature: u16][ring_count: u16]
+        let features: Vec<u16> = (0..2)
+            .map(|i| u16::from_le_bytes([result[12 + i * 12], result[13 + i * 12]]))
+            .collect();
+        assert_eq!(features, vec![1, 1 | flags::RELATION]);
+    }
 }
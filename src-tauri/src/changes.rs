@@ -0,0 +1,420 @@
+//! 变更跟踪
+//!
+//! 命令首次触及某个要素时（执行前）保存其加载时的原始版本（基准版本），
+//! 与当前数据比较即可得到每个要素的变更状态：
+//! - 基准不存在、当前存在 → 新建
+//! - 基准存在、当前不存在 → 删除
+//! - 两者都存在且标签/几何/成员不同 → 修改
+//!
+//! 撤销回原状的要素自然恢复为未变更。
+
+use crate::osm_store::{DataBounds, MemberType, OsmNode, OsmRelation, OsmStore, OsmWay};
+use crate::types::FeatureSelection;
+use dashmap::DashMap;
+use std::collections::HashSet;
+
+/// 要素的变更状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum ChangeStatus {
+    Unchanged,
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// 修改的具体内容（仅 Modified 状态有意义）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeKind {
+    pub tags: bool,
+    /// Node 坐标或 Way 节点序列
+    pub geometry: bool,
+    /// Relation 成员
+    pub members: bool,
+}
+
+/// 单个已变更要素
+#[derive(Debug, Clone, Copy)]
+pub struct EntityChange {
+    pub member_type: MemberType,
+    pub id: i64,
+    pub status: ChangeStatus,
+    pub kind: ChangeKind,
+}
+
+/// 已变更要素的 ID 集合（用于视口编码时标记高亮）
+#[derive(Debug, Clone, Default)]
+pub struct ChangedSet {
+    pub nodes: HashSet<i64>,
+    pub ways: HashSet<i64>,
+    pub relations: HashSet<i64>,
+}
+
+impl ChangedSet {
+    /// Way 自身被修改，或其任一节点被移动/新建
+    pub fn way_changed(&self, way: &OsmWay) -> bool {
+        self.ways.contains(&way.id)
+            || (!self.nodes.is_empty() && way.node_refs.iter().any(|id| self.nodes.contains(id)))
+    }
+
+    /// Relation 自身被修改，或其任一成员 Way 被修改（面的几何随之改变）
+    pub fn relation_changed(&self, store: &OsmStore, relation_id: i64) -> bool {
+        if self.relations.contains(&relation_id) {
+            return true;
+        }
+        let Some(relation) = store.relations.get(&relation_id) else {
+            return false;
+        };
+        relation
+            .members
+            .iter()
+            .filter(|m| m.member_type == MemberType::Way)
+            .any(|m| {
+                store
+                    .ways
+                    .get(&m.ref_id)
+                    .is_some_and(|way| self.way_changed(&way))
+            })
+    }
+}
+
+/// 基准版本（None 表示加载时不存在，即新建的要素）
+#[derive(Default)]
+pub struct ChangeTracker {
+    nodes: DashMap<i64, Option<OsmNode>>,
+    ways: DashMap<i64, Option<OsmWay>>,
+    relations: DashMap<i64, Option<OsmRelation>>,
+}
+
+fn compare<T>(
+    base: &Option<T>,
+    current: Option<&T>,
+    diff: impl FnOnce(&T, &T) -> ChangeKind,
+) -> (ChangeStatus, ChangeKind) {
+    match (base, current) {
+        (None, Some(_)) => (ChangeStatus::Created, ChangeKind::default()),
+        (Some(_), None) => (ChangeStatus::Deleted, ChangeKind::default()),
+        (Some(base), Some(current)) => {
+            let kind = diff(base, current);
+            if kind == ChangeKind::default() {
+                (ChangeStatus::Unchanged, kind)
+            } else {
+                (ChangeStatus::Modified, kind)
+            }
+        }
+        (None, None) => (ChangeStatus::Unchanged, ChangeKind::default()),
+    }
+}
+
+fn node_diff(base: &OsmNode, current: &OsmNode) -> ChangeKind {
+    ChangeKind {
+        tags: base.tags != current.tags,
+        geometry: base.lon != current.lon || base.lat != current.lat,
+        members: false,
+    }
+}
+
+fn way_diff(base: &OsmWay, current: &OsmWay) -> ChangeKind {
+    ChangeKind {
+        tags: base.tags != current.tags,
+        geometry: base.node_refs != current.node_refs,
+        members: false,
+    }
+}
+
+fn relation_diff(base: &OsmRelation, current: &OsmRelation) -> ChangeKind {
+    ChangeKind {
+        tags: base.tags != current.tags,
+        geometry: false,
+        members: base.members.len() != current.members.len()
+            || base.members.iter().zip(&current.members).any(|(a, b)| {
+                a.member_type != b.member_type || a.ref_id != b.ref_id || a.role != b.role
+            }),
+    }
+}
+
+impl ChangeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 捕获尚未记录的要素的基准版本（在命令执行前调用）
+    pub fn capture(&self, store: &OsmStore, selection: &FeatureSelection) {
+        for &id in &selection.node_ids {
+            self.nodes
+                .entry(id)
+                .or_insert_with(|| store.nodes.get(&id).map(|n| n.clone()));
+        }
+        for &id in &selection.way_ids {
+            self.ways
+                .entry(id)
+                .or_insert_with(|| store.ways.get(&id).map(|w| w.clone()));
+        }
+        for &id in &selection.relation_ids {
+            self.relations
+                .entry(id)
+                .or_insert_with(|| store.relations.get(&id).map(|r| r.clone()));
+        }
+    }
+
+    /// 选中要素的基准版本是否都已记录
+    pub fn tracks(&self, selection: &FeatureSelection) -> bool {
+        selection
+            .node_ids
+            .iter()
+            .all(|id| self.nodes.contains_key(id))
+            && selection
+                .way_ids
+                .iter()
+                .all(|id| self.ways.contains_key(id))
+            && selection
+                .relation_ids
+                .iter()
+                .all(|id| self.relations.contains_key(id))
+    }
+
+    /// 全部已变更的要素（按 Node/Way/Relation、ID 排序）
+    pub fn changes(&self, store: &OsmStore) -> Vec<EntityChange> {
+        let mut changes = Vec::new();
+        let mut push = |member_type, id, (status, kind)| {
+            if status != ChangeStatus::Unchanged {
+                changes.push(EntityChange {
+                    member_type,
+                    id,
+                    status,
+                    kind,
+                });
+            }
+        };
+
+        for entry in self.nodes.iter() {
+            let current = store.nodes.get(entry.key());
+            push(
+                MemberType::Node,
+                *entry.key(),
+                compare(entry.value(), current.as_deref(), node_diff),
+            );
+        }
+        for entry in self.ways.iter() {
+            let current = store.ways.get(entry.key());
+            push(
+                MemberType::Way,
+                *entry.key(),
+                compare(entry.value(), current.as_deref(), way_diff),
+            );
+        }
+        for entry in self.relations.iter() {
+            let current = store.relations.get(entry.key());
+            push(
+                MemberType::Relation,
+                *entry.key(),
+                compare(entry.value(), current.as_deref(), relation_diff),
+            );
+        }
+
+        changes.sort_by_key(|c| (c.member_type as u8, c.id));
+        changes
+    }
+
+    /// 单个要素的变更状态（未被任何命令触及的要素为未变更）
+    pub fn status(&self, store: &OsmStore, member_type: MemberType, id: i64) -> ChangeStatus {
+        let status = match member_type {
+            MemberType::Node => self
+                .nodes
+                .get(&id)
+                .map(|base| compare(base.value(), store.nodes.get(&id).as_deref(), node_diff)),
+            MemberType::Way => self
+                .ways
+                .get(&id)
+                .map(|base| compare(base.value(), store.ways.get(&id).as_deref(), way_diff)),
+            MemberType::Relation => self.relations.get(&id).map(|base| {
+                compare(
+                    base.value(),
+                    store.relations.get(&id).as_deref(),
+                    relation_diff,
+                )
+            }),
+        };
+        status.map_or(ChangeStatus::Unchanged, |(status, _)| status)
+    }
+
+    /// 包含指定节点的基准 Way（仅限已被触及的 Way）
+    pub fn base_ways_with_node(&self, node_id: i64) -> Vec<OsmWay> {
+        self.ways
+            .iter()
+            .filter_map(|entry| entry.value().clone())
+            .filter(|way| way.node_refs.contains(&node_id))
+            .collect()
+    }
+
+    /// 以指定要素为成员的基准 Relation（仅限已被触及的 Relation）
+    pub fn base_parents(&self, member_type: MemberType, id: i64) -> Vec<OsmRelation> {
+        self.relations
+            .iter()
+            .filter_map(|entry| entry.value().clone())
+            .filter(|relation| {
+                relation
+                    .members
+                    .iter()
+                    .any(|m| m.member_type == member_type && m.ref_id == id)
+            })
+            .collect()
+    }
+
+    /// 已变更要素的 ID 集合
+    pub fn changed_set(&self, store: &OsmStore) -> ChangedSet {
+        let mut set = ChangedSet::default();
+        for change in self.changes(store) {
+            match change.member_type {
+                MemberType::Node => set.nodes.insert(change.id),
+                MemberType::Way => set.ways.insert(change.id),
+                MemberType::Relation => set.relations.insert(change.id),
+            };
+        }
+        set
+    }
+
+    /// 基准 Node（加载时的版本）
+    pub fn base_node(&self, id: i64) -> Option<OsmNode> {
+        self.nodes.get(&id).and_then(|n| n.clone())
+    }
+
+    /// 基准 Way（加载时的版本）
+    pub fn base_way(&self, id: i64) -> Option<OsmWay> {
+        self.ways.get(&id).and_then(|w| w.clone())
+    }
+
+    /// 基准 Relation（加载时的版本）
+    pub fn base_relation(&self, id: i64) -> Option<OsmRelation> {
+        self.relations.get(&id).and_then(|r| r.clone())
+    }
+
+    /// 节点坐标（已删除的节点使用基准坐标）
+    fn node_position(&self, store: &OsmStore, id: i64) -> Option<(f64, f64)> {
+        match store.nodes.get(&id) {
+            Some(node) => Some((node.lon, node.lat)),
+            None => self.base_node(id).map(|n| (n.lon, n.lat)),
+        }
+    }
+
+    /// Way 节点序列（已删除的 Way 使用基准版本）
+    fn way_refs(&self, store: &OsmStore, id: i64) -> Vec<i64> {
+        match store.ways.get(&id) {
+            Some(way) => way.node_refs.clone(),
+            None => self.base_way(id).map(|w| w.node_refs).unwrap_or_default(),
+        }
+    }
+
+    /// 要素的经纬度边界框
+    ///
+    /// 已删除的要素使用删除前的几何；Relation 只展开直接成员的 Node 和 Way
+    pub fn bounds(&self, store: &OsmStore, member_type: MemberType, id: i64) -> Option<DataBounds> {
+        let node_ids = match member_type {
+            MemberType::Node => vec![id],
+            MemberType::Way => self.way_refs(store, id),
+            MemberType::Relation => {
+                let members = match store.relations.get(&id) {
+                    Some(relation) => relation.members.clone(),
+                    None => self.base_relation(id)?.members,
+                };
+                members
+                    .iter()
+                    .flat_map(|m| match m.member_type {
+                        MemberType::Node => vec![m.ref_id],
+                        MemberType::Way => self.way_refs(store, m.ref_id),
+                        MemberType::Relation => Vec::new(),
+                    })
+                    .collect()
+            }
+        };
+        DataBounds::from_points(
+            node_ids
+                .iter()
+                .filter_map(|&node_id| self.node_position(store, node_id)),
+        )
+    }
+
+    pub fn clear(&self) {
+        self.nodes.clear();
+        self.ways.clear();
+        self.relations.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{HistoryManager, MoveNodeCommand, UpdateNodeTagsCommand};
+
+    fn node(id: i64, lon: f64) -> OsmNode {
+        OsmNode {
+            id,
+            lon,
+            lat: 0.0,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn test_change_status_follows_history() {
+        let store = OsmStore::new();
+        store.add_node_with_index(node(1, 0.0));
+        store.add_node_with_index(node(2, 0.0));
+        store.insert_way(OsmWay {
+            id: 10,
+            node_refs: vec![1, 2],
+            tags: vec![],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        });
+        let history = HistoryManager::new();
+
+        let command = MoveNodeCommand {
+            node_id: 1,
+            old_lon: 0.0,
+            old_lat: 0.0,
+            new_lon: 1.0,
+            new_lat: 0.0,
+        };
+        history.execute(Box::new(command), &store);
+        let command = UpdateNodeTagsCommand {
+            node_id: 2,
+            old_tags: vec![],
+            new_tags: vec![("name".to_string(), "A".to_string())],
+        };
+        history.execute(Box::new(command), &store);
+
+        let changes = history.changes().changes(&store);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].status, ChangeStatus::Modified);
+        assert!(changes[0].kind.geometry && !changes[0].kind.tags);
+        assert!(changes[1].kind.tags && !changes[1].kind.geometry);
+
+        // Way 本身未修改，但其节点被移动时仍需高亮
+        let set = history.changed_set(&store);
+        assert!(set.ways.is_empty());
+        assert!(set.way_changed(&store.ways.get(&10).unwrap()));
+        // 以该 Way 为成员的 Relation 面同样需要高亮
+        store.relations.insert(
+            100,
+            OsmRelation {
+                id: 100,
+                members: vec![crate::osm_store::RelationMember {
+                    member_type: MemberType::Way,
+                    ref_id: 10,
+                    role: "outer".to_string(),
+                }],
+                tags: vec![("type".to_string(), "multipolygon".to_string())],
+            },
+        );
+        assert!(set.relation_changed(&store, 100));
+        assert!(!set.relation_changed(&store, 10));
+
+        history.undo(&store);
+        history.undo(&store);
+        assert!(history.changes().changes(&store).is_empty());
+        // 撤销后修订号变化，缓存的变更集随之更新
+        assert!(history.changed_set(&store).nodes.is_empty());
+        assert!(!history.changed_set(&store).relation_changed(&store, 100));
+    }
+}
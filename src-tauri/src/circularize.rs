@@ -0,0 +1,322 @@
+//! 闭合 Way 圆形化 (Circularize)
+//!
+//! 用于环岛、水塔、筒仓等圆形地物。
+//!
+//! ## 算法
+//!
+//! 在 Web 墨卡托空间中：
+//! 1. 以多边形面积质心为圆心、顶点平均距离为半径拟合圆
+//! 2. 与其他 Way 共享的节点作为"关键节点"，沿径向投影到圆上，保持拓扑连接
+//! 3. 相邻关键节点之间的弧段上，已有节点按角度均匀分布；
+//!    若相邻点夹角超过最大角步长，则插入新节点
+//! 4. 没有共享节点时，以第一个节点作为唯一关键节点
+
+use crate::history::{NodeMove, WayNodesChange};
+use crate::osm_store::{OsmNode, OsmStore};
+use crate::projection::{lonlat_to_mercator, mercator_to_lonlat};
+use std::collections::HashSet;
+use std::f64::consts::PI;
+
+/// 默认最大角步长（度）
+pub const DEFAULT_MAX_STEP_DEGREES: f64 = 20.0;
+
+/// 圆形化后环上的一个点
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CirclePoint {
+    /// 已有顶点（输入索引）移动到新坐标
+    Existing(usize, (f64, f64)),
+    /// 需要插入的新节点
+    New((f64, f64)),
+}
+
+/// 圆形化计划（尚未提交）
+#[derive(Debug, Clone)]
+pub struct CircularizePlan {
+    pub moves: Vec<NodeMove>,
+    pub added_nodes: Vec<OsmNode>,
+    pub way_change: WayNodesChange,
+}
+
+/// 多边形有向面积（逆时针为正）
+fn signed_area(points: &[(f64, f64)]) -> f64 {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let (x1, y1) = points[i];
+            let (x2, y2) = points[(i + 1) % n];
+            x1 * y2 - x2 * y1
+        })
+        .sum::<f64>()
+        / 2.0
+}
+
+/// 多边形面积质心
+fn centroid(points: &[(f64, f64)], area: f64) -> (f64, f64) {
+    let n = points.len();
+    let (mut cx, mut cy) = (0.0, 0.0);
+    for i in 0..n {
+        let (x1, y1) = points[i];
+        let (x2, y2) = points[(i + 1) % n];
+        let cross = x1 * y2 - x2 * y1;
+        cx += (x1 + x2) * cross;
+        cy += (y1 + y2) * cross;
+    }
+    (cx / (6.0 * area), cy / (6.0 * area))
+}
+
+/// 圆形化一个闭合环（输入不含重复的闭合点）
+///
+/// - `is_key`: 每个顶点是否为关键节点（共享节点）
+/// - 返回的序列从输入的第 0 个顶点开始，保持原环方向
+pub fn circularize_ring(
+    points: &[(f64, f64)],
+    is_key: &[bool],
+    max_step_degrees: f64,
+) -> Result<Vec<CirclePoint>, String> {
+    let n = points.len();
+    if n < 3 {
+        return Err("Ring has fewer than 3 vertices".to_string());
+    }
+    if max_step_degrees.is_nan() || max_step_degrees <= 0.0 || max_step_degrees >= 180.0 {
+        return Err("Maximum angular step must be between 0° and 180°".to_string());
+    }
+
+    // 平移到局部坐标，避免大墨卡托坐标带来的精度损失
+    let origin = points[0];
+    let local: Vec<(f64, f64)> = points
+        .iter()
+        .map(|(x, y)| (x - origin.0, y - origin.1))
+        .collect();
+
+    let area = signed_area(&local);
+    if area.abs() < 1e-9 {
+        return Err("Ring has no area".to_string());
+    }
+    let direction = area.signum();
+    let center = centroid(&local, area);
+
+    let radius = local
+        .iter()
+        .map(|(x, y)| (x - center.0).hypot(y - center.1))
+        .sum::<f64>()
+        / n as f64;
+
+    let angle_of = |i: usize| (local[i].1 - center.1).atan2(local[i].0 - center.0);
+    let point_at = |angle: f64| {
+        (
+            origin.0 + center.0 + radius * angle.cos(),
+            origin.1 + center.1 + radius * angle.sin(),
+        )
+    };
+
+    let mut keys: Vec<usize> = (0..n).filter(|&i| is_key[i]).collect();
+    if keys.is_empty() {
+        keys.push(0);
+    }
+
+    let max_step = max_step_degrees.to_radians();
+    let mut result: Vec<CirclePoint> = Vec::new();
+
+    for (k, &start) in keys.iter().enumerate() {
+        let end = keys[(k + 1) % keys.len()];
+        let start_angle = angle_of(start);
+
+        // 沿环方向从 start 到 end 的角度跨度，范围 (0, 2π]
+        let span = if keys.len() == 1 {
+            2.0 * PI
+        } else {
+            let raw = (angle_of(end) - start_angle) * direction;
+            let wrapped = raw.rem_euclid(2.0 * PI);
+            if wrapped < 1e-9 {
+                2.0 * PI
+            } else {
+                wrapped
+            }
+        };
+
+        // 弧段内部已有的顶点
+        let inner: Vec<usize> = (1..n)
+            .map(|d| (start + d) % n)
+            .take_while(|&i| i != end)
+            .collect();
+        let existing = inner.len();
+
+        let segments = (existing + 1).max((span / max_step).ceil() as usize);
+
+        result.push(CirclePoint::Existing(start, point_at(start_angle)));
+
+        // 已有顶点均匀占用内部位置，其余位置插入新节点
+        let mut next_existing = 0;
+        for pos in 1..segments {
+            let angle = start_angle + direction * span * pos as f64 / segments as f64;
+            let slot = if next_existing < existing {
+                (next_existing + 1) * segments / (existing + 1)
+            } else {
+                usize::MAX
+            };
+
+            if pos == slot {
+                result.push(CirclePoint::Existing(inner[next_existing], point_at(angle)));
+                next_existing += 1;
+            } else {
+                result.push(CirclePoint::New(point_at(angle)));
+            }
+        }
+    }
+
+    // 旋转序列，使第 0 个顶点位于开头
+    let first = result
+        .iter()
+        .position(|p| matches!(p, CirclePoint::Existing(0, _)))
+        .unwrap_or(0);
+    result.rotate_left(first);
+
+    Ok(result)
+}
+
+/// 计算闭合 Way 的圆形化计划
+pub fn plan_circularize(
+    store: &OsmStore,
+    way_id: i64,
+    max_step_degrees: f64,
+) -> Result<CircularizePlan, String> {
+    let old_refs = store
+        .ways
+        .get(&way_id)
+        .map(|w| w.node_refs.clone())
+        .ok_or_else(|| "Way not found".to_string())?;
+
+    if old_refs.len() < 4 || old_refs.first() != old_refs.last() {
+        return Err("Way is not closed".to_string());
+    }
+
+    let vertex_ids = &old_refs[..old_refs.len() - 1];
+    let unique: HashSet<i64> = vertex_ids.iter().copied().collect();
+    if unique.len() != vertex_ids.len() {
+        return Err("Way visits the same node more than once".to_string());
+    }
+
+    let mut coords = Vec::with_capacity(vertex_ids.len());
+    let mut is_key = Vec::with_capacity(vertex_ids.len());
+
+    for (k, node_id) in vertex_ids.iter().enumerate() {
+        let node = store
+            .nodes
+            .get(node_id)
+            .ok_or_else(|| format!("Node #{} not found", node_id))?;
+        coords.push(lonlat_to_mercator(node.lon, node.lat));
+
+        // 闭合节点在本 Way 中被引用两次
+        let own_refs = if k == 0 { 2 } else { 1 };
+        let ref_count = store.node_ref_count.get(node_id).map(|r| *r).unwrap_or(0);
+        is_key.push(ref_count > own_refs);
+    }
+
+    let circle = circularize_ring(&coords, &is_key, max_step_degrees)?;
+
+    let mut moves = Vec::new();
+    let mut added_nodes = Vec::new();
+    let mut new_refs = Vec::with_capacity(circle.len() + 1);
+
+    for point in circle {
+        match point {
+            CirclePoint::Existing(k, (x, y)) => {
+                let node_id = vertex_ids[k];
+                let node = store.nodes.get(&node_id).unwrap();
+                let (new_lon, new_lat) = mercator_to_lonlat(x, y);
+                moves.push(NodeMove {
+                    node_id,
+                    old_lon: node.lon,
+                    old_lat: node.lat,
+                    new_lon,
+                    new_lat,
+                });
+                new_refs.push(node_id);
+            }
+            CirclePoint::New((x, y)) => {
+                let (lon, lat) = mercator_to_lonlat(x, y);
+                let node = OsmNode {
+                    id: store.generate_local_id(),
+                    lon,
+                    lat,
+                    tags: Vec::new(),
+                };
+                new_refs.push(node.id);
+                added_nodes.push(node);
+            }
+        }
+    }
+    new_refs.push(new_refs[0]);
+
+    Ok(CircularizePlan {
+        moves,
+        added_nodes,
+        way_change: WayNodesChange {
+            way_id,
+            old_refs,
+            new_refs,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coords(points: &[CirclePoint]) -> Vec<(f64, f64)> {
+        points
+            .iter()
+            .map(|p| match p {
+                CirclePoint::Existing(_, c) | CirclePoint::New(c) => *c,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_square_becomes_circle() {
+        let square = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let result = circularize_ring(&square, &[false; 4], DEFAULT_MAX_STEP_DEGREES).unwrap();
+
+        assert_eq!(result.len(), 18);
+        assert!(matches!(result[0], CirclePoint::Existing(0, _)));
+        let existing: Vec<usize> = result
+            .iter()
+            .filter_map(|p| match p {
+                CirclePoint::Existing(i, _) => Some(*i),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(existing, vec![0, 1, 2, 3]);
+
+        let radii: Vec<f64> = coords(&result)
+            .iter()
+            .map(|(x, y)| (x - 5.0).hypot(y - 5.0))
+            .collect();
+        for r in &radii {
+            assert!((r - radii[0]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_key_nodes_stay_on_their_angle() {
+        let square = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let is_key = [false, false, true, false];
+        let result = circularize_ring(&square, &is_key, DEFAULT_MAX_STEP_DEGREES).unwrap();
+
+        let key = result
+            .iter()
+            .find_map(|p| match p {
+                CirclePoint::Existing(2, c) => Some(*c),
+                _ => None,
+            })
+            .unwrap();
+        // 关键节点沿径向投影，保持 45° 方向
+        assert!(((key.1 - 5.0).atan2(key.0 - 5.0).to_degrees() - 45.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rejects_degenerate_ring() {
+        let line = vec![(0.0, 0.0), (5.0, 0.0), (10.0, 0.0)];
+        assert!(circularize_ring(&line, &[false; 3], DEFAULT_MAX_STEP_DEGREES).is_err());
+    }
+}
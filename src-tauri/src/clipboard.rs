@@ -0,0 +1,320 @@
+//! 要素剪贴板 (Copy / Paste)
+//!
+//! 复制时捕获选择集的完整子图快照：
+//! - 选中的 Node
+//! - 选中的 Way 及其全部节点
+//! - 选中的 Relation 及其已加载的成员（递归）
+//!
+//! 粘贴时所有副本分配新的本地 ID（负数），内部引用按新 ID 重映射；
+//! 指向子图之外要素的 Relation 成员保持原引用。
+//! 与子图之外的 Way 共享的节点可以选择复制，或直接引用原节点。
+
+use crate::osm_store::{MemberType, OsmNode, OsmRelation, OsmStore, OsmWay};
+use crate::projection::{lonlat_to_mercator, mercator_to_lonlat};
+use crate::types::FeatureSelection;
+use std::collections::{HashMap, HashSet};
+
+/// 剪贴板内容（复制时的快照）
+#[derive(Debug, Clone, Default)]
+pub struct ClipboardContent {
+    pub nodes: Vec<OsmNode>,
+    pub ways: Vec<OsmWay>,
+    pub relations: Vec<OsmRelation>,
+    /// 与子图之外的 Way 共享、且未被直接选中的节点
+    pub shared_node_ids: HashSet<i64>,
+}
+
+impl ClipboardContent {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.ways.is_empty() && self.relations.is_empty()
+    }
+}
+
+/// 粘贴计划（尚未提交）
+#[derive(Debug, Clone, Default)]
+pub struct PastePlan {
+    pub nodes: Vec<OsmNode>,
+    pub ways: Vec<OsmWay>,
+    pub relations: Vec<OsmRelation>,
+}
+
+/// 捕获选择集的子图快照
+pub fn capture(store: &OsmStore, selection: &FeatureSelection) -> ClipboardContent {
+    let mut node_ids: Vec<i64> = Vec::new();
+    let mut way_ids: Vec<i64> = Vec::new();
+    let mut relation_ids: Vec<i64> = Vec::new();
+    let mut seen: HashSet<(MemberType, i64)> = HashSet::new();
+
+    // 递归展开 Relation 成员（防止循环引用）
+    let mut stack: Vec<(MemberType, i64)> = selection
+        .relation_ids
+        .iter()
+        .rev()
+        .map(|&id| (MemberType::Relation, id))
+        .collect();
+    stack.extend(
+        selection
+            .way_ids
+            .iter()
+            .rev()
+            .map(|&id| (MemberType::Way, id)),
+    );
+    stack.extend(
+        selection
+            .node_ids
+            .iter()
+            .rev()
+            .map(|&id| (MemberType::Node, id)),
+    );
+
+    while let Some((member_type, id)) = stack.pop() {
+        if !seen.insert((member_type, id)) {
+            continue;
+        }
+        match member_type {
+            MemberType::Node if store.nodes.contains_key(&id) => node_ids.push(id),
+            MemberType::Way if store.ways.contains_key(&id) => way_ids.push(id),
+            MemberType::Relation => {
+                if let Some(relation) = store.relations.get(&id) {
+                    relation_ids.push(id);
+                    for member in relation.members.iter().rev() {
+                        stack.push((member.member_type, member.ref_id));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut content = ClipboardContent::default();
+    let explicit_nodes: HashSet<i64> = node_ids.iter().copied().collect();
+    let mut node_set: HashSet<i64> = HashSet::new();
+    // 节点在子图内被引用的次数
+    let mut inner_refs: HashMap<i64, u16> = HashMap::new();
+
+    for way_id in &way_ids {
+        let way = match store.ways.get(way_id) {
+            Some(way) => way.clone(),
+            None => continue,
+        };
+        for &node_id in &way.node_refs {
+            *inner_refs.entry(node_id).or_insert(0) += 1;
+            if store.nodes.contains_key(&node_id) && node_set.insert(node_id) {
+                content
+                    .nodes
+                    .push(store.nodes.get(&node_id).unwrap().clone());
+            }
+        }
+        content.ways.push(way);
+    }
+
+    for node_id in &node_ids {
+        if node_set.insert(*node_id) {
+            content
+                .nodes
+                .push(store.nodes.get(node_id).unwrap().clone());
+        }
+    }
+
+    content.relations = relation_ids
+        .iter()
+        .filter_map(|id| store.relations.get(id).map(|r| r.clone()))
+        .collect();
+
+    content.shared_node_ids = inner_refs
+        .into_iter()
+        .filter(|(node_id, count)| {
+            let total = store.node_ref_count.get(node_id).map(|r| *r).unwrap_or(0);
+            total > *count && !explicit_nodes.contains(node_id)
+        })
+        .map(|(node_id, _)| node_id)
+        .collect();
+
+    content
+}
+
+/// 根据剪贴板内容生成粘贴计划
+///
+/// - `offset`: 墨卡托偏移量（米）
+/// - `duplicate_shared`: 是否复制共享节点；否则副本直接引用原节点（原节点不移动）
+pub fn plan_paste(
+    store: &OsmStore,
+    content: &ClipboardContent,
+    offset: (f64, f64),
+    duplicate_shared: bool,
+) -> PastePlan {
+    let mut id_map: HashMap<(MemberType, i64), i64> = HashMap::new();
+    let mut plan = PastePlan::default();
+
+    for node in &content.nodes {
+        let keep_original = !duplicate_shared
+            && content.shared_node_ids.contains(&node.id)
+            && store.nodes.contains_key(&node.id);
+        if keep_original {
+            continue;
+        }
+
+        let (x, y) = lonlat_to_mercator(node.lon, node.lat);
+        let (lon, lat) = mercator_to_lonlat(x + offset.0, y + offset.1);
+        let copy = OsmNode {
+            id: store.generate_local_id(),
+            lon,
+            lat,
+            tags: node.tags.clone(),
+        };
+        id_map.insert((MemberType::Node, node.id), copy.id);
+        plan.nodes.push(copy);
+    }
+
+    for way in &content.ways {
+        id_map.insert((MemberType::Way, way.id), store.generate_local_id());
+    }
+    for relation in &content.relations {
+        id_map.insert(
+            (MemberType::Relation, relation.id),
+            store.generate_local_id(),
+        );
+    }
+
+    for way in &content.ways {
+        let mut copy = way.clone();
+        copy.id = id_map[&(MemberType::Way, way.id)];
+        for node_id in copy.node_refs.iter_mut() {
+            if let Some(&new_id) = id_map.get(&(MemberType::Node, *node_id)) {
+                *node_id = new_id;
+            }
+        }
+        plan.ways.push(copy);
+    }
+
+    for relation in &content.relations {
+        let mut copy = relation.clone();
+        copy.id = id_map[&(MemberType::Relation, relation.id)];
+        for member in copy.members.iter_mut() {
+            if let Some(&new_id) = id_map.get(&(member.member_type, member.ref_id)) {
+                member.ref_id = new_id;
+            }
+        }
+        plan.relations.push(copy);
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_store::RelationMember;
+
+    fn node(id: i64, lon: f64, lat: f64) -> OsmNode {
+        OsmNode {
+            id,
+            lon,
+            lat,
+            tags: vec![],
+        }
+    }
+
+    fn way(id: i64, node_refs: Vec<i64>) -> OsmWay {
+        OsmWay {
+            id,
+            node_refs,
+            tags: vec![("building".to_string(), "yes".to_string())],
+            render_feature: 0,
+            layer: 0,
+            is_area: true,
+        }
+    }
+
+    /// 两栋相邻建筑共享节点 2、3
+    fn store_with_buildings() -> OsmStore {
+        let store = OsmStore::new();
+        for (id, lon, lat) in [
+            (1, 0.0, 0.0),
+            (2, 0.001, 0.0),
+            (3, 0.001, 0.001),
+            (4, 0.0, 0.001),
+            (5, 0.002, 0.0),
+            (6, 0.002, 0.001),
+        ] {
+            store.insert_node(node(id, lon, lat));
+        }
+        store.insert_way(way(10, vec![1, 2, 3, 4, 1]));
+        store.insert_way(way(11, vec![2, 5, 6, 3, 2]));
+        store.relations.insert(
+            100,
+            OsmRelation {
+                id: 100,
+                members: vec![
+                    RelationMember {
+                        member_type: MemberType::Way,
+                        ref_id: 10,
+                        role: "outer".to_string(),
+                    },
+                    RelationMember {
+                        member_type: MemberType::Way,
+                        ref_id: 999,
+                        role: "outer".to_string(),
+                    },
+                ],
+                tags: vec![("type".to_string(), "multipolygon".to_string())],
+            },
+        );
+        store
+    }
+
+    #[test]
+    fn test_capture_subgraph() {
+        let store = store_with_buildings();
+        let selection = FeatureSelection {
+            relation_ids: vec![100],
+            ..Default::default()
+        };
+        let content = capture(&store, &selection);
+
+        assert_eq!(content.relations.len(), 1);
+        assert_eq!(content.ways.len(), 1);
+        assert_eq!(content.nodes.len(), 4);
+        let mut shared: Vec<i64> = content.shared_node_ids.iter().copied().collect();
+        shared.sort();
+        assert_eq!(shared, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_paste_remaps_references() {
+        let store = store_with_buildings();
+        let selection = FeatureSelection {
+            relation_ids: vec![100],
+            ..Default::default()
+        };
+        let content = capture(&store, &selection);
+        let plan = plan_paste(&store, &content, (100.0, 0.0), true);
+
+        assert_eq!(plan.nodes.len(), 4);
+        let way = &plan.ways[0];
+        assert!(way.id < 0);
+        assert!(way.node_refs.iter().all(|id| *id < 0));
+        assert_eq!(way.node_refs.first(), way.node_refs.last());
+
+        let members = &plan.relations[0].members;
+        assert_eq!(members[0].ref_id, way.id);
+        // 子图之外的成员保持原引用
+        assert_eq!(members[1].ref_id, 999);
+    }
+
+    #[test]
+    fn test_paste_keeps_shared_nodes() {
+        let store = store_with_buildings();
+        let selection = FeatureSelection {
+            way_ids: vec![10],
+            ..Default::default()
+        };
+        let content = capture(&store, &selection);
+        let plan = plan_paste(&store, &content, (0.0, 0.0), false);
+
+        assert_eq!(plan.nodes.len(), 2);
+        let refs = &plan.ways[0].node_refs;
+        assert_eq!(refs[1], 2);
+        assert_eq!(refs[2], 3);
+    }
+}
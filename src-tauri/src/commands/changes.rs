@@ -0,0 +1,90 @@
+//! 变更集命令
+//!
+//! 查询相对于加载时数据的编辑结果，以及将单个要素恢复到加载时的版本
+
+use crate::changes::ChangeStatus;
+use crate::history::Command;
+use crate::osm_store::MemberType;
+use crate::revert::plan_revert;
+use crate::types::{ChangeCounts, ChangeEntry, ChangesSummary, FeatureSelection, RevertResult};
+use crate::AppState;
+use tauri::State;
+
+/// 每页默认条目数
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// 获取变更集摘要：各类要素的新建/修改/删除计数，以及分页的要素列表
+#[tauri::command]
+pub fn get_changes_summary(
+    offset: Option<usize>,
+    limit: Option<usize>,
+    state: State<AppState>,
+) -> ChangesSummary {
+    let tracker = state.history.changes();
+    let changes = tracker.changes(&state.store);
+
+    let mut summary = ChangesSummary {
+        nodes: ChangeCounts::default(),
+        ways: ChangeCounts::default(),
+        relations: ChangeCounts::default(),
+        total: changes.len(),
+        entries: Vec::new(),
+    };
+
+    for change in &changes {
+        let counts = match change.member_type {
+            MemberType::Node => &mut summary.nodes,
+            MemberType::Way => &mut summary.ways,
+            MemberType::Relation => &mut summary.relations,
+        };
+        match change.status {
+            ChangeStatus::Created => counts.created += 1,
+            ChangeStatus::Modified => counts.modified += 1,
+            ChangeStatus::Deleted => counts.deleted += 1,
+            ChangeStatus::Unchanged => {}
+        }
+    }
+
+    summary.entries = changes
+        .iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(DEFAULT_PAGE_SIZE))
+        .map(|change| ChangeEntry {
+            member_type: change.member_type,
+            id: change.id,
+            status: change.status,
+            tags_changed: change.kind.tags,
+            geometry_changed: change.kind.geometry,
+            members_changed: change.kind.members,
+            bounds: tracker.bounds(&state.store, change.member_type, change.id),
+        })
+        .collect();
+
+    summary
+}
+
+/// 将要素恢复到加载时的版本（作为新的可撤销命令）
+///
+/// 恢复标签、几何以及在父 Relation 中的成员身份；
+/// Way 引用的节点已被删除时一并重新创建
+#[tauri::command]
+pub fn revert_entity(member_type: MemberType, id: i64, state: State<AppState>) -> RevertResult {
+    let command = match plan_revert(&state.store, state.history.changes(), member_type, id) {
+        Ok(command) => command,
+        Err(message) => {
+            return RevertResult {
+                success: false,
+                message: Some(message),
+                affected: FeatureSelection::default(),
+            }
+        }
+    };
+
+    let affected = command.affected_entities();
+    let result = state.history.execute(Box::new(command), &state.store);
+    RevertResult {
+        success: result.success,
+        message: result.message,
+        affected,
+    }
+}
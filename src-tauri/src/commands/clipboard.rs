@@ -0,0 +1,84 @@
+//! 剪贴板命令
+//!
+//! 处理要素的复制和粘贴
+
+use crate::clipboard;
+use crate::history::PasteCommand;
+use crate::types::{CopyResult, FeatureSelection, PasteResult};
+use crate::AppState;
+use tauri::State;
+
+/// 复制选中要素到剪贴板（捕获完整子图快照）
+#[tauri::command]
+pub fn copy_features(selection: FeatureSelection, state: State<AppState>) -> CopyResult {
+    let content = clipboard::capture(&state.store, &selection);
+    if content.is_empty() {
+        return CopyResult {
+            success: false,
+            message: Some("Nothing to copy".to_string()),
+            node_count: 0,
+            way_count: 0,
+            relation_count: 0,
+        };
+    }
+
+    let result = CopyResult {
+        success: true,
+        message: None,
+        node_count: content.nodes.len(),
+        way_count: content.ways.len(),
+        relation_count: content.relations.len(),
+    };
+    *state.clipboard.lock().unwrap() = content;
+    result
+}
+
+/// 在墨卡托偏移量处粘贴剪贴板内容（使用命令模式支持撤销）
+///
+/// `duplicate_shared_nodes` 为 false 时，与其他 Way 共享的节点不复制，
+/// 副本直接引用原节点；默认复制
+#[tauri::command]
+pub fn paste_features(
+    offset_x: f64,
+    offset_y: f64,
+    duplicate_shared_nodes: Option<bool>,
+    state: State<AppState>,
+) -> PasteResult {
+    let plan = {
+        let content = state.clipboard.lock().unwrap();
+        if content.is_empty() {
+            return PasteResult {
+                success: false,
+                message: Some("Clipboard is empty".to_string()),
+                node_ids: Vec::new(),
+                way_ids: Vec::new(),
+                relation_ids: Vec::new(),
+            };
+        }
+        clipboard::plan_paste(
+            &state.store,
+            &content,
+            (offset_x, offset_y),
+            duplicate_shared_nodes.unwrap_or(true),
+        )
+    };
+
+    let node_ids = plan.nodes.iter().map(|n| n.id).collect();
+    let way_ids = plan.ways.iter().map(|w| w.id).collect();
+    let relation_ids = plan.relations.iter().map(|r| r.id).collect();
+
+    let command = PasteCommand {
+        nodes: plan.nodes,
+        ways: plan.ways,
+        relations: plan.relations,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    PasteResult {
+        success: result.success,
+        message: result.message,
+        node_ids,
+        way_ids,
+        relation_ids,
+    }
+}
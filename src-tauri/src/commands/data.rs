@@ -2,6 +2,7 @@
 //!
 //! 处理 PBF 文件加载、统计信息和边界查询
 
+use crate::journal::JournalSource;
 use crate::osm_store::{DataBounds, StoreStats};
 use crate::pbf_parser;
 use crate::AppState;
@@ -22,6 +23,11 @@ pub fn get_bounds(state: State<AppState>) -> Option<DataBounds> {
 }
 
 /// 加载 PBF 文件 (异步命令)
+///
+/// 加载完成后清空历史记录，并检查源文件旁的崩溃恢复日志：
+/// - 存在与源文件匹配的遗留日志时停止记录并锁定编辑，
+///   等待前端调用 replay_journal / discard_journal
+/// - 否则开始新的日志
 #[tauri::command]
 pub async fn load_pbf(
     path: String,
@@ -30,9 +36,30 @@ pub async fn load_pbf(
     let store = Arc::clone(&state.store);
     let path = PathBuf::from(path);
 
-    tokio::task::spawn_blocking(move || {
-        pbf_parser::parse_pbf_parallel(&path, store).map_err(|e| e.to_string())
+    let (progress, source) = tokio::task::spawn_blocking(move || {
+        let progress = pbf_parser::parse_pbf_parallel(&path, store).map_err(|e| e.to_string())?;
+        // 无法计算指纹时不启用日志，不影响加载
+        let source = JournalSource::new(&path).ok();
+        Ok::<_, String>((progress, source))
     })
     .await
-    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())??;
+
+    // 上一个文件的撤销栈和基准版本不再适用于新数据
+    state.history.clear();
+    // 不再写入上一个文件的日志；存在遗留日志时在重放或丢弃前不能产生新的编辑
+    state.history.set_journal(None);
+    let pending = source.as_ref().is_some_and(|s| s.pending().is_some());
+    state.history.set_locked(pending);
+    match source {
+        Some(source) if pending => *state.pending_journal.lock().unwrap() = Some(source),
+        source => {
+            *state.pending_journal.lock().unwrap() = None;
+            state
+                .history
+                .set_journal(source.and_then(|s| s.start().ok()));
+        }
+    }
+
+    Ok(progress)
 }
@@ -3,17 +3,30 @@
 //! 处理标签编辑、Undo/Redo 等修改操作
 
 use crate::history::{
-    AddNodeCommand, DeleteNodeCommand, DeleteWayCommand, MoveNodeCommand, UpdateNodeTagsCommand,
-    UpdateWayTagsCommand,
+    AddNodeCommand, AttachNodeCommand, BatchUpdateTagsCommand, Command, CommandResult,
+    CompositeCommand, DeleteNodeCommand, DeleteWayCommand, ExtractNodeCommand, HistoryJump,
+    HistoryLimits, MoveNodeCommand, RelationCascade, TagChange, TransformCommand,
+    UpdateNodeTagsCommand, UpdateWayTagsCommand, WayStyle,
 };
-use crate::osm_store::OsmNode;
+use crate::osm_store::{DataBounds, MemberType, OsmNode, OsmStore};
 use crate::polygon_assembler;
 use crate::projection;
 use crate::render_feature;
-use crate::types::{AddNodeResult, DeleteFeatureResult, MoveNodeResult, UndoRedoResult, UpdateTagsResult};
+use crate::snap::{self, Snap};
+use crate::spatial_query::{self, NearestSegment};
+use crate::tag_edit::{self, FindReplace, SearchScope, TagOperation};
+use crate::transform::{self, TransformOperation};
+use crate::types::{
+    AddNodeResult, AttachNodeResult, BatchTagEditResult, DeleteFeatureResult, FeatureSelection,
+    FindReplaceResult, GeometryEditResult, HistoryEntries, HistoryJumpResult, HistoryState,
+    MoveNodeResult, SnapTarget, TagEditChange, UndoRedoResult, UpdateTagsResult,
+};
 use crate::AppState;
 use tauri::State;
 
+/// 附着节点时查找附近 Way 的默认距离（地面米）
+const ATTACH_TOLERANCE_METERS: f64 = 10.0;
+
 /// 更新路径标签（使用命令模式支持撤销）
 #[tauri::command]
 pub fn update_way_tags(
@@ -103,10 +116,119 @@ pub fn update_node_tags(
     }
 }
 
-/// 撤销上一个操作
+/// 批量编辑选择集中所有要素的标签（使用命令模式支持撤销）
+///
+/// 操作按顺序应用于每个要素，全部变更作为一个历史记录
 #[tauri::command]
-pub fn undo(state: State<AppState>) -> UndoRedoResult {
-    let result = state.history.undo(&state.store);
+pub fn batch_update_tags(
+    selection: FeatureSelection,
+    operations: Vec<TagOperation>,
+    state: State<AppState>,
+) -> BatchTagEditResult {
+    let changes = tag_edit::plan_tag_changes(&state.store, &selection, &operations);
+    if changes.is_empty() {
+        return BatchTagEditResult {
+            success: false,
+            message: Some("No tags changed".to_string()),
+            changes: Vec::new(),
+        };
+    }
+
+    let summary = tag_edit_summary(&changes);
+    let command = BatchUpdateTagsCommand {
+        label: "Batch edit tags".to_string(),
+        changes,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    BatchTagEditResult {
+        success: result.success,
+        message: result.message,
+        changes: if result.success { summary } else { Vec::new() },
+    }
+}
+
+/// 查找替换标签（dry_run 时只返回匹配结果，不修改数据）
+///
+/// 全部变更作为一个历史记录；Way 的渲染属性随新标签重新计算
+#[tauri::command]
+pub fn find_replace_tags(
+    find: FindReplace,
+    scope: SearchScope,
+    dry_run: bool,
+    state: State<AppState>,
+) -> FindReplaceResult {
+    let plan = match tag_edit::plan_find_replace(&state.store, &find, &scope) {
+        Ok(plan) => plan,
+        Err(message) => {
+            return FindReplaceResult {
+                success: false,
+                message: Some(message),
+                matched: FeatureSelection::default(),
+                tag_count: 0,
+                changes: Vec::new(),
+            }
+        }
+    };
+
+    let summary = tag_edit_summary(&plan.changes);
+    if dry_run {
+        return FindReplaceResult {
+            success: true,
+            message: None,
+            matched: plan.matched,
+            tag_count: plan.tag_count,
+            changes: summary,
+        };
+    }
+    if plan.changes.is_empty() {
+        return FindReplaceResult {
+            success: false,
+            message: Some("No tags changed".to_string()),
+            matched: plan.matched,
+            tag_count: plan.tag_count,
+            changes: Vec::new(),
+        };
+    }
+
+    let command = BatchUpdateTagsCommand {
+        label: "Find and replace tags".to_string(),
+        changes: plan.changes,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    FindReplaceResult {
+        success: result.success,
+        message: result.message,
+        matched: plan.matched,
+        tag_count: plan.tag_count,
+        changes: if result.success { summary } else { Vec::new() },
+    }
+}
+
+/// 标签变更的渲染属性摘要
+fn tag_edit_summary(changes: &[TagChange]) -> Vec<TagEditChange> {
+    changes
+        .iter()
+        .map(|change| {
+            let style = change.new_style.unwrap_or(WayStyle {
+                render_feature: 0,
+                layer: 0,
+                is_area: false,
+            });
+            TagEditChange {
+                member_type: change.member_type,
+                id: change.id,
+                render_feature: style.render_feature,
+                layer: style.layer,
+                is_area: style.is_area,
+                render_changed: change.style_changed(),
+            }
+        })
+        .collect()
+}
+
+fn history_result(result: CommandResult, state: &AppState) -> UndoRedoResult {
     UndoRedoResult {
         success: result.success,
         needs_redraw: result.needs_redraw,
@@ -116,33 +238,150 @@ pub fn undo(state: State<AppState>) -> UndoRedoResult {
     }
 }
 
+/// 撤销上一个操作
+#[tauri::command]
+pub fn undo(state: State<AppState>) -> UndoRedoResult {
+    let result = state.history.undo(&state.store);
+    history_result(result, &state)
+}
+
 /// 重做上一个撤销的操作
 #[tauri::command]
 pub fn redo(state: State<AppState>) -> UndoRedoResult {
     let result = state.history.redo(&state.store);
-    UndoRedoResult {
-        success: result.success,
-        needs_redraw: result.needs_redraw,
-        message: result.message,
+    history_result(result, &state)
+}
+
+/// 获取完整的撤销/重做条目列表
+#[tauri::command]
+pub fn get_history_entries(state: State<AppState>) -> HistoryEntries {
+    let (undo, redo) = state.history.entries();
+    HistoryEntries { undo, redo }
+}
+
+/// 计算要素集合的经纬度边界框（Way/Relation 展开为节点）
+fn selection_bounds(store: &OsmStore, selection: &FeatureSelection) -> Option<DataBounds> {
+    let node_ids = transform::resolve_selection_nodes(store, selection);
+    DataBounds::from_points(
+        node_ids
+            .iter()
+            .filter_map(|id| store.nodes.get(id).map(|n| (n.lon, n.lat))),
+    )
+}
+
+/// `before` 为跳转前受影响要素的边界框：跳转删除的要素只在跳转前有坐标，
+/// 被移动的要素在前后两个位置都需要可见，因此取前后边界框的并集
+fn jump_result(
+    jump: HistoryJump,
+    before: Option<DataBounds>,
+    state: &AppState,
+) -> HistoryJumpResult {
+    let after = selection_bounds(&state.store, &jump.affected);
+    HistoryJumpResult {
+        success: jump.result.success,
+        needs_redraw: jump.result.needs_redraw,
+        message: jump.result.message,
         undo_count: state.history.undo_count(),
         redo_count: state.history.redo_count(),
+        steps: jump.steps,
+        bounds: before.into_iter().chain(after).reduce(|a, b| a.union(&b)),
     }
 }
 
-/// 获取历史记录状态
+/// 连续撤销到指定条目之前的状态（撤销第 index 条及之后的全部条目）
+#[tauri::command]
+pub fn undo_to(index: usize, state: State<AppState>) -> HistoryJumpResult {
+    undo_to_impl(&state, index)
+}
+
+fn undo_to_impl(state: &AppState, index: usize) -> HistoryJumpResult {
+    let before = selection_bounds(&state.store, &state.history.undo_to_affected(index));
+    let jump = state.history.undo_to(index, &state.store);
+    jump_result(jump, before, state)
+}
+
+/// 连续重做到指定的重做条目（包含该条目）
+#[tauri::command]
+pub fn redo_to(index: usize, state: State<AppState>) -> HistoryJumpResult {
+    redo_to_impl(&state, index)
+}
+
+fn redo_to_impl(state: &AppState, index: usize) -> HistoryJumpResult {
+    let before = selection_bounds(&state.store, &state.history.redo_to_affected(index));
+    let jump = state.history.redo_to(index, &state.store);
+    jump_result(jump, before, state)
+}
+
+/// 开始事务，之后的编辑在提交时合并为一个撤销步骤
+///
+/// 用于拖拽、连续编辑等多步手势
+#[tauri::command]
+pub fn begin_transaction(label: Option<String>, state: State<AppState>) -> UndoRedoResult {
+    let label = label.unwrap_or_else(|| "Edit".to_string());
+    let result = state.history.begin_transaction(&label);
+    history_result(result, &state)
+}
+
+/// 提交事务
+#[tauri::command]
+pub fn commit_transaction(state: State<AppState>) -> UndoRedoResult {
+    let result = state.history.commit_transaction();
+    history_result(result, &state)
+}
+
+/// 中止事务，撤销事务中已执行的全部编辑
 #[tauri::command]
-pub fn get_history_state(state: State<AppState>) -> (usize, usize) {
-    (state.history.undo_count(), state.history.redo_count())
+pub fn abort_transaction(state: State<AppState>) -> UndoRedoResult {
+    let result = state.history.abort_transaction(&state.store);
+    history_result(result, &state)
+}
+
+fn history_state(state: &AppState) -> HistoryState {
+    let limits = state.history.limits();
+    let (trimmed_count, trimmed_entities) = state.history.trimmed();
+    HistoryState {
+        undo_count: state.history.undo_count(),
+        redo_count: state.history.redo_count(),
+        memory_bytes: state.history.memory_usage(),
+        max_entries: limits.max_entries,
+        max_bytes: limits.max_bytes,
+        trimmed_count,
+        trimmed_entities,
+    }
+}
+
+/// 获取历史记录状态（条目数、内存占用和容量限制）
+#[tauri::command]
+pub fn get_history_state(state: State<AppState>) -> HistoryState {
+    history_state(&state)
+}
+
+/// 设置历史记录容量限制（为空的项保持不变），超出部分立即裁剪
+#[tauri::command]
+pub fn set_history_limits(
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+    state: State<AppState>,
+) -> HistoryState {
+    let current = state.history.limits();
+    state.history.set_limits(HistoryLimits {
+        max_entries: max_entries.unwrap_or(current.max_entries).max(1),
+        max_bytes: max_bytes.unwrap_or(current.max_bytes),
+    });
+    history_state(&state)
 }
 
 /// 移动节点（使用命令模式支持撤销）
 ///
-/// 接收墨卡托坐标（米），转换为经纬度后更新节点
+/// 接收墨卡托坐标（米），转换为经纬度后更新节点。
+/// 指定 `snap_tolerance_meters` 时，容差范围内的节点被合并到已有节点，
+/// 或作为顶点插入最近的 Way 线段；撤销时连接和位置一并恢复
 #[tauri::command]
 pub fn move_node(
     node_id: i64,
     new_merc_x: f64,
     new_merc_y: f64,
+    snap_tolerance_meters: Option<f64>,
     state: State<AppState>,
 ) -> MoveNodeResult {
     let node = state.store.nodes.get(&node_id);
@@ -150,6 +389,7 @@ pub fn move_node(
         return MoveNodeResult {
             success: false,
             message: Some("Node not found".to_string()),
+            snapped: None,
         };
     }
 
@@ -158,31 +398,149 @@ pub fn move_node(
     let old_lat = node.lat;
     drop(node);
 
-    // 墨卡托坐标转经纬度
-    let (new_lon, new_lat) = projection::mercator_to_lonlat(new_merc_x, new_merc_y);
+    let snap = snap_tolerance_meters.and_then(|tolerance| {
+        snap::find_snap(
+            &state.store,
+            new_merc_x,
+            new_merc_y,
+            tolerance,
+            Some(node_id),
+        )
+    });
+
+    let (command, snapped): (Box<dyn Command>, _) = match snap {
+        Some(Snap::Node(target_id)) => {
+            match snap::plan_merge_nodes(&state.store, node_id, target_id) {
+                Ok(command) => (
+                    Box::new(command),
+                    Some(SnapTarget::Node { node_id: target_id }),
+                ),
+                Err(message) => {
+                    return MoveNodeResult {
+                        success: false,
+                        message: Some(message),
+                        snapped: None,
+                    }
+                }
+            }
+        }
+        Some(Snap::Way(segment)) => {
+            let (new_lon, new_lat) =
+                projection::mercator_to_lonlat(segment.point.0, segment.point.1);
+            let command = AttachNodeCommand {
+                node_id,
+                way_id: segment.way_id,
+                index: segment.index + 1,
+                old_lon,
+                old_lat,
+                new_lon,
+                new_lat,
+            };
+            (Box::new(command), Some(snap_target(&segment)))
+        }
+        None => {
+            // 墨卡托坐标转经纬度
+            let (new_lon, new_lat) = projection::mercator_to_lonlat(new_merc_x, new_merc_y);
+            let command = MoveNodeCommand {
+                node_id,
+                old_lon,
+                old_lat,
+                new_lon,
+                new_lat,
+            };
+            (Box::new(command), None)
+        }
+    };
 
-    let command = MoveNodeCommand {
-        node_id,
-        old_lon,
-        old_lat,
-        new_lon,
-        new_lat,
+    let result = state.history.execute(command, &state.store);
+
+    MoveNodeResult {
+        success: result.success,
+        message: result.message,
+        snapped: if result.success { snapped } else { None },
+    }
+}
+
+fn snap_target(segment: &NearestSegment) -> SnapTarget {
+    SnapTarget::Way {
+        way_id: segment.way_id,
+        index: segment.index + 1,
+    }
+}
+
+/// 批量变换选中要素（平移/旋转/缩放，使用命令模式支持撤销）
+///
+/// 选择集中的 Way 和 Relation 会被展开为节点集合，共享节点只移动一次
+#[tauri::command]
+pub fn transform_features(
+    selection: FeatureSelection,
+    operation: TransformOperation,
+    state: State<AppState>,
+) -> GeometryEditResult {
+    let node_ids = transform::resolve_selection_nodes(&state.store, &selection);
+
+    let moves = match transform::plan_transform(&state.store, &node_ids, &operation) {
+        Some(moves) if !moves.is_empty() => moves,
+        Some(_) => {
+            return GeometryEditResult {
+                success: false,
+                message: Some("No nodes to transform".to_string()),
+                moved_node_count: 0,
+                removed_node_count: 0,
+            };
+        }
+        None => {
+            return GeometryEditResult {
+                success: false,
+                message: Some("Invalid transform".to_string()),
+                moved_node_count: 0,
+                removed_node_count: 0,
+            };
+        }
     };
 
+    let moved_node_count = moves.len();
+    let command = TransformCommand { operation, moves };
     let result = state.history.execute(Box::new(command), &state.store);
 
-    MoveNodeResult {
+    GeometryEditResult {
         success: result.success,
         message: result.message,
+        moved_node_count,
+        removed_node_count: 0,
     }
 }
 
 /// 添加节点（使用命令模式支持撤销）
 ///
-/// 接收墨卡托坐标（米），转换为经纬度后创建节点
+/// 接收墨卡托坐标（米），转换为经纬度后创建节点。
+/// 指定 `snap_tolerance_meters` 时，容差范围内有已有节点则直接返回该节点，
+/// 靠近 Way 时新节点作为顶点插入最近的线段（与创建合并为一个历史记录）
 #[tauri::command]
-pub fn add_node(merc_x: f64, merc_y: f64, state: State<AppState>) -> AddNodeResult {
-    // 墨卡托坐标转经纬度
+pub fn add_node(
+    merc_x: f64,
+    merc_y: f64,
+    snap_tolerance_meters: Option<f64>,
+    state: State<AppState>,
+) -> AddNodeResult {
+    let snap = snap_tolerance_meters
+        .and_then(|tolerance| snap::find_snap(&state.store, merc_x, merc_y, tolerance, None));
+
+    let segment = match snap {
+        Some(Snap::Node(node_id)) => {
+            return AddNodeResult {
+                success: true,
+                node_id,
+                message: None,
+                snapped: Some(SnapTarget::Node { node_id }),
+            }
+        }
+        Some(Snap::Way(segment)) => Some(segment),
+        None => None,
+    };
+
+    // 墨卡托坐标转经纬度（吸附到 Way 时使用线段上的投影点）
+    let (merc_x, merc_y) = segment.map_or((merc_x, merc_y), |s| s.point);
     let (lon, lat) = projection::mercator_to_lonlat(merc_x, merc_y);
 
     // 生成负数 ID
@@ -195,13 +553,190 @@ pub fn add_node(merc_x: f64, merc_y: f64, state: State<AppState>) -> AddNodeResu
         tags: Vec::new(),
     };
 
-    let command = AddNodeCommand { node };
+    let command: Box<dyn Command> = match &segment {
+        Some(segment) => Box::new(CompositeCommand {
+            label: "Add Node".to_string(),
+            children: vec![
+                Box::new(AddNodeCommand { node }),
+                Box::new(AttachNodeCommand {
+                    node_id,
+                    way_id: segment.way_id,
+                    index: segment.index + 1,
+                    old_lon: lon,
+                    old_lat: lat,
+                    new_lon: lon,
+                    new_lat: lat,
+                }),
+            ],
+        }),
+        None => Box::new(AddNodeCommand { node }),
+    };
+    let result = state.history.execute(command, &state.store);
+
+    AddNodeResult {
+        success: result.success,
+        node_id,
+        message: result.message,
+        snapped: segment.filter(|_| result.success).map(|s| snap_target(&s)),
+    }
+}
+
+/// 提取顶点时转交给新 POI 的 Relation 成员位置: (relation_id, indices)
+///
+/// `via` 角色描述的是路网拓扑（转向限制经过的路口），必须留在原顶点上
+fn extracted_memberships(store: &OsmStore, node_id: i64) -> Vec<(i64, Vec<usize>)> {
+    store
+        .find_relations_referencing(MemberType::Node, node_id)
+        .into_iter()
+        .filter_map(|relation_id| {
+            let relation = store.relations.get(&relation_id)?;
+            let indices: Vec<usize> = relation
+                .members
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| {
+                    m.member_type == MemberType::Node && m.ref_id == node_id && m.role != "via"
+                })
+                .map(|(i, _)| i)
+                .collect();
+            (!indices.is_empty()).then_some((relation_id, indices))
+        })
+        .collect()
+}
+
+/// 将带标签的顶点提取为独立 POI（使用命令模式支持撤销）
+///
+/// 新节点位于顶点位置加上偏移量（墨卡托米，为空时与顶点重合），
+/// 接管顶点的标签和 Relation 成员身份（`via` 角色除外）；原顶点以无标签形式留在 Way 中
+#[tauri::command]
+pub fn extract_node(
+    node_id: i64,
+    offset_x: Option<f64>,
+    offset_y: Option<f64>,
+    state: State<AppState>,
+) -> AddNodeResult {
+    let failure = |message: &str| AddNodeResult {
+        success: false,
+        node_id: 0,
+        message: Some(message.to_string()),
+        snapped: None,
+    };
+
+    let vertex = match state.store.nodes.get(&node_id) {
+        Some(node) => node.clone(),
+        None => return failure("Node not found"),
+    };
+    if vertex.tags.is_empty() {
+        return failure("Node has no tags to extract");
+    }
+    if state.store.find_ways_referencing_node(node_id).is_empty() {
+        return failure("Node is not a vertex of any way");
+    }
+
+    let (merc_x, merc_y) = projection::lonlat_to_mercator(vertex.lon, vertex.lat);
+    let (lon, lat) = projection::mercator_to_lonlat(
+        merc_x + offset_x.unwrap_or(0.0),
+        merc_y + offset_y.unwrap_or(0.0),
+    );
+
+    let poi = OsmNode {
+        id: state.store.generate_local_id(),
+        lon,
+        lat,
+        tags: vertex.tags,
+    };
+    let poi_id = poi.id;
+
+    let relation_members = extracted_memberships(&state.store, node_id);
+
+    let command = ExtractNodeCommand {
+        vertex_id: node_id,
+        poi,
+        relation_members,
+    };
     let result = state.history.execute(Box::new(command), &state.store);
 
     AddNodeResult {
         success: result.success,
+        node_id: poi_id,
+        message: result.message,
+        snapped: None,
+    }
+}
+
+/// 将独立节点附着到附近的 Way 上成为顶点（使用命令模式支持撤销）
+///
+/// 节点被移动到最近线段的投影点并插入该线段。
+/// `way_id` 为空时在 `tolerance_meters`（地面米，默认 10 米）范围内查找最近的 Way
+#[tauri::command]
+pub fn attach_node(
+    node_id: i64,
+    way_id: Option<i64>,
+    tolerance_meters: Option<f64>,
+    state: State<AppState>,
+) -> AttachNodeResult {
+    attach_node_impl(&state, node_id, way_id, tolerance_meters)
+}
+
+fn attach_node_impl(
+    state: &AppState,
+    node_id: i64,
+    way_id: Option<i64>,
+    tolerance_meters: Option<f64>,
+) -> AttachNodeResult {
+    let failure = |message: &str| AttachNodeResult {
+        success: false,
+        message: Some(message.to_string()),
+        way_id: None,
+    };
+
+    let node = match state.store.nodes.get(&node_id) {
+        Some(node) => node.clone(),
+        None => return failure("Node not found"),
+    };
+    if state.store.node_ref_count.get(&node_id).is_some_and(|c| *c > 0) {
+        return failure("Node is already part of a way");
+    }
+
+    let (merc_x, merc_y) = projection::lonlat_to_mercator(node.lon, node.lat);
+    let tolerance = match way_id {
+        // 指定了 Way 时不限制距离
+        Some(_) => f64::INFINITY,
+        None => {
+            tolerance_meters.unwrap_or(ATTACH_TOLERANCE_METERS)
+                * projection::mercator_scale_factor(node.lat)
+        }
+    };
+
+    // 节点不属于任何 Way，无需排除
+    let nearest = match spatial_query::nearest_way_segment(
+        &state.store,
+        merc_x,
+        merc_y,
+        tolerance,
+        way_id,
+        None,
+    ) {
+        Some(nearest) => nearest,
+        None => return failure("No way nearby"),
+    };
+
+    let (new_lon, new_lat) = projection::mercator_to_lonlat(nearest.point.0, nearest.point.1);
+    let command = AttachNodeCommand {
         node_id,
+        way_id: nearest.way_id,
+        index: nearest.index + 1,
+        old_lon: node.lon,
+        old_lat: node.lat,
+        new_lon,
+        new_lat,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    AttachNodeResult {
+        success: result.success,
         message: result.message,
+        way_id: Some(nearest.way_id),
     }
 }
 
@@ -214,19 +749,30 @@ pub fn delete_way(way_id: i64, state: State<AppState>) -> DeleteFeatureResult {
             success: false,
             message: Some("Way not found".to_string()),
             cascaded_way_ids: Vec::new(),
+            affected_relation_ids: Vec::new(),
+            cascaded_relation_ids: Vec::new(),
         };
     }
 
     let way = way.unwrap().clone();
     drop(state.store.ways.get(&way_id)); // 确保释放引用
 
-    let command = DeleteWayCommand { way };
+    let relation_cascade = RelationCascade::plan(&state.store, &[(MemberType::Way, way_id)]);
+    let affected_relation_ids = relation_cascade.affected_relation_ids();
+    let cascaded_relation_ids = relation_cascade.deleted_relation_ids();
+
+    let command = DeleteWayCommand {
+        way,
+        relation_cascade,
+    };
     let result = state.history.execute(Box::new(command), &state.store);
 
     DeleteFeatureResult {
         success: result.success,
         message: result.message,
         cascaded_way_ids: Vec::new(),
+        affected_relation_ids,
+        cascaded_relation_ids,
     }
 }
 
@@ -235,6 +781,7 @@ pub fn delete_way(way_id: i64, state: State<AppState>) -> DeleteFeatureResult {
 /// 删除节点时：
 /// 1. 从所有引用该节点的 Way 中移除
 /// 2. 如果 Way 只剩 1 个节点，级联删除该 Way
+/// 3. 从所有 Relation 中移除该节点和级联删除的 Way
 #[tauri::command]
 pub fn delete_node(node_id: i64, state: State<AppState>) -> DeleteFeatureResult {
     let node = state.store.nodes.get(&node_id);
@@ -243,6 +790,8 @@ pub fn delete_node(node_id: i64, state: State<AppState>) -> DeleteFeatureResult
             success: false,
             message: Some("Node not found".to_string()),
             cascaded_way_ids: Vec::new(),
+            affected_relation_ids: Vec::new(),
+            cascaded_relation_ids: Vec::new(),
         };
     }
 
@@ -279,10 +828,18 @@ pub fn delete_node(node_id: i64, state: State<AppState>) -> DeleteFeatureResult
 
     let cascaded_way_ids: Vec<i64> = cascaded_ways.iter().map(|w| w.id).collect();
 
+    // 节点本身和级联删除的 Way 都需要从 Relation 中移除
+    let mut removed_members = vec![(MemberType::Node, node_id)];
+    removed_members.extend(cascaded_way_ids.iter().map(|&id| (MemberType::Way, id)));
+    let relation_cascade = RelationCascade::plan(&state.store, &removed_members);
+    let affected_relation_ids = relation_cascade.affected_relation_ids();
+    let cascaded_relation_ids = relation_cascade.deleted_relation_ids();
+
     let command = DeleteNodeCommand {
         node,
         way_references,
         cascaded_ways,
+        relation_cascade,
     };
 
     let result = state.history.execute(Box::new(command), &state.store);
@@ -291,5 +848,129 @@ pub fn delete_node(node_id: i64, state: State<AppState>) -> DeleteFeatureResult
         success: result.success,
         message: result.message,
         cascaded_way_ids,
+        affected_relation_ids,
+        cascaded_relation_ids,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_store::{OsmRelation, RelationMember};
+
+    fn node(id: i64, lon: f64, lat: f64) -> OsmNode {
+        OsmNode {
+            id,
+            lon,
+            lat,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn test_attach_node_to_nearest_way() {
+        let state = AppState::default();
+        for (id, lon, lat) in [
+            (1, 0.0, 0.0),
+            (2, 0.001, 0.0),
+            (3, 0.0, 0.0002),
+            (4, 0.001, 0.0002),
+            (5, 0.0005, 0.00005),
+        ] {
+            state.store.insert_node(node(id, lon, lat));
+        }
+        let way = |id, node_refs| crate::osm_store::OsmWay {
+            id,
+            node_refs,
+            tags: vec![],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        };
+        state.store.insert_way(way(10, vec![1, 2]));
+        state.store.insert_way(way(11, vec![3, 4]));
+        state.store.rebuild_indices();
+
+        // 节点 1 已是顶点
+        assert!(!attach_node_impl(&state, 1, None, None).success);
+
+        // 指定 Way 时不限制距离
+        let attached = attach_node_impl(&state, 5, Some(11), Some(1.0));
+        assert!(attached.success);
+        assert_eq!(state.store.ways.get(&11).unwrap().node_refs, vec![3, 5, 4]);
+        state.history.undo(&state.store);
+
+        // 未指定时吸附到最近的 Way 10，并投影到线段上
+        let attached = attach_node_impl(&state, 5, None, None);
+        assert_eq!(attached.way_id, Some(10));
+        assert_eq!(state.store.ways.get(&10).unwrap().node_refs, vec![1, 5, 2]);
+        assert!(state.store.nodes.get(&5).unwrap().lat.abs() < 1e-9);
+        assert!(!attach_node_impl(&state, 5, None, None).success);
+    }
+
+    #[test]
+    fn test_jump_bounds_cover_before_and_after() {
+        let state = AppState::default();
+        state.store.insert_node(node(1, 0.0, 0.0));
+        state.store.rebuild_indices();
+
+        // 0: 新建节点 -1，1: 将节点 1 移到 (2, 2)
+        let add = AddNodeCommand {
+            node: node(-1, 5.0, 5.0),
+        };
+        assert!(state.history.execute(Box::new(add), &state.store).success);
+        let drag = MoveNodeCommand {
+            node_id: 1,
+            old_lon: 0.0,
+            old_lat: 0.0,
+            new_lon: 2.0,
+            new_lat: 2.0,
+        };
+        assert!(state.history.execute(Box::new(drag), &state.store).success);
+
+        // 撤销全部：节点 -1 被删除，边界仍需包含它原来的位置和节点 1 的前后位置
+        let undone = undo_to_impl(&state, 0);
+        assert!(undone.success);
+        assert!(!state.store.nodes.contains_key(&-1));
+        let bounds = undone.bounds.unwrap();
+        assert_eq!((bounds.min_lon, bounds.max_lon), (0.0, 5.0));
+
+        // 全部重做：边界同样包含节点 1 移动前的位置
+        let redone = redo_to_impl(&state, 1);
+        assert!(redone.success);
+        assert_eq!(state.store.nodes.get(&1).unwrap().lon, 2.0);
+        let bounds = redone.bounds.unwrap();
+        assert_eq!((bounds.min_lon, bounds.max_lon), (0.0, 5.0));
+    }
+
+    fn member(ref_id: i64, role: &str) -> RelationMember {
+        RelationMember {
+            member_type: MemberType::Node,
+            ref_id,
+            role: role.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_extract_keeps_via_memberships() {
+        let store = OsmStore::new();
+        store.relations.insert(
+            100,
+            OsmRelation {
+                id: 100,
+                members: vec![member(2, "via")],
+                tags: vec![("type".to_string(), "restriction".to_string())],
+            },
+        );
+        store.relations.insert(
+            101,
+            OsmRelation {
+                id: 101,
+                members: vec![member(1, "stop"), member(2, "stop")],
+                tags: vec![("type".to_string(), "route".to_string())],
+            },
+        );
+
+        assert_eq!(extracted_memberships(&store, 2), vec![(101, vec![1])]);
     }
 }
@@ -0,0 +1,199 @@
+//! 几何整形命令
+//!
+//! 处理正交化、圆形化、拉直、简化等针对 Way 形状的批量编辑操作
+
+use crate::circularize;
+use crate::history::{ReshapeCommand, StraightenCommand};
+use crate::orthogonalize;
+use crate::simplify::{self, SimplifyAlgorithm};
+use crate::straighten;
+use crate::types::{FeatureSelection, GeometryEditResult, SimplifyPreview};
+use crate::AppState;
+use tauri::State;
+
+fn geometry_failure(message: String) -> GeometryEditResult {
+    GeometryEditResult {
+        success: false,
+        message: Some(message),
+        moved_node_count: 0,
+        removed_node_count: 0,
+    }
+}
+
+/// 正交化选中的闭合 Way 和 Multipolygon（使用命令模式支持撤销）
+///
+/// `threshold_degrees` 为空时使用默认容差 (13°)
+#[tauri::command]
+pub fn orthogonalize(
+    selection: FeatureSelection,
+    threshold_degrees: Option<f64>,
+    state: State<AppState>,
+) -> GeometryEditResult {
+    let threshold = threshold_degrees.unwrap_or(orthogonalize::DEFAULT_THRESHOLD_DEGREES);
+
+    let plan = match orthogonalize::plan_orthogonalize(
+        &state.store,
+        &selection.way_ids,
+        &selection.relation_ids,
+        threshold,
+    ) {
+        Ok(plan) => plan,
+        Err(message) => return geometry_failure(message),
+    };
+
+    let moved_node_count = plan.moves.len();
+    let removed_node_count = plan.removed_nodes.len();
+    let command = ReshapeCommand {
+        label: "Orthogonalize".to_string(),
+        moves: plan.moves,
+        added_nodes: Vec::new(),
+        removed_nodes: plan.removed_nodes,
+        way_changes: plan.way_changes,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    GeometryEditResult {
+        success: result.success,
+        message: result.message,
+        moved_node_count,
+        removed_node_count,
+    }
+}
+
+/// 圆形化闭合 Way（使用命令模式支持撤销）
+///
+/// `max_step_degrees` 为相邻节点之间的最大圆心角，为空时使用默认值 (20°)
+#[tauri::command]
+pub fn circularize(
+    way_id: i64,
+    max_step_degrees: Option<f64>,
+    state: State<AppState>,
+) -> GeometryEditResult {
+    let max_step = max_step_degrees.unwrap_or(circularize::DEFAULT_MAX_STEP_DEGREES);
+
+    let plan = match circularize::plan_circularize(&state.store, way_id, max_step) {
+        Ok(plan) => plan,
+        Err(message) => return geometry_failure(message),
+    };
+
+    let moved_node_count = plan.moves.len();
+    let command = ReshapeCommand {
+        label: format!("Circularize Way #{}", way_id),
+        moves: plan.moves,
+        added_nodes: plan.added_nodes,
+        removed_nodes: Vec::new(),
+        way_changes: vec![plan.way_change],
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    GeometryEditResult {
+        success: result.success,
+        message: result.message,
+        moved_node_count,
+        removed_node_count: 0,
+    }
+}
+
+/// 拉直 Way 或其中一段顶点区间（使用命令模式支持撤销）
+///
+/// `from_index`/`to_index` 为空时拉直整条 Way；
+/// `max_deviation_ratio` 为空时使用默认值（首尾距离的 20%）
+#[tauri::command]
+pub fn straighten(
+    way_id: i64,
+    from_index: Option<usize>,
+    to_index: Option<usize>,
+    max_deviation_ratio: Option<f64>,
+    state: State<AppState>,
+) -> GeometryEditResult {
+    let range = match (from_index, to_index) {
+        (Some(from), Some(to)) => Some((from, to)),
+        (None, None) => None,
+        _ => return geometry_failure("Both range indices are required".to_string()),
+    };
+    let ratio = max_deviation_ratio.unwrap_or(straighten::DEFAULT_MAX_DEVIATION_RATIO);
+
+    let plan = match straighten::plan_straighten(&state.store, way_id, range, ratio) {
+        Ok(plan) => plan,
+        Err(message) => return geometry_failure(message),
+    };
+
+    let moved_node_count = plan.moves.len();
+    let removed_node_count = plan.removed_vertices.len();
+    let command = StraightenCommand {
+        way_id,
+        moves: plan.moves,
+        removed_vertices: plan.removed_vertices,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    GeometryEditResult {
+        success: result.success,
+        message: result.message,
+        moved_node_count,
+        removed_node_count,
+    }
+}
+
+/// 预览简化结果（不修改数据）
+#[tauri::command]
+pub fn preview_simplify(
+    selection: FeatureSelection,
+    tolerance_meters: f64,
+    algorithm: Option<SimplifyAlgorithm>,
+    state: State<AppState>,
+) -> Result<SimplifyPreview, String> {
+    let plan = simplify::plan_simplify(
+        &state.store,
+        &selection.way_ids,
+        tolerance_meters,
+        algorithm.unwrap_or_default(),
+    )?;
+
+    Ok(SimplifyPreview {
+        ways: plan.previews,
+        removed_node_count: plan.removed_nodes.len(),
+    })
+}
+
+/// 简化选中的 Way（使用命令模式支持撤销）
+///
+/// 只删除无标签、未被共享的顶点
+#[tauri::command]
+pub fn simplify_ways(
+    selection: FeatureSelection,
+    tolerance_meters: f64,
+    algorithm: Option<SimplifyAlgorithm>,
+    state: State<AppState>,
+) -> GeometryEditResult {
+    let plan = match simplify::plan_simplify(
+        &state.store,
+        &selection.way_ids,
+        tolerance_meters,
+        algorithm.unwrap_or_default(),
+    ) {
+        Ok(plan) => plan,
+        Err(message) => return geometry_failure(message),
+    };
+
+    if plan.removed_nodes.is_empty() {
+        return geometry_failure("Nothing to simplify".to_string());
+    }
+
+    let removed_node_count = plan.removed_nodes.len();
+    let command = ReshapeCommand {
+        label: "Simplify".to_string(),
+        moves: Vec::new(),
+        added_nodes: Vec::new(),
+        removed_nodes: plan.removed_nodes,
+        way_changes: plan.way_changes,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    GeometryEditResult {
+        success: result.success,
+        message: result.message,
+        moved_node_count: 0,
+        removed_node_count,
+    }
+}
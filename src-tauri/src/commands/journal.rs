@@ -0,0 +1,68 @@
+//! 崩溃恢复命令
+//!
+//! 查询、重放或丢弃上次异常退出时遗留的编辑日志
+
+use crate::journal;
+use crate::types::{JournalRecovery, JournalReplayResult};
+use crate::AppState;
+use tauri::State;
+
+/// 获取可恢复的遗留日志（加载 PBF 后调用，无遗留日志时返回 None）
+#[tauri::command]
+pub fn get_journal_recovery(state: State<AppState>) -> Option<JournalRecovery> {
+    let pending = state.pending_journal.lock().unwrap();
+    let (header, events) = pending.as_ref()?.pending()?;
+    Some(JournalRecovery {
+        source: header.source,
+        created: header.created,
+        event_count: events.len(),
+    })
+}
+
+/// 重放遗留日志，恢复编辑结果和撤销/重做栈，之后继续记录到同一日志
+#[tauri::command]
+pub fn replay_journal(state: State<AppState>) -> JournalReplayResult {
+    let source = state.pending_journal.lock().unwrap().take();
+    state.history.set_locked(false);
+    let (source, events) = match source.and_then(|s| s.pending().map(|(_, e)| (s, e))) {
+        Some(pending) => pending,
+        None => {
+            return JournalReplayResult {
+                success: false,
+                needs_redraw: false,
+                message: Some("No journal to replay".to_string()),
+                replayed: 0,
+                undo_count: state.history.undo_count(),
+                redo_count: state.history.redo_count(),
+            }
+        }
+    };
+
+    state.history.clear();
+    let replay = journal::replay(&state.history, &state.store, events);
+    state
+        .history
+        .set_journal(source.resume(&replay.events).ok());
+
+    JournalReplayResult {
+        success: replay.error.is_none(),
+        needs_redraw: replay.needs_redraw,
+        message: replay.error,
+        replayed: replay.events.len(),
+        undo_count: state.history.undo_count(),
+        redo_count: state.history.redo_count(),
+    }
+}
+
+/// 丢弃遗留日志并开始新的日志
+#[tauri::command]
+pub fn discard_journal(state: State<AppState>) -> bool {
+    state.history.set_locked(false);
+    match state.pending_journal.lock().unwrap().take() {
+        Some(source) => {
+            state.history.set_journal(source.start().ok());
+            true
+        }
+        None => false,
+    }
+}
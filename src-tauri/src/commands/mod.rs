@@ -2,10 +2,20 @@
 //!
 //! 按功能分组的命令处理器
 
+mod changes;
+mod clipboard;
 mod data;
 mod editing;
+mod geometry;
+mod journal;
 mod query;
+mod relation;
 
+pub use changes::*;
+pub use clipboard::*;
 pub use data::*;
 pub use editing::*;
+pub use geometry::*;
+pub use journal::*;
 pub use query::*;
+pub use relation::*;
@@ -1,10 +1,17 @@
 //! 空间查询命令
 //!
-//! 处理视口查询、要素拾取和详情获取
+//! 处理视口查询、要素拾取、搜索和详情获取
 
+use crate::geojson::{self, GeometryMode};
 use crate::osm_store::{MemberType, OsmStore};
-use crate::spatial_query::{self, PickedFeature, Viewport};
-use crate::types::{FeatureDetails, NodeDetails, ParentRelation, WayDetails};
+use crate::overpass::{self, OutMode};
+use crate::search::{self, SearchQuery};
+use crate::spatial_query::{self, PickCandidate, Viewport};
+use crate::types::{
+    AreaAtPointInfo, FeatureDetails, FeatureSelection, NodeDetails, OverpassQueryResult,
+    ParentRelation, RelationDetails, RelationMemberDetails, SearchFeaturesResult, SearchHit,
+    WayDetails,
+};
 use crate::{binary_protocol, AppState};
 use tauri::State;
 
@@ -12,7 +19,8 @@ use tauri::State;
 #[tauri::command]
 pub fn query_viewport_nodes(viewport: Viewport, state: State<AppState>) -> Vec<u8> {
     let result = spatial_query::query_viewport(&state.store, &viewport);
-    binary_protocol::encode_priority_nodes(&result.nodes)
+    let changed = state.history.changed_set(&state.store);
+    binary_protocol::encode_priority_nodes(&result.nodes, &changed)
 }
 
 /// 查询视口内的坐标 (纯坐标，用于渲染) - 已弃用，使用 query_viewport_full
@@ -27,10 +35,11 @@ pub fn query_viewport_coords(viewport: Viewport, state: State<AppState>) -> Vec<
     buffer
 }
 
-/// 查询视口内的完整数据 (V4: 带节点优先级 + Polygon)
+/// 查询视口内的完整数据 (V4: 带节点优先级 + Polygon + 编辑标记)
 #[tauri::command]
 pub fn query_viewport_full(viewport: Viewport, state: State<AppState>) -> Vec<u8> {
     let result = spatial_query::query_viewport(&state.store, &viewport);
+    let changed = state.history.changed_set(&state.store);
 
     binary_protocol::build_viewport_response_v4(
         &state.store,
@@ -38,10 +47,11 @@ pub fn query_viewport_full(viewport: Viewport, state: State<AppState>) -> Vec<u8
         &result.way_ids,
         &result.polygons,
         result.truncated,
+        &changed,
     )
 }
 
-/// 空间拾取：在指定坐标查找最近的要素
+/// 空间拾取：在指定坐标查找候选要素（Node、Way、Relation 和面内部），按优先级排列
 #[tauri::command]
 pub fn pick_feature(
     merc_x: f64,
@@ -49,8 +59,176 @@ pub fn pick_feature(
     tolerance_meters: f64,
     zoom: f64,
     state: State<AppState>,
-) -> PickedFeature {
-    spatial_query::pick_feature(&state.store, merc_x, merc_y, tolerance_meters, zoom)
+) -> Vec<PickCandidate> {
+    let parents = state.history.relation_parents(&state.store);
+    let area_relations = state.history.area_relation_index(&state.store);
+    spatial_query::pick_feature(
+        &state.store,
+        &parents,
+        &area_relations,
+        merc_x,
+        merc_y,
+        tolerance_meters,
+        zoom,
+    )
+}
+
+/// 查询包含点击位置的全部面（闭合 Way 和 multipolygon/boundary Relation），按面积从小到大排列
+#[tauri::command]
+pub fn query_areas_at_point(
+    merc_x: f64,
+    merc_y: f64,
+    state: State<AppState>,
+) -> Vec<AreaAtPointInfo> {
+    let area_relations = state.history.area_relation_index(&state.store);
+    spatial_query::areas_at_point(&state.store, &area_relations, merc_x, merc_y)
+        .into_iter()
+        .filter_map(|area| {
+            let tags = state.store.feature_tags(area.member_type, area.id)?;
+            let admin_level = tags
+                .iter()
+                .find(|(k, _)| k == "admin_level")
+                .and_then(|(_, v)| v.parse().ok());
+            Some(AreaAtPointInfo {
+                member_type: area.member_type,
+                id: area.id,
+                tags,
+                admin_level,
+                area_sq_meters: area.area,
+            })
+        })
+        .collect()
+}
+
+/// 每页默认结果数
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+/// 每页最大结果数
+const MAX_SEARCH_LIMIT: usize = 500;
+
+/// 要素的 name 标签
+fn feature_name(store: &OsmStore, member_type: MemberType, id: i64) -> Option<String> {
+    store
+        .feature_tags(member_type, id)?
+        .into_iter()
+        .find(|(k, _)| k == "name")
+        .map(|(_, v)| v)
+}
+
+/// 按名称或标签搜索要素
+///
+/// 支持自由文本（name/name:*/ref/addr:*）、`key=value`、`key=*` 和 `key~regex`；
+/// 结果按与视口中心的距离排序，`offset`/`limit` 用于分页
+#[tauri::command]
+pub fn search_features(
+    query: String,
+    viewport: Viewport,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    state: State<AppState>,
+) -> SearchFeaturesResult {
+    let query = match SearchQuery::parse(&query) {
+        Ok(query) => query,
+        Err(message) => {
+            return SearchFeaturesResult {
+                success: false,
+                message: Some(message),
+                total: 0,
+                hits: Vec::new(),
+            };
+        }
+    };
+
+    let center = (
+        (viewport.min_lon + viewport.max_lon) / 2.0,
+        (viewport.min_lat + viewport.max_lat) / 2.0,
+    );
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT).min(MAX_SEARCH_LIMIT);
+    let results = search::search(&state.store, &query, center, offset.saturating_add(limit));
+    let total = results.total;
+    let hits = results
+        .features
+        .into_iter()
+        .skip(offset)
+        .map(|feature| SearchHit {
+            member_type: feature.member_type,
+            id: feature.id,
+            name: feature_name(&state.store, feature.member_type, feature.id),
+            matched_tag: feature.matched_tag,
+            lon: feature.location.map(|(lon, _)| lon),
+            lat: feature.location.map(|(_, lat)| lat),
+            distance_meters: feature.distance,
+        })
+        .collect();
+
+    SearchFeaturesResult {
+        success: true,
+        message: None,
+        total,
+        hits,
+    }
+}
+
+/// `out` 模式对应的 GeoJSON 内容（几何方式，是否带标签）；`out count` 不输出要素
+fn geojson_content(mode: OutMode) -> Option<(GeometryMode, bool)> {
+    match mode {
+        OutMode::Ids => Some((GeometryMode::Omit, false)),
+        OutMode::Skel => Some((GeometryMode::Full, false)),
+        OutMode::Body | OutMode::Geom => Some((GeometryMode::Full, true)),
+        OutMode::Tags => Some((GeometryMode::Omit, true)),
+        OutMode::Center => Some((GeometryMode::Center, true)),
+        OutMode::Count => None,
+    }
+}
+
+/// 在已加载数据上执行 Overpass QL 查询
+///
+/// 返回全部 `out` 语句输出的要素（可直接用于选择）；`geojson` 为 true 时
+/// 同时按各 `out` 语句的输出模式生成 GeoJSON FeatureCollection
+#[tauri::command]
+pub fn run_overpass_query(
+    query: String,
+    geojson: Option<bool>,
+    state: State<AppState>,
+) -> OverpassQueryResult {
+    let outputs = match overpass::run(&state.store, &query) {
+        Ok(outputs) => outputs,
+        Err(message) => {
+            return OverpassQueryResult {
+                success: false,
+                message: Some(message),
+                count: 0,
+                selection: FeatureSelection::default(),
+                geojson: None,
+            };
+        }
+    };
+
+    let with_geojson = geojson.unwrap_or(false);
+    let mut count = 0;
+    let mut selection = FeatureSelection::default();
+    let mut features = Vec::new();
+    for output in &outputs {
+        count += output.elements.count();
+        let Some((mode, with_tags)) = geojson_content(output.mode) else {
+            continue;
+        };
+        selection.extend(output.elements.selection());
+        if with_geojson {
+            features.extend(output.elements.iter().filter_map(|(member_type, id)| {
+                geojson::feature(&state.store, member_type, id, mode, with_tags)
+            }));
+        }
+    }
+    selection.dedup();
+
+    OverpassQueryResult {
+        success: true,
+        message: None,
+        count,
+        selection,
+        geojson: with_geojson.then(|| geojson::feature_collection(features)),
+    }
 }
 
 /// 查找包含指定要素的所有 Relation
@@ -138,3 +316,138 @@ pub fn get_way_details(way_id: i64, state: State<AppState>) -> FeatureDetails {
         FeatureDetails::NotFound
     }
 }
+
+/// 解析成员要素的显示名称
+///
+/// 优先级: name > ref > "Type #id"；返回值第二项表示要素是否已加载
+fn resolve_member_name(store: &OsmStore, member_type: MemberType, ref_id: i64) -> (String, bool) {
+    let pick_name = |tags: &[(String, String)]| {
+        tags.iter()
+            .find(|(k, _)| k == "name")
+            .or_else(|| tags.iter().find(|(k, _)| k == "ref"))
+            .map(|(_, v)| v.clone())
+    };
+
+    let (name, loaded) = match member_type {
+        MemberType::Node => match store.nodes.get(&ref_id) {
+            Some(node) => (pick_name(&node.tags), true),
+            None => (None, false),
+        },
+        MemberType::Way => match store.ways.get(&ref_id) {
+            Some(way) => (pick_name(&way.tags), true),
+            None => (None, false),
+        },
+        MemberType::Relation => match store.relations.get(&ref_id) {
+            Some(relation) => (pick_name(&relation.tags), true),
+            None => (None, false),
+        },
+    };
+
+    let display_name = name.unwrap_or_else(|| format!("{:?} #{}", member_type, ref_id));
+    (display_name, loaded)
+}
+
+/// 获取 Relation 详情（含成员角色、类型和显示名称）
+#[tauri::command]
+pub fn get_relation_details(relation_id: i64, state: State<AppState>) -> FeatureDetails {
+    relation_details(&state.store, relation_id)
+}
+
+fn relation_details(store: &OsmStore, relation_id: i64) -> FeatureDetails {
+    let relation = match store.relations.get(&relation_id) {
+        Some(r) => r.clone(),
+        None => return FeatureDetails::NotFound,
+    };
+
+    let members = relation
+        .members
+        .iter()
+        .map(|member| {
+            let (display_name, loaded) =
+                resolve_member_name(store, member.member_type, member.ref_id);
+            RelationMemberDetails {
+                member_type: member.member_type,
+                ref_id: member.ref_id,
+                role: member.role.clone(),
+                display_name,
+                loaded,
+            }
+        })
+        .collect();
+
+    let parent_relations = find_parent_relations(store, MemberType::Relation, relation_id);
+
+    FeatureDetails::Relation(RelationDetails {
+        id: relation.id,
+        tags: relation.tags,
+        members,
+        parent_relations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_store::{OsmNode, OsmRelation, RelationMember};
+
+    fn member(member_type: MemberType, ref_id: i64, role: &str) -> RelationMember {
+        RelationMember {
+            member_type,
+            ref_id,
+            role: role.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_relation_details() {
+        let store = OsmStore::new();
+        store.insert_node(OsmNode {
+            id: 1,
+            lon: 0.0,
+            lat: 0.0,
+            tags: vec![("name".to_string(), "Stop A".to_string())],
+        });
+        store.relations.insert(
+            10,
+            OsmRelation {
+                id: 10,
+                members: vec![
+                    member(MemberType::Node, 1, "stop"),
+                    member(MemberType::Way, 99, ""),
+                ],
+                tags: vec![("type".to_string(), "route".to_string())],
+            },
+        );
+        store.relations.insert(
+            20,
+            OsmRelation {
+                id: 20,
+                members: vec![member(MemberType::Relation, 10, "")],
+                tags: vec![
+                    ("type".to_string(), "route_master".to_string()),
+                    ("name".to_string(), "Line 1".to_string()),
+                ],
+            },
+        );
+
+        let FeatureDetails::Relation(details) = relation_details(&store, 10) else {
+            panic!("expected relation details");
+        };
+        assert_eq!(details.members.len(), 2);
+        assert_eq!(details.members[0].display_name, "Stop A");
+        assert!(details.members[0].loaded);
+        assert_eq!(details.members[1].display_name, "Way #99");
+        assert!(!details.members[1].loaded);
+        assert_eq!(details.parent_relations.len(), 1);
+        assert_eq!(details.parent_relations[0].id, 20);
+        assert_eq!(
+            details.parent_relations[0].relation_type.as_deref(),
+            Some("route_master")
+        );
+
+        assert!(matches!(
+            relation_details(&store, 30),
+            FeatureDetails::NotFound
+        ));
+    }
+}
@@ -0,0 +1,1024 @@
+//! Relation 编辑命令
+//!
+//! 处理 Relation 的创建、删除、成员编辑和标签编辑，以及转向限制的创建和编辑
+
+use crate::history::{
+    AddRelationMemberCommand, BatchUpdateTagsCommand, Command, CommandResult, CompositeCommand,
+    CreateRelationCommand, DeleteRelationCommand, MoveRelationMemberCommand, RelationCascade,
+    RemoveRelationMemberCommand, SetRelationMembersCommand, UpdateMemberRoleCommand,
+    UpdateRelationTagsCommand,
+};
+use crate::multipolygon;
+use crate::osm_store::{MemberType, OsmRelation, OsmStore, RelationMember};
+use crate::render_feature;
+use crate::restriction;
+use crate::split;
+use crate::types::{
+    DeleteFeatureResult, RelationEditResult, TurnRestrictionInfo, TurnRestrictionLegs,
+    UpdateTagsResult,
+};
+use crate::AppState;
+use std::collections::HashSet;
+use tauri::State;
+
+fn edit_result(result: CommandResult, relation_id: i64) -> RelationEditResult {
+    RelationEditResult {
+        success: result.success,
+        relation_id,
+        message: result.message,
+    }
+}
+
+fn edit_failure(relation_id: i64, message: &str) -> RelationEditResult {
+    edit_result(CommandResult::failure(message), relation_id)
+}
+
+/// 创建 Relation（使用命令模式支持撤销）
+///
+/// 至少需要一个成员，且所有成员必须已存在于当前数据中
+#[tauri::command]
+pub fn create_relation(
+    tags: Vec<(String, String)>,
+    members: Vec<RelationMember>,
+    state: State<AppState>,
+) -> RelationEditResult {
+    create_relation_impl(&state, tags, members)
+}
+
+fn create_relation_impl(
+    state: &AppState,
+    tags: Vec<(String, String)>,
+    members: Vec<RelationMember>,
+) -> RelationEditResult {
+    // 没有成员的 Relation 无效（见 `OsmRelation::is_valid`），不允许创建
+    if members.is_empty() {
+        return edit_failure(0, "Relation must have at least one member");
+    }
+    if let Some(missing) = members
+        .iter()
+        .find(|m| !state.store.contains_feature(m.member_type, m.ref_id))
+    {
+        return edit_failure(
+            0,
+            &format!("{:?} #{} not found", missing.member_type, missing.ref_id),
+        );
+    }
+
+    let relation_id = state.store.generate_local_id();
+    let relation = OsmRelation {
+        id: relation_id,
+        members,
+        tags,
+    };
+
+    let command = CreateRelationCommand { relation };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    edit_result(result, relation_id)
+}
+
+/// 由闭合 Way 创建 Multipolygon Relation（使用命令模式支持撤销）
+///
+/// 按几何嵌套关系分配 outer/inner 角色；`move_tags` 为 true 时
+/// 将 outer Way 共有的标签移动到 Relation 上，两者作为一个历史记录
+#[tauri::command]
+pub fn create_multipolygon(
+    way_ids: Vec<i64>,
+    move_tags: Option<bool>,
+    state: State<AppState>,
+) -> RelationEditResult {
+    let relation_id = state.store.generate_local_id();
+    let plan = match multipolygon::plan_multipolygon(
+        &state.store,
+        &way_ids,
+        relation_id,
+        move_tags.unwrap_or(false),
+    ) {
+        Ok(plan) => plan,
+        Err(message) => return edit_failure(0, &message),
+    };
+
+    let mut children: Vec<Box<dyn Command>> = vec![Box::new(CreateRelationCommand {
+        relation: plan.relation,
+    })];
+    if !plan.tag_changes.is_empty() {
+        children.push(Box::new(BatchUpdateTagsCommand {
+            label: "Move tags to multipolygon".to_string(),
+            changes: plan.tag_changes,
+        }));
+    }
+    let command = CompositeCommand {
+        label: "Create multipolygon".to_string(),
+        children,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    edit_result(result, relation_id)
+}
+
+/// 在事务中执行多步编辑，失败时回滚
+///
+/// 已有事务进行中时直接在其中执行，由调用方决定提交或回滚
+fn in_transaction<T>(
+    state: &AppState,
+    label: &str,
+    edit: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    let owned = !state.history.in_transaction();
+    if owned {
+        state.history.begin_transaction(label);
+    }
+    let result = edit();
+    if owned {
+        if result.is_ok() {
+            state.history.commit_transaction();
+        } else {
+            state.history.abort_transaction(&state.store);
+        }
+    }
+    result
+}
+
+fn execute(state: &AppState, command: Box<dyn Command>) -> Result<(), String> {
+    let result = state.history.execute(command, &state.store);
+    if result.success {
+        Ok(())
+    } else {
+        Err(result.message.unwrap_or_default())
+    }
+}
+
+/// 确保 Way 以指定节点为端点，否则在该节点处拆分，返回 `side` 所在的一段
+///
+/// `side` 为该段上除 `node_id` 以外的任一节点；Way 穿过节点、需要拆分时，
+/// 两段都可能是转向的一侧，未指定 `side` 则返回错误
+fn connect_leg(
+    state: &AppState,
+    way_id: i64,
+    node_id: i64,
+    side: Option<i64>,
+) -> Result<i64, String> {
+    let node_refs = match state.store.ways.get(&way_id) {
+        Some(way) => way.node_refs.clone(),
+        None => return Err(format!("Way #{} not found", way_id)),
+    };
+    if let Some(side) = side.filter(|side| !node_refs.contains(side)) {
+        return Err(format!("Node #{} is not on Way #{}", side, way_id));
+    }
+    if node_refs.first() == Some(&node_id) || node_refs.last() == Some(&node_id) {
+        return Ok(way_id);
+    }
+    let side = match side {
+        Some(side) if side != node_id => side,
+        _ => {
+            return Err(format!(
+                "Way #{} passes through Node #{}; specify which side to use",
+                way_id, node_id
+            ))
+        }
+    };
+
+    let command = split::plan_split(
+        &state.store,
+        way_id,
+        node_id,
+        state.store.generate_local_id(),
+    )?;
+    let new_way_id = command.new_way.id;
+    let on_new_way = command.new_way.node_refs.contains(&side);
+    execute(state, Box::new(command))?;
+    Ok(if on_new_way { new_way_id } else { way_id })
+}
+
+/// 计算转向限制的 from/via/to 成员，必要时在 via 处拆分 from/to Way
+///
+/// via 为 Way 时，from 连接 via 的一端，to 连接另一端
+fn restriction_members(
+    state: &AppState,
+    legs: &TurnRestrictionLegs,
+) -> Result<Vec<RelationMember>, String> {
+    let &TurnRestrictionLegs {
+        from_way,
+        from_side,
+        via_type,
+        via_id,
+        to_way,
+        to_side,
+    } = legs;
+    let way_refs = |way_id: i64| match state.store.ways.get(&way_id) {
+        Some(way) => Ok(way.node_refs.clone()),
+        None => Err(format!("Way #{} not found", way_id)),
+    };
+    let from_refs = way_refs(from_way)?;
+    let to_refs = way_refs(to_way)?;
+
+    let (from_node, to_node) = match via_type {
+        MemberType::Node if state.store.nodes.contains_key(&via_id) => (via_id, via_id),
+        MemberType::Way => {
+            let via_refs = way_refs(via_id)?;
+            let (first, last) = match (via_refs.first(), via_refs.last()) {
+                (Some(&first), Some(&last)) => (first, last),
+                _ => return Err(format!("Via way #{} has no nodes", via_id)),
+            };
+            if from_refs.contains(&first) {
+                (first, last)
+            } else {
+                (last, first)
+            }
+        }
+        _ => return Err(format!("{:?} #{} not found", via_type, via_id)),
+    };
+    if !from_refs.contains(&from_node) {
+        return Err("From way is not connected to via".to_string());
+    }
+    if !to_refs.contains(&to_node) {
+        return Err("To way is not connected to via".to_string());
+    }
+
+    // from 与 to 为同一 Way 时是掉头，to 与拆分后的 from 段相同
+    let from = connect_leg(state, from_way, from_node, from_side)?;
+    let to = if to_way == from_way {
+        from
+    } else {
+        connect_leg(state, to_way, to_node, to_side)?
+    };
+
+    let member = |member_type: MemberType, ref_id: i64, role: &str| RelationMember {
+        member_type,
+        ref_id,
+        role: role.to_string(),
+    };
+    Ok(vec![
+        member(MemberType::Way, from, "from"),
+        member(via_type, via_id, "via"),
+        member(MemberType::Way, to, "to"),
+    ])
+}
+
+fn check_restriction_value(restriction: &str) -> Result<(), String> {
+    if restriction::KNOWN_RESTRICTIONS.contains(&restriction) {
+        Ok(())
+    } else {
+        Err(format!("Unknown restriction value: {}", restriction))
+    }
+}
+
+/// 创建转向限制（使用命令模式支持撤销）
+///
+/// from/to Way 穿过 via 节点时自动在该处拆分（需要 `from_side` / `to_side`
+/// 指明转向的一侧），拆分与创建作为一个历史记录
+#[tauri::command]
+pub fn create_turn_restriction(
+    legs: TurnRestrictionLegs,
+    restriction: String,
+    state: State<AppState>,
+) -> RelationEditResult {
+    create_turn_restriction_impl(&state, &legs, restriction)
+}
+
+fn create_turn_restriction_impl(
+    state: &AppState,
+    legs: &TurnRestrictionLegs,
+    restriction: String,
+) -> RelationEditResult {
+    if let Err(message) = check_restriction_value(&restriction) {
+        return edit_failure(0, &message);
+    }
+
+    let relation_id = state.store.generate_local_id();
+    let result = in_transaction(state, "Create turn restriction", || {
+        let members = restriction_members(state, legs)?;
+        let relation = OsmRelation {
+            id: relation_id,
+            members,
+            tags: vec![
+                ("type".to_string(), "restriction".to_string()),
+                ("restriction".to_string(), restriction),
+            ],
+        };
+        execute(state, Box::new(CreateRelationCommand { relation }))
+    });
+
+    match result {
+        Ok(()) => edit_result(CommandResult::success(true), relation_id),
+        Err(message) => edit_failure(0, &message),
+    }
+}
+
+/// 修改转向限制的 from/via/to 和 restriction 取值（使用命令模式支持撤销）
+///
+/// 成员按 from/via/to 重建（其他角色的成员保留在末尾），必要的拆分自动执行
+#[tauri::command]
+pub fn update_turn_restriction(
+    relation_id: i64,
+    legs: TurnRestrictionLegs,
+    restriction: String,
+    state: State<AppState>,
+) -> RelationEditResult {
+    update_turn_restriction_impl(&state, relation_id, &legs, restriction)
+}
+
+fn update_turn_restriction_impl(
+    state: &AppState,
+    relation_id: i64,
+    legs: &TurnRestrictionLegs,
+    restriction: String,
+) -> RelationEditResult {
+    match state.store.relations.get(&relation_id) {
+        Some(r) if r.tag("type") == Some("restriction") => {}
+        Some(_) => return edit_failure(relation_id, "Relation is not a turn restriction"),
+        None => return edit_failure(relation_id, "Relation not found"),
+    }
+    if let Err(message) = check_restriction_value(&restriction) {
+        return edit_failure(relation_id, &message);
+    }
+
+    let result = in_transaction(state, "Edit turn restriction", || {
+        let mut new_members = restriction_members(state, legs)?;
+        // 拆分可能已更新成员，重新读取
+        let relation = match state.store.relations.get(&relation_id) {
+            Some(r) => r.clone(),
+            None => return Err("Relation not found".to_string()),
+        };
+        new_members.extend(
+            relation
+                .members
+                .iter()
+                .filter(|m| !matches!(m.role.as_str(), "from" | "via" | "to"))
+                .cloned(),
+        );
+        if new_members != relation.members {
+            execute(
+                state,
+                Box::new(SetRelationMembersCommand {
+                    relation_id,
+                    old_members: relation.members.clone(),
+                    new_members,
+                }),
+            )?;
+        }
+
+        let mut new_tags = relation.tags.clone();
+        match new_tags.iter_mut().find(|(k, _)| k == "restriction") {
+            Some(tag) => tag.1 = restriction,
+            None => new_tags.push(("restriction".to_string(), restriction)),
+        }
+        if new_tags != relation.tags {
+            execute(
+                state,
+                Box::new(UpdateRelationTagsCommand {
+                    relation_id,
+                    old_tags: relation.tags,
+                    new_tags,
+                }),
+            )?;
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => edit_result(CommandResult::success(true), relation_id),
+        Err(message) => edit_failure(relation_id, &message),
+    }
+}
+
+/// 列出路口节点处的转向限制及其有效性
+#[tauri::command]
+pub fn get_junction_restrictions(node_id: i64, state: State<AppState>) -> Vec<TurnRestrictionInfo> {
+    restriction::restrictions_at_node(&state.store, node_id)
+        .into_iter()
+        .filter_map(|relation_id| {
+            let relation = state.store.relations.get(&relation_id)?.clone();
+            let issues = restriction::validate(&state.store, &relation);
+            let way_ids = |role: &str| {
+                relation
+                    .members
+                    .iter()
+                    .filter(|m| m.role == role && m.member_type == MemberType::Way)
+                    .map(|m| m.ref_id)
+                    .collect()
+            };
+            Some(TurnRestrictionInfo {
+                relation_id,
+                restriction: restriction::restriction_value(&relation).map(str::to_string),
+                from_way_ids: way_ids("from"),
+                via_node_id: relation
+                    .members
+                    .iter()
+                    .find(|m| m.role == "via" && m.member_type == MemberType::Node)
+                    .map(|m| m.ref_id),
+                via_way_ids: way_ids("via"),
+                to_way_ids: way_ids("to"),
+                valid: issues.is_empty(),
+                issues,
+            })
+        })
+        .collect()
+}
+
+/// 删除 Relation（使用命令模式支持撤销，含上级 Relation 级联处理）
+#[tauri::command]
+pub fn delete_relation(relation_id: i64, state: State<AppState>) -> DeleteFeatureResult {
+    delete_relation_impl(&state, relation_id)
+}
+
+fn delete_relation_impl(state: &AppState, relation_id: i64) -> DeleteFeatureResult {
+    let relation = match state.store.relations.get(&relation_id) {
+        Some(r) => r.clone(),
+        None => {
+            return DeleteFeatureResult {
+                success: false,
+                message: Some("Relation not found".to_string()),
+                cascaded_way_ids: Vec::new(),
+                affected_relation_ids: Vec::new(),
+                cascaded_relation_ids: Vec::new(),
+            };
+        }
+    };
+
+    let relation_cascade =
+        RelationCascade::plan(&state.store, &[(MemberType::Relation, relation_id)]);
+    let affected_relation_ids = relation_cascade.affected_relation_ids();
+    let cascaded_relation_ids = relation_cascade.deleted_relation_ids();
+
+    let command = DeleteRelationCommand {
+        relation,
+        relation_cascade,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    DeleteFeatureResult {
+        success: result.success,
+        message: result.message,
+        cascaded_way_ids: Vec::new(),
+        affected_relation_ids,
+        cascaded_relation_ids,
+    }
+}
+
+/// 将 `member_id` 加入 `relation_id` 后是否形成环
+///
+/// 即 `member_id` 就是 `relation_id`，或直接、间接地包含 `relation_id`
+fn creates_cycle(store: &OsmStore, relation_id: i64, member_id: i64) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![member_id];
+    while let Some(id) = stack.pop() {
+        if id == relation_id {
+            return true;
+        }
+        if !visited.insert(id) {
+            continue;
+        }
+        if let Some(relation) = store.relations.get(&id) {
+            stack.extend(
+                relation
+                    .members
+                    .iter()
+                    .filter(|m| m.member_type == MemberType::Relation)
+                    .map(|m| m.ref_id),
+            );
+        }
+    }
+    false
+}
+
+/// 添加 Relation 成员
+///
+/// `index` 为空时追加到末尾；会形成 Relation 环（A 包含 B、B 包含 A）的成员被拒绝
+#[tauri::command]
+pub fn add_relation_member(
+    relation_id: i64,
+    member: RelationMember,
+    index: Option<usize>,
+    state: State<AppState>,
+) -> RelationEditResult {
+    add_relation_member_impl(&state, relation_id, member, index)
+}
+
+fn add_relation_member_impl(
+    state: &AppState,
+    relation_id: i64,
+    member: RelationMember,
+    index: Option<usize>,
+) -> RelationEditResult {
+    let member_count = match state.store.relations.get(&relation_id) {
+        Some(r) => r.members.len(),
+        None => return edit_failure(relation_id, "Relation not found"),
+    };
+
+    if !state
+        .store
+        .contains_feature(member.member_type, member.ref_id)
+    {
+        return edit_failure(
+            relation_id,
+            &format!("{:?} #{} not found", member.member_type, member.ref_id),
+        );
+    }
+
+    if member.member_type == MemberType::Relation {
+        if member.ref_id == relation_id {
+            return edit_failure(relation_id, "Relation cannot contain itself");
+        }
+        if creates_cycle(&state.store, relation_id, member.ref_id) {
+            return edit_failure(
+                relation_id,
+                &format!(
+                    "Relation #{} already contains Relation #{}",
+                    member.ref_id, relation_id
+                ),
+            );
+        }
+    }
+
+    let command = AddRelationMemberCommand {
+        relation_id,
+        index: index.unwrap_or(member_count),
+        member,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    edit_result(result, relation_id)
+}
+
+/// 移除 Relation 成员
+#[tauri::command]
+pub fn remove_relation_member(
+    relation_id: i64,
+    index: usize,
+    state: State<AppState>,
+) -> RelationEditResult {
+    remove_relation_member_impl(&state, relation_id, index)
+}
+
+fn remove_relation_member_impl(
+    state: &AppState,
+    relation_id: i64,
+    index: usize,
+) -> RelationEditResult {
+    let member = match state.store.relations.get(&relation_id) {
+        Some(r) => match r.members.get(index) {
+            Some(m) => m.clone(),
+            None => return edit_failure(relation_id, "Member index out of range"),
+        },
+        None => return edit_failure(relation_id, "Relation not found"),
+    };
+
+    let command = RemoveRelationMemberCommand {
+        relation_id,
+        index,
+        member,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    edit_result(result, relation_id)
+}
+
+/// 调整 Relation 成员顺序
+#[tauri::command]
+pub fn move_relation_member(
+    relation_id: i64,
+    from_index: usize,
+    to_index: usize,
+    state: State<AppState>,
+) -> RelationEditResult {
+    move_relation_member_impl(&state, relation_id, from_index, to_index)
+}
+
+fn move_relation_member_impl(
+    state: &AppState,
+    relation_id: i64,
+    from_index: usize,
+    to_index: usize,
+) -> RelationEditResult {
+    if from_index == to_index {
+        return edit_failure(relation_id, "Member is already at the target position");
+    }
+
+    let command = MoveRelationMemberCommand {
+        relation_id,
+        from_index,
+        to_index,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    edit_result(result, relation_id)
+}
+
+/// 修改 Relation 成员角色
+#[tauri::command]
+pub fn update_member_role(
+    relation_id: i64,
+    index: usize,
+    role: String,
+    state: State<AppState>,
+) -> RelationEditResult {
+    update_member_role_impl(&state, relation_id, index, role)
+}
+
+fn update_member_role_impl(
+    state: &AppState,
+    relation_id: i64,
+    index: usize,
+    role: String,
+) -> RelationEditResult {
+    let old_role = match state.store.relations.get(&relation_id) {
+        Some(r) => match r.members.get(index) {
+            Some(m) => m.role.clone(),
+            None => return edit_failure(relation_id, "Member index out of range"),
+        },
+        None => return edit_failure(relation_id, "Relation not found"),
+    };
+
+    let command = UpdateMemberRoleCommand {
+        relation_id,
+        index,
+        old_role,
+        new_role: role,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    edit_result(result, relation_id)
+}
+
+/// 更新 Relation 标签（使用命令模式支持撤销）
+#[tauri::command]
+pub fn update_relation_tags(
+    relation_id: i64,
+    new_tags: Vec<(String, String)>,
+    state: State<AppState>,
+) -> UpdateTagsResult {
+    update_relation_tags_impl(&state, relation_id, new_tags)
+}
+
+fn update_relation_tags_impl(
+    state: &AppState,
+    relation_id: i64,
+    new_tags: Vec<(String, String)>,
+) -> UpdateTagsResult {
+    let old_tags = match state.store.relations.get(&relation_id) {
+        Some(r) => r.tags.clone(),
+        None => {
+            return UpdateTagsResult {
+                success: false,
+                render_feature: 0,
+                layer: 0,
+                is_area: false,
+            };
+        }
+    };
+
+    let parsed = render_feature::parse_tags(&new_tags);
+    // multipolygon 与 boundary 都按面渲染
+    let is_area = new_tags
+        .iter()
+        .any(|(k, v)| k == "type" && (v == "multipolygon" || v == "boundary"));
+
+    let command = UpdateRelationTagsCommand {
+        relation_id,
+        old_tags,
+        new_tags,
+    };
+    let result = state.history.execute(Box::new(command), &state.store);
+
+    UpdateTagsResult {
+        success: result.success,
+        render_feature: parsed.feature,
+        layer: parsed.layer,
+        is_area,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_store::{OsmNode, OsmWay};
+
+    fn member(member_type: MemberType, ref_id: i64, role: &str) -> RelationMember {
+        RelationMember {
+            member_type,
+            ref_id,
+            role: role.to_string(),
+        }
+    }
+
+    fn tag(k: &str, v: &str) -> (String, String) {
+        (k.to_string(), v.to_string())
+    }
+
+    /// 节点 1-3，Way 10 (1-2-3)
+    fn setup() -> AppState {
+        let state = AppState::default();
+        for id in 1..=3 {
+            state.store.insert_node(OsmNode {
+                id,
+                lon: id as f64 * 0.001,
+                lat: 0.0,
+                tags: vec![],
+            });
+        }
+        state.store.insert_way(OsmWay {
+            id: 10,
+            node_refs: vec![1, 2, 3],
+            tags: vec![tag("highway", "residential")],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        });
+        state
+    }
+
+    fn roles(state: &AppState, relation_id: i64) -> Vec<String> {
+        state
+            .store
+            .relations
+            .get(&relation_id)
+            .unwrap()
+            .members
+            .iter()
+            .map(|m| m.role.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_create_relation() {
+        let state = setup();
+
+        let empty = create_relation_impl(&state, vec![tag("type", "route")], vec![]);
+        assert!(!empty.success);
+        let missing = create_relation_impl(&state, vec![], vec![member(MemberType::Way, 99, "")]);
+        assert!(!missing.success);
+        assert!(state.store.relations.is_empty());
+
+        let created = create_relation_impl(
+            &state,
+            vec![tag("type", "route")],
+            vec![member(MemberType::Way, 10, "")],
+        );
+        assert!(created.success);
+        assert!(state.store.relations.contains_key(&created.relation_id));
+
+        state.history.undo(&state.store);
+        assert!(state.store.relations.is_empty());
+    }
+
+    #[test]
+    fn test_member_editing() {
+        let state = setup();
+        let id = create_relation_impl(
+            &state,
+            vec![tag("type", "route")],
+            vec![member(MemberType::Way, 10, "")],
+        )
+        .relation_id;
+
+        assert!(
+            add_relation_member_impl(&state, id, member(MemberType::Node, 1, "stop"), None).success
+        );
+        assert!(
+            add_relation_member_impl(&state, id, member(MemberType::Node, 3, "platform"), Some(0))
+                .success
+        );
+        assert_eq!(roles(&state, id), ["platform", "", "stop"]);
+        assert!(
+            !add_relation_member_impl(&state, id, member(MemberType::Node, 99, ""), None).success
+        );
+
+        assert!(move_relation_member_impl(&state, id, 0, 2).success);
+        assert_eq!(roles(&state, id), ["", "stop", "platform"]);
+        assert!(!move_relation_member_impl(&state, id, 1, 1).success);
+
+        assert!(update_member_role_impl(&state, id, 0, "forward".to_string()).success);
+        assert_eq!(roles(&state, id), ["forward", "stop", "platform"]);
+        assert!(!update_member_role_impl(&state, id, 5, "x".to_string()).success);
+
+        assert!(remove_relation_member_impl(&state, id, 1).success);
+        assert_eq!(roles(&state, id), ["forward", "platform"]);
+        assert!(!remove_relation_member_impl(&state, id, 5).success);
+
+        state.history.undo(&state.store);
+        assert_eq!(roles(&state, id), ["forward", "stop", "platform"]);
+    }
+
+    #[test]
+    fn test_add_member_rejects_cycles() {
+        let state = setup();
+        let way = || vec![member(MemberType::Way, 10, "")];
+        let a = create_relation_impl(&state, vec![], way()).relation_id;
+        let b = create_relation_impl(&state, vec![], way()).relation_id;
+        let c = create_relation_impl(&state, vec![], way()).relation_id;
+
+        assert!(
+            !add_relation_member_impl(&state, a, member(MemberType::Relation, a, ""), None).success
+        );
+        // A → B → C
+        assert!(
+            add_relation_member_impl(&state, a, member(MemberType::Relation, b, ""), None).success
+        );
+        assert!(
+            add_relation_member_impl(&state, b, member(MemberType::Relation, c, ""), None).success
+        );
+        // C → A 与 B → A 都会形成环
+        assert!(
+            !add_relation_member_impl(&state, c, member(MemberType::Relation, a, ""), None).success
+        );
+        assert!(
+            !add_relation_member_impl(&state, b, member(MemberType::Relation, a, ""), None).success
+        );
+        // A 再次包含 C 不形成环
+        assert!(
+            add_relation_member_impl(&state, a, member(MemberType::Relation, c, ""), None).success
+        );
+    }
+
+    #[test]
+    fn test_update_relation_tags_and_delete() {
+        let state = setup();
+        let id = create_relation_impl(
+            &state,
+            vec![tag("type", "route")],
+            vec![member(MemberType::Way, 10, "outer")],
+        )
+        .relation_id;
+        let parent = create_relation_impl(
+            &state,
+            vec![tag("type", "route_master")],
+            vec![member(MemberType::Relation, id, "")],
+        )
+        .relation_id;
+
+        let boundary = update_relation_tags_impl(
+            &state,
+            id,
+            vec![tag("type", "boundary"), tag("boundary", "administrative")],
+        );
+        assert!(boundary.success && boundary.is_area);
+        let multipolygon = update_relation_tags_impl(&state, id, vec![tag("type", "multipolygon")]);
+        assert!(multipolygon.is_area);
+        let route = update_relation_tags_impl(&state, id, vec![tag("type", "route")]);
+        assert!(route.success && !route.is_area);
+        assert!(!update_relation_tags_impl(&state, 99, vec![]).success);
+
+        let deleted = delete_relation_impl(&state, id);
+        assert!(deleted.success);
+        assert!(!state.store.relations.contains_key(&id));
+        // 上级 Relation 失去唯一成员，级联删除
+        assert_eq!(deleted.cascaded_relation_ids, [parent]);
+        assert!(!state.store.relations.contains_key(&parent));
+        assert!(!delete_relation_impl(&state, id).success);
+
+        state.history.undo(&state.store);
+        assert!(state.store.relations.contains_key(&id));
+        assert!(state.store.relations.contains_key(&parent));
+    }
+
+    /// 在 setup 基础上增加节点 4 与 Way 11 (2-4)：Way 10 穿过路口节点 2
+    fn junction() -> AppState {
+        let state = setup();
+        state.store.insert_node(OsmNode {
+            id: 4,
+            lon: 0.002,
+            lat: 0.001,
+            tags: vec![],
+        });
+        state.store.insert_way(OsmWay {
+            id: 11,
+            node_refs: vec![2, 4],
+            tags: vec![tag("highway", "residential")],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        });
+        state.store.rebuild_indices();
+        state
+    }
+
+    fn legs(from_way: i64, from_side: Option<i64>, to_way: i64) -> TurnRestrictionLegs {
+        TurnRestrictionLegs {
+            from_way,
+            from_side,
+            via_type: MemberType::Node,
+            via_id: 2,
+            to_way,
+            to_side: None,
+        }
+    }
+
+    fn restriction_refs(state: &AppState, relation_id: i64) -> Vec<(i64, String)> {
+        state
+            .store
+            .relations
+            .get(&relation_id)
+            .unwrap()
+            .members
+            .iter()
+            .map(|m| (m.ref_id, m.role.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_create_turn_restriction_splits_at_via() {
+        let state = junction();
+        let create = |legs: TurnRestrictionLegs| {
+            create_turn_restriction_impl(&state, &legs, "no_left_turn".to_string())
+        };
+
+        // Way 10 穿过 via，未指明一侧时无法确定 from 段
+        assert!(!create(legs(10, None, 11)).success);
+        assert!(!create(legs(10, Some(4), 11)).success);
+        assert!(
+            !create_turn_restriction_impl(&state, &legs(10, Some(3), 11), "no_turn".to_string())
+                .success
+        );
+        assert_eq!(state.store.ways.get(&10).unwrap().node_refs, vec![1, 2, 3]);
+
+        // 从节点 3 一侧驶来：from 为拆分出的新段 2-3
+        let created = create(legs(10, Some(3), 11));
+        assert!(created.success);
+        let members = restriction_refs(&state, created.relation_id);
+        let from = members[0].0;
+        assert_ne!(from, 10);
+        assert_eq!(state.store.ways.get(&from).unwrap().node_refs, vec![2, 3]);
+        assert_eq!(state.store.ways.get(&10).unwrap().node_refs, vec![1, 2]);
+        assert_eq!(
+            members,
+            [
+                (from, "from".to_string()),
+                (2, "via".to_string()),
+                (11, "to".to_string())
+            ]
+        );
+
+        // 拆分与创建为同一个历史记录
+        assert!(state.history.undo(&state.store).success);
+        assert!(!state.store.relations.contains_key(&created.relation_id));
+        assert_eq!(state.store.ways.get(&10).unwrap().node_refs, vec![1, 2, 3]);
+
+        // 从节点 1 一侧驶来：from 为保留原 ID 的 1-2 段
+        let created = create(legs(10, Some(1), 11));
+        assert!(created.success);
+        assert_eq!(restriction_refs(&state, created.relation_id)[0].0, 10);
+    }
+
+    #[test]
+    fn test_update_turn_restriction() {
+        let state = junction();
+        let created =
+            create_turn_restriction_impl(&state, &legs(11, None, 11), "no_u_turn".to_string());
+        assert!(created.success);
+        let id = created.relation_id;
+        assert_eq!(restriction_refs(&state, id)[2].0, 11);
+
+        // 改为驶向 Way 10 的节点 3 一侧
+        let mut to_east = legs(11, None, 10);
+        to_east.to_side = Some(3);
+        let updated =
+            update_turn_restriction_impl(&state, id, &to_east, "no_left_turn".to_string());
+        assert!(updated.success);
+        let to = restriction_refs(&state, id)[2].0;
+        assert_eq!(state.store.ways.get(&to).unwrap().node_refs, vec![2, 3]);
+        let relation = state.store.relations.get(&id).unwrap().clone();
+        assert_eq!(relation.tag("restriction"), Some("no_left_turn"));
+
+        // 拆分、成员与标签作为一个历史记录撤销
+        assert!(state.history.undo(&state.store).success);
+        assert_eq!(restriction_refs(&state, id)[2].0, 11);
+        assert_eq!(state.store.ways.get(&10).unwrap().node_refs, vec![1, 2, 3]);
+
+        assert!(
+            !update_turn_restriction_impl(&state, 99, &to_east, "no_left_turn".to_string()).success
+        );
+        let route = create_relation_impl(
+            &state,
+            vec![tag("type", "route")],
+            vec![member(MemberType::Way, 10, "")],
+        );
+        assert!(
+            !update_turn_restriction_impl(
+                &state,
+                route.relation_id,
+                &to_east,
+                "no_left_turn".to_string()
+            )
+            .success
+        );
+    }
+
+    #[test]
+    fn test_restriction_via_way_without_nodes() {
+        let state = junction();
+        state.store.insert_way(OsmWay {
+            id: 12,
+            node_refs: vec![],
+            tags: vec![],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        });
+        let mut via_way = legs(11, None, 10);
+        via_way.via_type = MemberType::Way;
+        via_way.via_id = 12;
+        let result = create_turn_restriction_impl(&state, &via_way, "no_left_turn".to_string());
+        assert!(!result.success);
+    }
+}
@@ -0,0 +1,236 @@
+//! GeoJSON 输出
+//!
+//! 将要素转换为 GeoJSON Feature（坐标为经纬度）：
+//! - Node → Point
+//! - 闭合的面状 Way → Polygon，其他 Way → LineString
+//! - multipolygon / boundary Relation → MultiPolygon（inner 环归入包含它的 outer 环）
+//! - 其他 Relation → 成员几何的 GeometryCollection（展开一层）
+//!
+//! Feature 的 `id` 形如 `node/123`，`properties` 为标签。
+
+use crate::osm_store::{DataBounds, MemberType, OsmStore};
+use crate::polygon_assembler::{point_in_ring, stitch_ways_to_node_rings};
+use crate::spatial_query;
+use serde_json::{json, Map, Value};
+
+/// 几何输出方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeometryMode {
+    /// 完整几何
+    Full,
+    /// 包围盒中心点
+    Center,
+    /// 不输出几何（`geometry` 为 null）
+    Omit,
+}
+
+type Line = Vec<(f64, f64)>;
+
+fn type_name(member_type: MemberType) -> &'static str {
+    match member_type {
+        MemberType::Node => "node",
+        MemberType::Way => "way",
+        MemberType::Relation => "relation",
+    }
+}
+
+fn line_geometry(line: Line) -> Value {
+    match line.as_slice() {
+        [point] => json!({ "type": "Point", "coordinates": point }),
+        _ => json!({ "type": "LineString", "coordinates": line }),
+    }
+}
+
+/// multipolygon / boundary Relation 的 MultiPolygon 几何；无法组成 outer 环时返回 None
+fn multipolygon(store: &OsmStore, relation_id: i64) -> Option<Value> {
+    let relation = store.relations.get(&relation_id)?.clone();
+    if !matches!(relation.tag("type"), Some("multipolygon" | "boundary")) {
+        return None;
+    }
+
+    let rings = |roles: &[&str]| -> Vec<Line> {
+        let way_ids: Vec<i64> = relation
+            .members
+            .iter()
+            .filter(|m| m.member_type == MemberType::Way && roles.contains(&m.role.as_str()))
+            .map(|m| m.ref_id)
+            .collect();
+        stitch_ways_to_node_rings(store, &way_ids)
+            .into_iter()
+            .map(|ring| {
+                ring.iter()
+                    .filter_map(|id| store.nodes.get(id).map(|n| (n.lon, n.lat)))
+                    .collect::<Line>()
+            })
+            .filter(|ring| ring.len() >= 4)
+            .collect()
+    };
+
+    let mut polygons: Vec<Vec<Line>> = rings(&["outer", ""])
+        .into_iter()
+        .map(|outer| vec![outer])
+        .collect();
+    if polygons.is_empty() {
+        return None;
+    }
+    for inner in rings(&["inner"]) {
+        let outer = polygons
+            .iter_mut()
+            .find(|polygon| point_in_ring(inner[0], &polygon[0]));
+        if let Some(polygon) = outer {
+            polygon.push(inner);
+        }
+    }
+    Some(json!({ "type": "MultiPolygon", "coordinates": polygons }))
+}
+
+/// 要素的完整几何
+fn geometry(store: &OsmStore, member_type: MemberType, id: i64) -> Option<Value> {
+    let lines = spatial_query::feature_lines(store, member_type, id)?;
+    let geometry = match member_type {
+        MemberType::Node | MemberType::Way => {
+            let line = lines.into_iter().next().unwrap_or_default();
+            let is_area = store.ways.get(&id).is_some_and(|w| w.is_area);
+            if member_type == MemberType::Way
+                && is_area
+                && line.len() >= 4
+                && line.first() == line.last()
+            {
+                json!({ "type": "Polygon", "coordinates": [line] })
+            } else {
+                line_geometry(line)
+            }
+        }
+        MemberType::Relation => multipolygon(store, id).unwrap_or_else(|| {
+            let geometries: Vec<Value> = lines.into_iter().map(line_geometry).collect();
+            json!({ "type": "GeometryCollection", "geometries": geometries })
+        }),
+    };
+    Some(geometry)
+}
+
+/// 单个要素的 GeoJSON Feature；要素不存在时返回 None
+pub fn feature(
+    store: &OsmStore,
+    member_type: MemberType,
+    id: i64,
+    mode: GeometryMode,
+    with_tags: bool,
+) -> Option<Value> {
+    let tags = store.feature_tags(member_type, id)?;
+    let geometry = match mode {
+        GeometryMode::Full => geometry(store, member_type, id)?,
+        GeometryMode::Center => {
+            let lines = spatial_query::feature_lines(store, member_type, id)?;
+            match DataBounds::from_points(lines.into_iter().flatten()) {
+                Some(b) => json!({ "type": "Point", "coordinates": [b.center_lon, b.center_lat] }),
+                None => Value::Null,
+            }
+        }
+        GeometryMode::Omit => Value::Null,
+    };
+    let properties: Map<String, Value> = if with_tags {
+        tags.into_iter()
+            .map(|(k, v)| (k, Value::String(v)))
+            .collect()
+    } else {
+        Map::new()
+    };
+
+    Some(json!({
+        "type": "Feature",
+        "id": format!("{}/{}", type_name(member_type), id),
+        "properties": properties,
+        "geometry": geometry,
+    }))
+}
+
+pub fn feature_collection(features: Vec<Value>) -> Value {
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_store::{OsmNode, OsmRelation, OsmWay, RelationMember};
+
+    fn way(id: i64, node_refs: Vec<i64>, is_area: bool) -> OsmWay {
+        OsmWay {
+            id,
+            node_refs,
+            tags: vec![("building".to_string(), "yes".to_string())],
+            render_feature: 0,
+            layer: 0,
+            is_area,
+        }
+    }
+
+    #[test]
+    fn test_feature_geometries() {
+        let store = OsmStore::new();
+        // 外环 1-4，内环 5-8
+        for (id, lon, lat) in [
+            (1, 0.0, 0.0),
+            (2, 1.0, 0.0),
+            (3, 1.0, 1.0),
+            (4, 0.0, 1.0),
+            (5, 0.4, 0.4),
+            (6, 0.6, 0.4),
+            (7, 0.6, 0.6),
+            (8, 0.4, 0.6),
+        ] {
+            store.insert_node(OsmNode {
+                id,
+                lon,
+                lat,
+                tags: vec![],
+            });
+        }
+        store.insert_way(way(10, vec![1, 2, 3, 4, 1], true));
+        store.insert_way(way(11, vec![5, 6, 7, 8, 5], false));
+        store.relations.insert(
+            100,
+            OsmRelation {
+                id: 100,
+                members: vec![
+                    RelationMember {
+                        member_type: MemberType::Way,
+                        ref_id: 10,
+                        role: "outer".to_string(),
+                    },
+                    RelationMember {
+                        member_type: MemberType::Way,
+                        ref_id: 11,
+                        role: "inner".to_string(),
+                    },
+                ],
+                tags: vec![("type".to_string(), "multipolygon".to_string())],
+            },
+        );
+
+        let full = |t, id| feature(&store, t, id, GeometryMode::Full, true).unwrap();
+        let node = full(MemberType::Node, 5);
+        assert_eq!(node["id"], "node/5");
+        assert_eq!(node["geometry"]["coordinates"], json!([0.4, 0.4]));
+
+        let area = full(MemberType::Way, 10);
+        assert_eq!(area["geometry"]["type"], "Polygon");
+        assert_eq!(area["properties"]["building"], "yes");
+        assert_eq!(full(MemberType::Way, 11)["geometry"]["type"], "LineString");
+
+        let relation = full(MemberType::Relation, 100);
+        assert_eq!(relation["geometry"]["type"], "MultiPolygon");
+        assert_eq!(
+            relation["geometry"]["coordinates"][0]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let center = feature(&store, MemberType::Way, 10, GeometryMode::Center, false).unwrap();
+        assert_eq!(center["geometry"]["coordinates"], json!([0.5, 0.5]));
+        assert!(center["properties"].as_object().unwrap().is_empty());
+        assert!(feature(&store, MemberType::Way, 99, GeometryMode::Omit, true).is_none());
+    }
+}
@@ -5,8 +5,20 @@
 //! - Command 必须实现 apply() 和 undo() 方法
 //! - HistoryManager 维护 undo_stack 和 redo_stack
 
-use crate::osm_store::{OsmNode, OsmStore, OsmWay};
-use std::sync::Mutex;
+use crate::changes::{ChangeTracker, ChangedSet};
+use crate::journal::{Journal, JournalEvent};
+use crate::osm_store::{
+    AreaRelationIndex, MemberType, OsmNode, OsmRelation, OsmStore, OsmWay, RelationMember,
+    RelationParents,
+};
+use crate::transform::TransformOperation;
+use crate::types::{FeatureSelection, HistoryEntryInfo};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// 命令执行结果
 #[derive(Debug, Clone, serde::Serialize)]
@@ -44,11 +56,62 @@ pub trait Command: Send + Sync {
     /// 撤销命令（逆向操作）
     fn undo(&self, store: &OsmStore) -> CommandResult;
 
+    /// 受影响的要素（用于历史浏览和定位变更）
+    fn affected_entities(&self) -> FeatureSelection;
+
+    /// 命令占用内存的估算值（字节），用于限制历史记录大小
+    fn estimated_size(&self) -> usize;
+
+    /// 具体命令类型（用于合并时向下转型）
+    fn as_any(&self) -> &dyn Any;
+
+    /// 可序列化的命令记录（用于崩溃恢复日志）
+    fn record(&self) -> CommandRecord;
+
+    /// 尝试把紧随其后执行的命令合并到本命令中
+    ///
+    /// 合并后本命令保留原始的旧状态、采用 `next` 的新状态；
+    /// 返回 true 表示已合并，`next` 不再单独进入历史记录
+    fn merge_with(&mut self, _next: &dyn Command) -> bool {
+        false
+    }
+
     /// 命令描述（用于调试和 UI 显示）
     fn description(&self) -> String;
 }
 
+/// 标签列表的估算内存（字节）
+fn tags_size(tags: &[(String, String)]) -> usize {
+    tags.iter()
+        .map(|(k, v)| size_of::<(String, String)>() + k.len() + v.len())
+        .sum()
+}
+
+fn node_size(node: &OsmNode) -> usize {
+    size_of::<OsmNode>() + tags_size(&node.tags)
+}
+
+fn way_size(way: &OsmWay) -> usize {
+    size_of::<OsmWay>() + way.node_refs.len() * size_of::<i64>() + tags_size(&way.tags)
+}
+
+fn relation_size(relation: &OsmRelation) -> usize {
+    size_of::<OsmRelation>()
+        + relation
+            .members
+            .iter()
+            .map(|m| size_of::<RelationMember>() + m.role.len())
+            .sum::<usize>()
+        + tags_size(&relation.tags)
+}
+
+/// Relation 是否作为面渲染（增删后需要重绘）
+fn renders_as_area(relation: &OsmRelation) -> bool {
+    matches!(relation.tag("type"), Some("multipolygon" | "boundary"))
+}
+
 /// 更新 Way 标签命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct UpdateWayTagsCommand {
     pub way_id: i64,
     pub old_tags: Vec<(String, String)>,
@@ -86,12 +149,45 @@ impl Command for UpdateWayTagsCommand {
         }
     }
 
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            way_ids: vec![self.way_id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + tags_size(&self.old_tags) + tags_size(&self.new_tags)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::UpdateWayTags(self.clone())
+    }
+
+    fn merge_with(&mut self, next: &dyn Command) -> bool {
+        match next.as_any().downcast_ref::<UpdateWayTagsCommand>() {
+            Some(next) if next.way_id == self.way_id => {
+                self.new_tags = next.new_tags.clone();
+                self.new_render_feature = next.new_render_feature;
+                self.new_layer = next.new_layer;
+                self.new_is_area = next.new_is_area;
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn description(&self) -> String {
         format!("Update tags for Way #{}", self.way_id)
     }
 }
 
 /// 更新 Node 标签命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct UpdateNodeTagsCommand {
     pub node_id: i64,
     pub old_tags: Vec<(String, String)>,
@@ -117,14 +213,167 @@ impl Command for UpdateNodeTagsCommand {
         }
     }
 
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: vec![self.node_id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + tags_size(&self.old_tags) + tags_size(&self.new_tags)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::UpdateNodeTags(self.clone())
+    }
+
+    fn merge_with(&mut self, next: &dyn Command) -> bool {
+        match next.as_any().downcast_ref::<UpdateNodeTagsCommand>() {
+            Some(next) if next.node_id == self.node_id => {
+                self.new_tags = next.new_tags.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn description(&self) -> String {
         format!("Update tags for Node #{}", self.node_id)
     }
 }
 
+/// Way 的渲染属性（随标签变化）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WayStyle {
+    pub render_feature: u16,
+    pub layer: i8,
+    pub is_area: bool,
+}
+
+/// 单个要素的标签变更记录（批量标签编辑的基本单元）
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TagChange {
+    pub member_type: MemberType,
+    pub id: i64,
+    pub old_tags: Vec<(String, String)>,
+    pub new_tags: Vec<(String, String)>,
+    /// 仅 Way 有渲染属性
+    pub old_style: Option<WayStyle>,
+    pub new_style: Option<WayStyle>,
+}
+
+impl TagChange {
+    /// 渲染属性是否改变
+    pub fn style_changed(&self) -> bool {
+        self.old_style != self.new_style
+    }
+
+    fn write(&self, store: &OsmStore, tags: &[(String, String)], style: Option<WayStyle>) -> bool {
+        match self.member_type {
+            MemberType::Node => match store.nodes.get_mut(&self.id) {
+                Some(mut node) => node.tags = tags.to_vec(),
+                None => return false,
+            },
+            MemberType::Way => match store.ways.get_mut(&self.id) {
+                Some(mut way) => {
+                    way.tags = tags.to_vec();
+                    if let Some(style) = style {
+                        way.render_feature = style.render_feature;
+                        way.layer = style.layer;
+                        way.is_area = style.is_area;
+                    }
+                }
+                None => return false,
+            },
+            MemberType::Relation => match store.relations.get_mut(&self.id) {
+                Some(mut relation) => relation.tags = tags.to_vec(),
+                None => return false,
+            },
+        }
+        true
+    }
+}
+
+/// 批量标签编辑命令
+///
+/// 一次操作修改多个不同类型要素的标签，作为一个历史记录撤销
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct BatchUpdateTagsCommand {
+    /// 操作名称（用于历史记录）
+    pub label: String,
+    pub changes: Vec<TagChange>,
+}
+
+impl Command for BatchUpdateTagsCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        // 先检查全部要素存在，避免部分应用
+        if !self
+            .changes
+            .iter()
+            .all(|c| store.contains_feature(c.member_type, c.id))
+        {
+            return CommandResult::failure("Feature not found");
+        }
+        for change in &self.changes {
+            change.write(store, &change.new_tags, change.new_style);
+        }
+        CommandResult::success(self.changes.iter().any(|c| c.style_changed()))
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        if !self
+            .changes
+            .iter()
+            .all(|c| store.contains_feature(c.member_type, c.id))
+        {
+            return CommandResult::failure("Feature not found");
+        }
+        for change in self.changes.iter().rev() {
+            change.write(store, &change.old_tags, change.old_style);
+        }
+        CommandResult::success(self.changes.iter().any(|c| c.style_changed()))
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        let mut affected = FeatureSelection::default();
+        for change in &self.changes {
+            affected.push(change.member_type, change.id);
+        }
+        affected
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + self.label.len()
+            + self
+                .changes
+                .iter()
+                .map(|c| size_of::<TagChange>() + tags_size(&c.old_tags) + tags_size(&c.new_tags))
+                .sum::<usize>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::BatchUpdateTags(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!("{} ({} features)", self.label, self.changes.len())
+    }
+}
+
 /// 移动节点命令
 ///
 /// 更新节点坐标，同时维护 R-Tree 索引
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct MoveNodeCommand {
     pub node_id: i64,
     pub old_lon: f64,
@@ -150,6 +399,36 @@ impl Command for MoveNodeCommand {
         }
     }
 
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: vec![self.node_id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::MoveNode(self.clone())
+    }
+
+    fn merge_with(&mut self, next: &dyn Command) -> bool {
+        match next.as_any().downcast_ref::<MoveNodeCommand>() {
+            Some(next) if next.node_id == self.node_id => {
+                self.new_lon = next.new_lon;
+                self.new_lat = next.new_lat;
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn description(&self) -> String {
         format!(
             "Move Node #{} from ({:.6}, {:.6}) to ({:.6}, {:.6})",
@@ -158,189 +437,2404 @@ impl Command for MoveNodeCommand {
     }
 }
 
-/// 添加节点命令
-pub struct AddNodeCommand {
-    pub node: OsmNode,
+/// 单个节点的坐标变更记录（批量几何编辑的基本单元）
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct NodeMove {
+    pub node_id: i64,
+    pub old_lon: f64,
+    pub old_lat: f64,
+    pub new_lon: f64,
+    pub new_lat: f64,
 }
 
-impl Command for AddNodeCommand {
-    fn apply(&self, store: &OsmStore) -> CommandResult {
-        store.add_node_with_index(self.node.clone());
-        CommandResult::success(true)
-    }
-
-    fn undo(&self, store: &OsmStore) -> CommandResult {
-        store.remove_node_with_index(self.node.id);
-        CommandResult::success(true)
+impl NodeMove {
+    /// 批量应用新坐标
+    pub fn apply_all(moves: &[NodeMove], store: &OsmStore) -> usize {
+        let positions: Vec<(i64, f64, f64)> = moves
+            .iter()
+            .map(|m| (m.node_id, m.new_lon, m.new_lat))
+            .collect();
+        store.update_node_positions(&positions)
     }
 
-    fn description(&self) -> String {
-        format!(
-            "Add Node #{} at ({:.6}, {:.6})",
-            self.node.id, self.node.lon, self.node.lat
-        )
+    /// 批量恢复旧坐标
+    pub fn undo_all(moves: &[NodeMove], store: &OsmStore) -> usize {
+        let positions: Vec<(i64, f64, f64)> = moves
+            .iter()
+            .map(|m| (m.node_id, m.old_lon, m.old_lat))
+            .collect();
+        store.update_node_positions(&positions)
     }
 }
 
-/// 删除 Way 命令
-pub struct DeleteWayCommand {
-    pub way: OsmWay,
+/// 批量几何变换命令（平移/旋转/缩放）
+///
+/// 移动的节点集合在创建命令时一次性解析，apply/undo 时批量更新 R-Tree
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct TransformCommand {
+    pub operation: TransformOperation,
+    pub moves: Vec<NodeMove>,
 }
 
-impl Command for DeleteWayCommand {
+impl Command for TransformCommand {
     fn apply(&self, store: &OsmStore) -> CommandResult {
-        store.remove_way_with_index(self.way.id);
+        if NodeMove::apply_all(&self.moves, store) == 0 {
+            return CommandResult::failure("No nodes to transform");
+        }
         CommandResult::success(true)
     }
 
     fn undo(&self, store: &OsmStore) -> CommandResult {
-        store.add_way_with_index(self.way.clone());
+        if NodeMove::undo_all(&self.moves, store) == 0 {
+            return CommandResult::failure("No nodes to transform");
+        }
         CommandResult::success(true)
     }
 
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: self.moves.iter().map(|m| m.node_id).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + self.moves.len() * size_of::<NodeMove>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::Transform(self.clone())
+    }
+
     fn description(&self) -> String {
-        format!("Delete Way #{}", self.way.id)
+        format!("{} ({} nodes)", self.operation.describe(), self.moves.len())
     }
 }
 
-/// 删除节点命令（含级联拓扑处理）
+/// Way 节点序列变更记录
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WayNodesChange {
+    pub way_id: i64,
+    pub old_refs: Vec<i64>,
+    pub new_refs: Vec<i64>,
+}
+
+impl WayNodesChange {
+    /// 依次替换各 Way 的节点序列（`forward` 为 true 时应用新序列，否则恢复旧序列）
+    ///
+    /// 遇到不存在的 Way 时，已替换的 Way 回退到替换前的序列，返回 false
+    fn replace_all(changes: &[WayNodesChange], store: &OsmStore, forward: bool) -> bool {
+        let target = |change: &WayNodesChange, forward: bool| {
+            if forward {
+                change.new_refs.clone()
+            } else {
+                change.old_refs.clone()
+            }
+        };
+        for (i, change) in changes.iter().enumerate() {
+            if store
+                .replace_way_node_refs(change.way_id, target(change, forward))
+                .is_none()
+            {
+                for done in changes[..i].iter().rev() {
+                    store.replace_way_node_refs(done.way_id, target(done, !forward));
+                }
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// 形状重塑命令（正交化、圆形化等几何整形操作）
 ///
-/// 删除节点时必须处理所有引用该节点的 Way：
-/// 1. 从 Way 的 node_refs 中移除该节点
-/// 2. 记录原始位置以便撤销时恢复
-/// 3. 如果 Way 只剩 1 个节点，级联删除该 Way
-pub struct DeleteNodeCommand {
-    pub node: OsmNode,
-    /// 节点在各个 Way 中的位置: (way_id, indices)
-    pub way_references: Vec<(i64, Vec<usize>)>,
-    /// 因节点删除而级联删除的 Way
-    pub cascaded_ways: Vec<OsmWay>,
+/// 一次操作可能同时包含：移动已有节点、插入新节点、删除冗余节点，
+/// 以及由此带来的 Way 节点序列变更。全部作为一个历史记录撤销。
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ReshapeCommand {
+    /// 操作名称（用于历史记录）
+    pub label: String,
+    pub moves: Vec<NodeMove>,
+    /// 新增的节点（已位于最终坐标）
+    pub added_nodes: Vec<OsmNode>,
+    /// 被删除的节点
+    pub removed_nodes: Vec<OsmNode>,
+    pub way_changes: Vec<WayNodesChange>,
 }
 
-impl Command for DeleteNodeCommand {
+impl Command for ReshapeCommand {
     fn apply(&self, store: &OsmStore) -> CommandResult {
-        // 1. 从所有引用的 Way 中移除该节点（但不在这里做，因为 way_references 已记录）
-        for (way_id, _indices) in &self.way_references {
-            store.remove_node_from_way(*way_id, self.node.id);
+        // 1. 新节点先入库，Way 才能引用它们
+        for node in &self.added_nodes {
+            store.add_node_with_index(node.clone());
         }
 
-        // 2. 级联删除无效的 Way（节点数 < 2）
-        for way in &self.cascaded_ways {
-            store.remove_way_with_index(way.id);
+        // 2. 更新 Way 节点序列；失败时撤回已入库的新节点，保证数据不被部分修改
+        if !WayNodesChange::replace_all(&self.way_changes, store, true) {
+            for node in &self.added_nodes {
+                store.remove_node_with_index(node.id);
+            }
+            return CommandResult::failure("Way not found");
         }
 
-        // 3. 删除节点本身
-        store.remove_node_with_index(self.node.id);
+        // 3. 删除不再被引用的节点
+        for node in &self.removed_nodes {
+            store.remove_node_with_index(node.id);
+        }
+
+        // 4. 批量移动节点
+        NodeMove::apply_all(&self.moves, store);
 
         CommandResult::success(true)
     }
 
     fn undo(&self, store: &OsmStore) -> CommandResult {
         // 恢复顺序必须严格相反
+        NodeMove::undo_all(&self.moves, store);
 
-        // 1. 恢复节点
-        store.add_node_with_index(self.node.clone());
+        for node in &self.removed_nodes {
+            store.add_node_with_index(node.clone());
+        }
 
-        // 2. 恢复级联删除的 Way
-        for way in &self.cascaded_ways {
-            store.add_way_with_index(way.clone());
+        if !WayNodesChange::replace_all(&self.way_changes, store, false) {
+            for node in &self.removed_nodes {
+                store.remove_node_with_index(node.id);
+            }
+            NodeMove::apply_all(&self.moves, store);
+            return CommandResult::failure("Way not found");
         }
 
-        // 3. 将节点恢复到各个 Way 的原始位置
-        for (way_id, indices) in &self.way_references {
-            store.insert_node_to_way(*way_id, self.node.id, indices);
+        for node in &self.added_nodes {
+            store.remove_node_with_index(node.id);
         }
 
         CommandResult::success(true)
     }
 
+    fn affected_entities(&self) -> FeatureSelection {
+        let node_ids = self
+            .moves
+            .iter()
+            .map(|m| m.node_id)
+            .chain(self.added_nodes.iter().map(|n| n.id))
+            .chain(self.removed_nodes.iter().map(|n| n.id))
+            .collect();
+        FeatureSelection {
+            node_ids,
+            way_ids: self.way_changes.iter().map(|c| c.way_id).collect(),
+            relation_ids: Vec::new(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + self.label.len()
+            + self.moves.len() * size_of::<NodeMove>()
+            + self.added_nodes.iter().map(node_size).sum::<usize>()
+            + self.removed_nodes.iter().map(node_size).sum::<usize>()
+            + self
+                .way_changes
+                .iter()
+                .map(|c| size_of::<WayNodesChange>() + (c.old_refs.len() + c.new_refs.len()) * 8)
+                .sum::<usize>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::Reshape(self.clone())
+    }
+
     fn description(&self) -> String {
         format!(
-            "Delete Node #{} (affects {} ways, cascades {} way deletions)",
-            self.node.id,
-            self.way_references.len(),
-            self.cascaded_ways.len()
+            "{} (moves {} nodes, adds {}, removes {})",
+            self.label,
+            self.moves.len(),
+            self.added_nodes.len(),
+            self.removed_nodes.len()
         )
     }
 }
 
-/// 历史记录管理器
-pub struct HistoryManager {
-    undo_stack: Mutex<Vec<Box<dyn Command>>>,
-    redo_stack: Mutex<Vec<Box<dyn Command>>>,
+/// 拉直 Way 命令
+///
+/// 移动保留的内部顶点到直线上，并删除冗余顶点
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct StraightenCommand {
+    pub way_id: i64,
+    pub moves: Vec<NodeMove>,
+    /// 被删除的顶点及其在 Way 中的原始位置（位置升序）
+    pub removed_vertices: Vec<(OsmNode, usize)>,
 }
 
-impl Default for HistoryManager {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+impl Command for StraightenCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        if !store.ways.contains_key(&self.way_id) {
+            return CommandResult::failure("Way not found");
+        }
 
-impl HistoryManager {
-    pub fn new() -> Self {
-        Self {
-            undo_stack: Mutex::new(Vec::new()),
-            redo_stack: Mutex::new(Vec::new()),
+        // 从后往前删除，避免索引位移问题
+        for (node, _) in self.removed_vertices.iter().rev() {
+            store.remove_node_from_way(self.way_id, node.id);
+            store.remove_node_with_index(node.id);
+        }
+
+        for m in &self.moves {
+            store.update_node_position(m.node_id, m.new_lon, m.new_lat);
         }
+
+        CommandResult::success(true)
     }
 
-    /// 执行命令并加入历史记录
-    pub fn execute(&self, command: Box<dyn Command>, store: &OsmStore) -> CommandResult {
-        let result = command.apply(store);
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        if !store.ways.contains_key(&self.way_id) {
+            return CommandResult::failure("Way not found");
+        }
 
-        if result.success {
-            let mut undo_stack = self.undo_stack.lock().unwrap();
-            let mut redo_stack = self.redo_stack.lock().unwrap();
+        for m in &self.moves {
+            store.update_node_position(m.node_id, m.old_lon, m.old_lat);
+        }
 
-            undo_stack.push(command);
-            redo_stack.clear();
+        // 从前往后插入，原始位置即为插入位置
+        for (node, index) in &self.removed_vertices {
+            store.add_node_with_index(node.clone());
+            store.insert_node_to_way(self.way_id, node.id, &[*index]);
         }
 
-        result
+        CommandResult::success(true)
     }
 
-    /// 撤销上一个命令
-    pub fn undo(&self, store: &OsmStore) -> CommandResult {
-        let command = {
-            let mut undo_stack = self.undo_stack.lock().unwrap();
-            undo_stack.pop()
-        };
+    fn affected_entities(&self) -> FeatureSelection {
+        let node_ids = self
+            .moves
+            .iter()
+            .map(|m| m.node_id)
+            .chain(self.removed_vertices.iter().map(|(n, _)| n.id))
+            .collect();
+        FeatureSelection {
+            node_ids,
+            way_ids: vec![self.way_id],
+            relation_ids: Vec::new(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + self.moves.len() * size_of::<NodeMove>()
+            + self
+                .removed_vertices
+                .iter()
+                .map(|(n, _)| node_size(n) + size_of::<usize>())
+                .sum::<usize>()
+    }
 
-        if let Some(cmd) = command {
-            let result = cmd.undo(store);
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 
-            if result.success {
-                let mut redo_stack = self.redo_stack.lock().unwrap();
-                redo_stack.push(cmd);
-            }
+    fn record(&self) -> CommandRecord {
+        CommandRecord::Straighten(self.clone())
+    }
 
-            result
-        } else {
-            CommandResult::failure("Nothing to undo")
-        }
+    fn description(&self) -> String {
+        format!(
+            "Straighten Way #{} (moves {} nodes, removes {})",
+            self.way_id,
+            self.moves.len(),
+            self.removed_vertices.len()
+        )
     }
+}
 
-    /// 重做上一个撤销的命令
-    pub fn redo(&self, store: &OsmStore) -> CommandResult {
-        let command = {
-            let mut redo_stack = self.redo_stack.lock().unwrap();
-            redo_stack.pop()
-        };
+/// 添加节点命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct AddNodeCommand {
+    pub node: OsmNode,
+}
 
-        if let Some(cmd) = command {
-            let result = cmd.apply(store);
+impl Command for AddNodeCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        store.add_node_with_index(self.node.clone());
+        CommandResult::success(true)
+    }
 
-            if result.success {
-                let mut undo_stack = self.undo_stack.lock().unwrap();
-                undo_stack.push(cmd);
-            }
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        store.remove_node_with_index(self.node.id);
+        CommandResult::success(true)
+    }
 
-            result
-        } else {
-            CommandResult::failure("Nothing to redo")
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: vec![self.node.id],
+            ..Default::default()
         }
     }
 
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + tags_size(&self.node.tags)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::AddNode(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Add Node #{} at ({:.6}, {:.6})",
+            self.node.id, self.node.lon, self.node.lat
+        )
+    }
+}
+
+/// 提取顶点标签为独立 POI 命令
+///
+/// 新节点接管顶点的标签和 Relation 成员身份（`via` 角色除外），原顶点保留在 Way 中但不再带标签
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExtractNodeCommand {
+    pub vertex_id: i64,
+    /// 新建的 POI 节点（带原顶点的全部标签）
+    pub poi: OsmNode,
+    /// 原顶点作为成员的位置: (relation_id, indices)
+    pub relation_members: Vec<(i64, Vec<usize>)>,
+}
+
+impl Command for ExtractNodeCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        match store.nodes.get_mut(&self.vertex_id) {
+            Some(mut vertex) => vertex.tags.clear(),
+            None => return CommandResult::failure("Node not found"),
+        }
+
+        store.add_node_with_index(self.poi.clone());
+        for (relation_id, indices) in &self.relation_members {
+            store.replace_relation_member_refs(*relation_id, indices, self.poi.id);
+        }
+
+        CommandResult::success(true)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        match store.nodes.get_mut(&self.vertex_id) {
+            Some(mut vertex) => vertex.tags = self.poi.tags.clone(),
+            None => return CommandResult::failure("Node not found"),
+        }
+
+        for (relation_id, indices) in &self.relation_members {
+            store.replace_relation_member_refs(*relation_id, indices, self.vertex_id);
+        }
+        store.remove_node_with_index(self.poi.id);
+
+        CommandResult::success(true)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: vec![self.vertex_id, self.poi.id],
+            way_ids: Vec::new(),
+            relation_ids: self.relation_members.iter().map(|(id, _)| *id).collect(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + tags_size(&self.poi.tags)
+            + self
+                .relation_members
+                .iter()
+                .map(|(_, indices)| size_of::<(i64, Vec<usize>)>() + indices.len() * 8)
+                .sum::<usize>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::ExtractNode(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!("Extract Node #{} from vertex #{}", self.poi.id, self.vertex_id)
+    }
+}
+
+/// 将独立节点附着到 Way 上成为顶点命令
+///
+/// 节点移动到最近线段上的投影点，并插入到该线段的两个端点之间
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct AttachNodeCommand {
+    pub node_id: i64,
+    pub way_id: i64,
+    /// 节点在 Way 中的插入位置
+    pub index: usize,
+    pub old_lon: f64,
+    pub old_lat: f64,
+    pub new_lon: f64,
+    pub new_lat: f64,
+}
+
+impl Command for AttachNodeCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        if !store.ways.contains_key(&self.way_id) {
+            return CommandResult::failure("Way not found");
+        }
+        if !store.update_node_position(self.node_id, self.new_lon, self.new_lat) {
+            return CommandResult::failure("Node not found");
+        }
+        store.insert_node_to_way(self.way_id, self.node_id, &[self.index]);
+        CommandResult::success(true)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        store.remove_node_from_way(self.way_id, self.node_id);
+        if !store.update_node_position(self.node_id, self.old_lon, self.old_lat) {
+            return CommandResult::failure("Node not found");
+        }
+        CommandResult::success(true)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: vec![self.node_id],
+            way_ids: vec![self.way_id],
+            relation_ids: Vec::new(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::AttachNode(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!("Attach Node #{} to Way #{}", self.node_id, self.way_id)
+    }
+}
+
+/// 合并节点命令（节点吸附到已有节点）
+///
+/// 被合并节点在各 Way 中的引用和 Relation 成员身份转移到目标节点，
+/// 标签并入目标节点（目标已有的键保持不变），然后删除被合并节点
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct MergeNodesCommand {
+    /// 被合并节点（合并前的快照）
+    pub node: OsmNode,
+    pub target_id: i64,
+    pub old_target_tags: Vec<(String, String)>,
+    pub new_target_tags: Vec<(String, String)>,
+    pub way_changes: Vec<WayNodesChange>,
+    /// 引用被合并节点的 Relation 成员: (relation_id, indices)
+    pub relation_refs: Vec<(i64, Vec<usize>)>,
+}
+
+impl MergeNodesCommand {
+    fn set_target_tags(&self, store: &OsmStore, tags: &[(String, String)]) -> bool {
+        match store.nodes.get_mut(&self.target_id) {
+            Some(mut target) => {
+                target.tags = tags.to_vec();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Command for MergeNodesCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        let ways_exist = self
+            .way_changes
+            .iter()
+            .all(|c| store.ways.contains_key(&c.way_id));
+        if !ways_exist || !store.nodes.contains_key(&self.node.id) {
+            return CommandResult::failure("Feature not found");
+        }
+        if !self.set_target_tags(store, &self.new_target_tags) {
+            return CommandResult::failure("Target node not found");
+        }
+        for change in &self.way_changes {
+            store.replace_way_node_refs(change.way_id, change.new_refs.clone());
+        }
+        for (relation_id, indices) in &self.relation_refs {
+            store.replace_relation_member_refs(*relation_id, indices, self.target_id);
+        }
+        store.remove_node_with_index(self.node.id);
+        CommandResult::success(true)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        if !self.set_target_tags(store, &self.old_target_tags) {
+            return CommandResult::failure("Target node not found");
+        }
+        store.add_node_with_index(self.node.clone());
+        for change in &self.way_changes {
+            store.replace_way_node_refs(change.way_id, change.old_refs.clone());
+        }
+        for (relation_id, indices) in &self.relation_refs {
+            store.replace_relation_member_refs(*relation_id, indices, self.node.id);
+        }
+        CommandResult::success(true)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: vec![self.node.id, self.target_id],
+            way_ids: self.way_changes.iter().map(|c| c.way_id).collect(),
+            relation_ids: self.relation_refs.iter().map(|(id, _)| *id).collect(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + node_size(&self.node)
+            + tags_size(&self.old_target_tags)
+            + tags_size(&self.new_target_tags)
+            + self
+                .way_changes
+                .iter()
+                .map(|c| (c.old_refs.len() + c.new_refs.len()) * size_of::<i64>())
+                .sum::<usize>()
+            + self
+                .relation_refs
+                .iter()
+                .map(|(_, indices)| size_of::<(i64, Vec<usize>)>() + indices.len() * 8)
+                .sum::<usize>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::MergeNodes(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!("Merge Node #{} into Node #{}", self.node.id, self.target_id)
+    }
+}
+
+/// 拆分 Way 命令
+///
+/// 原 Way 保留拆分节点之前的部分，拆分节点之后的部分成为新 Way（复制标签）。
+/// 两段的渲染属性按新几何重新计算；所属 Relation 的成员变更以快照形式保存
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SplitWayCommand {
+    pub way_id: i64,
+    pub old_refs: Vec<i64>,
+    pub new_refs: Vec<i64>,
+    pub old_style: WayStyle,
+    pub new_style: WayStyle,
+    /// 拆分出的新 Way
+    pub new_way: OsmWay,
+    pub relations: Vec<EntitySwap<OsmRelation>>,
+}
+
+impl SplitWayCommand {
+    fn set_style(&self, store: &OsmStore, style: WayStyle) {
+        if let Some(mut way) = store.ways.get_mut(&self.way_id) {
+            way.render_feature = style.render_feature;
+            way.layer = style.layer;
+            way.is_area = style.is_area;
+        }
+    }
+
+    fn write_relations(&self, store: &OsmStore, applied: bool) {
+        for swap in &self.relations {
+            if let Some(relation) = swap.state(applied) {
+                store.relations.insert(swap.id, relation.clone());
+            }
+        }
+    }
+}
+
+impl Command for SplitWayCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        if store
+            .replace_way_node_refs(self.way_id, self.new_refs.clone())
+            .is_none()
+        {
+            return CommandResult::failure("Way not found");
+        }
+        self.set_style(store, self.new_style);
+        store.add_way_with_index(self.new_way.clone());
+        self.write_relations(store, true);
+        CommandResult::success(true)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        if store
+            .replace_way_node_refs(self.way_id, self.old_refs.clone())
+            .is_none()
+        {
+            return CommandResult::failure("Way not found");
+        }
+        self.set_style(store, self.old_style);
+        store.remove_way_with_index(self.new_way.id);
+        self.write_relations(store, false);
+        CommandResult::success(true)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: Vec::new(),
+            way_ids: vec![self.way_id, self.new_way.id],
+            relation_ids: self.relations.iter().map(|s| s.id).collect(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + (self.old_refs.len() + self.new_refs.len()) * size_of::<i64>()
+            + way_size(&self.new_way)
+            + self
+                .relations
+                .iter()
+                .map(|s| {
+                    s.before.as_ref().map_or(0, relation_size)
+                        + s.after.as_ref().map_or(0, relation_size)
+                })
+                .sum::<usize>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::SplitWay(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!("Split Way #{} into #{}", self.way_id, self.new_way.id)
+    }
+}
+
+/// 粘贴要素命令
+///
+/// 所有副本已分配新 ID 并完成引用重映射，作为一个历史记录撤销
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct PasteCommand {
+    pub nodes: Vec<OsmNode>,
+    pub ways: Vec<OsmWay>,
+    pub relations: Vec<OsmRelation>,
+}
+
+impl Command for PasteCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        for node in &self.nodes {
+            store.add_node_with_index(node.clone());
+        }
+        for way in &self.ways {
+            store.add_way_with_index(way.clone());
+        }
+        for relation in &self.relations {
+            store.relations.insert(relation.id, relation.clone());
+        }
+        CommandResult::success(true)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        for relation in &self.relations {
+            store.relations.remove(&relation.id);
+        }
+        for way in &self.ways {
+            store.remove_way_with_index(way.id);
+        }
+        for node in &self.nodes {
+            store.remove_node_with_index(node.id);
+        }
+        CommandResult::success(true)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: self.nodes.iter().map(|n| n.id).collect(),
+            way_ids: self.ways.iter().map(|w| w.id).collect(),
+            relation_ids: self.relations.iter().map(|r| r.id).collect(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + self.nodes.iter().map(node_size).sum::<usize>()
+            + self.ways.iter().map(way_size).sum::<usize>()
+            + self.relations.iter().map(relation_size).sum::<usize>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::Paste(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Paste {} nodes, {} ways, {} relations",
+            self.nodes.len(),
+            self.ways.len(),
+            self.relations.len()
+        )
+    }
+}
+
+/// Relation 成员级联清理记录
+///
+/// 删除 Node/Way/Relation 时，所有以其为成员的 Relation 都必须同步移除对应成员：
+/// - 仍然有效的 Relation 只移除成员，并记录原始位置以便撤销
+/// - 变为空或无效的 Relation 被级联删除（递归处理其上级 Relation）
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RelationCascade {
+    /// 被移除的成员: (relation_id, [(原始位置, 成员)])，位置升序
+    pub member_removals: Vec<(i64, Vec<(usize, RelationMember)>)>,
+    /// 因成员移除而级联删除的 Relation（原始快照）
+    pub deleted_relations: Vec<OsmRelation>,
+}
+
+impl RelationCascade {
+    /// 计算删除指定要素后需要的 Relation 级联操作
+    ///
+    /// `removed` 为即将被删除的要素列表；级联删除的 Relation 会被加入工作队列，
+    /// 继续从其上级 Relation 中移除。
+    pub fn plan(store: &OsmStore, removed: &[(MemberType, i64)]) -> Self {
+        // 工作副本: relation_id -> (原始快照, 剩余成员 [(原始位置, 成员)])
+        let mut touched: HashMap<i64, (OsmRelation, Vec<(usize, RelationMember)>)> = HashMap::new();
+        let mut touched_order: Vec<i64> = Vec::new();
+        let mut deleted: HashSet<i64> = HashSet::new();
+        let mut queue: Vec<(MemberType, i64)> = removed.to_vec();
+
+        for &(member_type, ref_id) in removed {
+            if member_type == MemberType::Relation {
+                deleted.insert(ref_id);
+            }
+        }
+
+        while let Some((member_type, ref_id)) = queue.pop() {
+            for relation_id in store.find_relations_referencing(member_type, ref_id) {
+                if deleted.contains(&relation_id) {
+                    continue;
+                }
+
+                let (original, remaining) = touched.entry(relation_id).or_insert_with(|| {
+                    touched_order.push(relation_id);
+                    let relation = store.relations.get(&relation_id).unwrap().clone();
+                    let members = relation.members.iter().cloned().enumerate().collect();
+                    (relation, members)
+                });
+
+                remaining.retain(|(_, m)| !(m.member_type == member_type && m.ref_id == ref_id));
+
+                let candidate = OsmRelation {
+                    id: relation_id,
+                    members: remaining.iter().map(|(_, m)| m.clone()).collect(),
+                    tags: original.tags.clone(),
+                };
+
+                if !candidate.is_valid() {
+                    deleted.insert(relation_id);
+                    queue.push((MemberType::Relation, relation_id));
+                }
+            }
+        }
+
+        let mut cascade = Self::default();
+
+        for relation_id in touched_order {
+            let (original, remaining) = touched.remove(&relation_id).unwrap();
+
+            if deleted.contains(&relation_id) {
+                cascade.deleted_relations.push(original);
+                continue;
+            }
+
+            let kept: HashSet<usize> = remaining.iter().map(|(idx, _)| *idx).collect();
+            let removals: Vec<(usize, RelationMember)> = original
+                .members
+                .into_iter()
+                .enumerate()
+                .filter(|(idx, _)| !kept.contains(idx))
+                .collect();
+
+            cascade.member_removals.push((relation_id, removals));
+        }
+
+        cascade
+    }
+
+    /// 成员被修改（但未删除）的 Relation ID
+    pub fn affected_relation_ids(&self) -> Vec<i64> {
+        self.member_removals.iter().map(|(id, _)| *id).collect()
+    }
+
+    /// 被级联删除的 Relation ID
+    pub fn deleted_relation_ids(&self) -> Vec<i64> {
+        self.deleted_relations.iter().map(|r| r.id).collect()
+    }
+
+    /// 成员被修改或被级联删除的全部 Relation ID
+    pub fn all_relation_ids(&self) -> Vec<i64> {
+        let mut ids = self.affected_relation_ids();
+        ids.extend(self.deleted_relation_ids());
+        ids
+    }
+
+    /// 估算内存（字节）
+    pub fn estimated_size(&self) -> usize {
+        self.member_removals
+            .iter()
+            .map(|(_, removals)| {
+                size_of::<(i64, Vec<(usize, RelationMember)>)>()
+                    + removals
+                        .iter()
+                        .map(|(_, m)| size_of::<(usize, RelationMember)>() + m.role.len())
+                        .sum::<usize>()
+            })
+            .sum::<usize>()
+            + self.deleted_relations.iter().map(relation_size).sum::<usize>()
+    }
+
+    /// 正向执行：移除成员并删除无效 Relation
+    pub fn apply(&self, store: &OsmStore) {
+        for (relation_id, removals) in &self.member_removals {
+            let indices: Vec<usize> = removals.iter().map(|(idx, _)| *idx).collect();
+            store.remove_relation_members(*relation_id, &indices);
+        }
+
+        for relation in &self.deleted_relations {
+            store.relations.remove(&relation.id);
+        }
+    }
+
+    /// 逆向执行：恢复被删除的 Relation 和被移除的成员
+    pub fn undo(&self, store: &OsmStore) {
+        for relation in &self.deleted_relations {
+            store.relations.insert(relation.id, relation.clone());
+        }
+
+        for (relation_id, removals) in &self.member_removals {
+            store.insert_relation_members(*relation_id, removals);
+        }
+    }
+}
+
+/// 删除 Way 命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeleteWayCommand {
+    pub way: OsmWay,
+    /// 所属 Relation 的成员级联清理
+    pub relation_cascade: RelationCascade,
+}
+
+impl Command for DeleteWayCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        store.remove_way_with_index(self.way.id);
+        self.relation_cascade.apply(store);
+        CommandResult::success(true)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        self.relation_cascade.undo(store);
+        store.add_way_with_index(self.way.clone());
+        CommandResult::success(true)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: Vec::new(),
+            way_ids: vec![self.way.id],
+            relation_ids: self.relation_cascade.all_relation_ids(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + way_size(&self.way) + self.relation_cascade.estimated_size()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::DeleteWay(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!("Delete Way #{}", self.way.id)
+    }
+}
+
+/// 删除节点命令（含级联拓扑处理）
+///
+/// 删除节点时必须处理所有引用该节点的 Way：
+/// 1. 从 Way 的 node_refs 中移除该节点
+/// 2. 记录原始位置以便撤销时恢复
+/// 3. 如果 Way 只剩 1 个节点，级联删除该 Way
+/// 4. 从所有 Relation 中移除该节点及级联删除的 Way
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeleteNodeCommand {
+    pub node: OsmNode,
+    /// 节点在各个 Way 中的位置: (way_id, indices)
+    pub way_references: Vec<(i64, Vec<usize>)>,
+    /// 因节点删除而级联删除的 Way
+    pub cascaded_ways: Vec<OsmWay>,
+    /// 所属 Relation 的成员级联清理（包含级联删除的 Way）
+    pub relation_cascade: RelationCascade,
+}
+
+impl Command for DeleteNodeCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        // 1. 从所有引用的 Way 中移除该节点（但不在这里做，因为 way_references 已记录）
+        for (way_id, _indices) in &self.way_references {
+            store.remove_node_from_way(*way_id, self.node.id);
+        }
+
+        // 2. 级联删除无效的 Way（节点数 < 2）
+        for way in &self.cascaded_ways {
+            store.remove_way_with_index(way.id);
+        }
+
+        // 3. 删除节点本身
+        store.remove_node_with_index(self.node.id);
+
+        // 4. 清理 Relation 成员
+        self.relation_cascade.apply(store);
+
+        CommandResult::success(true)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        // 恢复顺序必须严格相反
+
+        // 1. 恢复 Relation 成员
+        self.relation_cascade.undo(store);
+
+        // 2. 恢复节点
+        store.add_node_with_index(self.node.clone());
+
+        // 3. 恢复级联删除的 Way
+        for way in &self.cascaded_ways {
+            store.add_way_with_index(way.clone());
+        }
+
+        // 4. 将节点恢复到各个 Way 的原始位置
+        for (way_id, indices) in &self.way_references {
+            store.insert_node_to_way(*way_id, self.node.id, indices);
+        }
+
+        CommandResult::success(true)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        let way_ids = self
+            .way_references
+            .iter()
+            .map(|(id, _)| *id)
+            .chain(self.cascaded_ways.iter().map(|w| w.id))
+            .collect();
+        FeatureSelection {
+            node_ids: vec![self.node.id],
+            way_ids,
+            relation_ids: self.relation_cascade.all_relation_ids(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + tags_size(&self.node.tags)
+            + self
+                .way_references
+                .iter()
+                .map(|(_, indices)| size_of::<(i64, Vec<usize>)>() + indices.len() * 8)
+                .sum::<usize>()
+            + self.cascaded_ways.iter().map(way_size).sum::<usize>()
+            + self.relation_cascade.estimated_size()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::DeleteNode(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Delete Node #{} (affects {} ways, cascades {} way deletions, {} relation deletions)",
+            self.node.id,
+            self.way_references.len(),
+            self.cascaded_ways.len(),
+            self.relation_cascade.deleted_relations.len()
+        )
+    }
+}
+
+/// 创建 Relation 命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct CreateRelationCommand {
+    pub relation: OsmRelation,
+}
+
+impl Command for CreateRelationCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        store
+            .relations
+            .insert(self.relation.id, self.relation.clone());
+        CommandResult::success(renders_as_area(&self.relation))
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        store.relations.remove(&self.relation.id);
+        CommandResult::success(renders_as_area(&self.relation))
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            relation_ids: vec![self.relation.id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + relation_size(&self.relation)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::CreateRelation(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Create Relation #{} with {} members",
+            self.relation.id,
+            self.relation.members.len()
+        )
+    }
+}
+
+/// 删除 Relation 命令
+///
+/// 同时从上级 Relation 中移除该 Relation，上级变为无效时级联删除
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeleteRelationCommand {
+    pub relation: OsmRelation,
+    /// 上级 Relation 的成员级联清理
+    pub relation_cascade: RelationCascade,
+}
+
+impl Command for DeleteRelationCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        if store.relations.remove(&self.relation.id).is_none() {
+            return CommandResult::failure("Relation not found");
+        }
+        self.relation_cascade.apply(store);
+        CommandResult::success(renders_as_area(&self.relation))
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        self.relation_cascade.undo(store);
+        store
+            .relations
+            .insert(self.relation.id, self.relation.clone());
+        CommandResult::success(renders_as_area(&self.relation))
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        let mut relation_ids = vec![self.relation.id];
+        relation_ids.extend(self.relation_cascade.all_relation_ids());
+        FeatureSelection {
+            relation_ids,
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + relation_size(&self.relation) + self.relation_cascade.estimated_size()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::DeleteRelation(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!("Delete Relation #{}", self.relation.id)
+    }
+}
+
+/// 添加 Relation 成员命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct AddRelationMemberCommand {
+    pub relation_id: i64,
+    /// 插入位置
+    pub index: usize,
+    pub member: RelationMember,
+}
+
+impl Command for AddRelationMemberCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        match store.relations.get(&self.relation_id) {
+            Some(relation) if self.index <= relation.members.len() => {}
+            Some(_) => return CommandResult::failure("Member index out of range"),
+            None => return CommandResult::failure("Relation not found"),
+        }
+        store.insert_relation_members(self.relation_id, &[(self.index, self.member.clone())]);
+        CommandResult::success(false)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        if !store.relations.contains_key(&self.relation_id) {
+            return CommandResult::failure("Relation not found");
+        }
+        store.remove_relation_members(self.relation_id, &[self.index]);
+        CommandResult::success(false)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            relation_ids: vec![self.relation_id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + self.member.role.len()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::AddRelationMember(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Add {:?} #{} to Relation #{} as '{}'",
+            self.member.member_type, self.member.ref_id, self.relation_id, self.member.role
+        )
+    }
+}
+
+/// 移除 Relation 成员命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RemoveRelationMemberCommand {
+    pub relation_id: i64,
+    /// 被移除成员的位置
+    pub index: usize,
+    pub member: RelationMember,
+}
+
+impl Command for RemoveRelationMemberCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        match store.relations.get(&self.relation_id) {
+            Some(relation) if self.index < relation.members.len() => {}
+            Some(_) => return CommandResult::failure("Member index out of range"),
+            None => return CommandResult::failure("Relation not found"),
+        }
+        store.remove_relation_members(self.relation_id, &[self.index]);
+        CommandResult::success(false)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        if !store.relations.contains_key(&self.relation_id) {
+            return CommandResult::failure("Relation not found");
+        }
+        store.insert_relation_members(self.relation_id, &[(self.index, self.member.clone())]);
+        CommandResult::success(false)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            relation_ids: vec![self.relation_id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + self.member.role.len()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::RemoveRelationMember(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Remove {:?} #{} from Relation #{}",
+            self.member.member_type, self.member.ref_id, self.relation_id
+        )
+    }
+}
+
+/// 调整 Relation 成员顺序命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct MoveRelationMemberCommand {
+    pub relation_id: i64,
+    pub from_index: usize,
+    pub to_index: usize,
+}
+
+impl MoveRelationMemberCommand {
+    fn move_member(store: &OsmStore, relation_id: i64, from: usize, to: usize) -> CommandResult {
+        let mut relation = match store.relations.get_mut(&relation_id) {
+            Some(r) => r,
+            None => return CommandResult::failure("Relation not found"),
+        };
+
+        if from >= relation.members.len() || to >= relation.members.len() {
+            return CommandResult::failure("Member index out of range");
+        }
+
+        let member = relation.members.remove(from);
+        relation.members.insert(to, member);
+        CommandResult::success(false)
+    }
+}
+
+impl Command for MoveRelationMemberCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        Self::move_member(store, self.relation_id, self.from_index, self.to_index)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        Self::move_member(store, self.relation_id, self.to_index, self.from_index)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            relation_ids: vec![self.relation_id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::MoveRelationMember(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Move member of Relation #{} from {} to {}",
+            self.relation_id, self.from_index, self.to_index
+        )
+    }
+}
+
+/// 修改 Relation 成员角色命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct UpdateMemberRoleCommand {
+    pub relation_id: i64,
+    pub index: usize,
+    pub old_role: String,
+    pub new_role: String,
+}
+
+impl UpdateMemberRoleCommand {
+    fn set_role(&self, store: &OsmStore, role: &str) -> CommandResult {
+        let mut relation = match store.relations.get_mut(&self.relation_id) {
+            Some(r) => r,
+            None => return CommandResult::failure("Relation not found"),
+        };
+
+        match relation.members.get_mut(self.index) {
+            Some(member) => {
+                member.role = role.to_string();
+                CommandResult::success(false)
+            }
+            None => CommandResult::failure("Member index out of range"),
+        }
+    }
+}
+
+impl Command for UpdateMemberRoleCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        self.set_role(store, &self.new_role)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        self.set_role(store, &self.old_role)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            relation_ids: vec![self.relation_id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + self.old_role.len() + self.new_role.len()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::UpdateMemberRole(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Change role of member {} in Relation #{} from '{}' to '{}'",
+            self.index, self.relation_id, self.old_role, self.new_role
+        )
+    }
+}
+
+/// 替换 Relation 全部成员命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SetRelationMembersCommand {
+    pub relation_id: i64,
+    pub old_members: Vec<RelationMember>,
+    pub new_members: Vec<RelationMember>,
+}
+
+impl SetRelationMembersCommand {
+    fn set_members(&self, store: &OsmStore, members: &[RelationMember]) -> CommandResult {
+        match store.relations.get_mut(&self.relation_id) {
+            Some(mut relation) => {
+                relation.members = members.to_vec();
+                CommandResult::success(false)
+            }
+            None => CommandResult::failure("Relation not found"),
+        }
+    }
+}
+
+impl Command for SetRelationMembersCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        self.set_members(store, &self.new_members)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        self.set_members(store, &self.old_members)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            relation_ids: vec![self.relation_id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + self
+                .old_members
+                .iter()
+                .chain(&self.new_members)
+                .map(|m| size_of::<RelationMember>() + m.role.len())
+                .sum::<usize>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::SetRelationMembers(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!("Set members of Relation #{}", self.relation_id)
+    }
+}
+
+/// 更新 Relation 标签命令
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct UpdateRelationTagsCommand {
+    pub relation_id: i64,
+    pub old_tags: Vec<(String, String)>,
+    pub new_tags: Vec<(String, String)>,
+}
+
+impl Command for UpdateRelationTagsCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        if let Some(mut relation) = store.relations.get_mut(&self.relation_id) {
+            relation.tags = self.new_tags.clone();
+            CommandResult::success(false)
+        } else {
+            CommandResult::failure("Relation not found")
+        }
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        if let Some(mut relation) = store.relations.get_mut(&self.relation_id) {
+            relation.tags = self.old_tags.clone();
+            CommandResult::success(false)
+        } else {
+            CommandResult::failure("Relation not found")
+        }
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            relation_ids: vec![self.relation_id],
+            ..Default::default()
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>() + tags_size(&self.old_tags) + tags_size(&self.new_tags)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::UpdateRelationTags(self.clone())
+    }
+
+    fn merge_with(&mut self, next: &dyn Command) -> bool {
+        match next.as_any().downcast_ref::<UpdateRelationTagsCommand>() {
+            Some(next) if next.relation_id == self.relation_id => {
+                self.new_tags = next.new_tags.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("Update tags for Relation #{}", self.relation_id)
+    }
+}
+
+/// 要素快照替换记录（None 表示要素不存在）
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct EntitySwap<T> {
+    pub id: i64,
+    pub before: Option<T>,
+    pub after: Option<T>,
+}
+
+impl<T> EntitySwap<T> {
+    fn state(&self, applied: bool) -> &Option<T> {
+        if applied {
+            &self.after
+        } else {
+            &self.before
+        }
+    }
+}
+
+/// 恢复要素到基准版本命令
+///
+/// 以快照替换的方式写入目标要素及级联涉及的要素：
+/// 重新创建的节点、被移除的孤立新建节点、父 Relation 的成员关系
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RevertEntityCommand {
+    pub member_type: MemberType,
+    pub id: i64,
+    pub nodes: Vec<EntitySwap<OsmNode>>,
+    pub ways: Vec<EntitySwap<OsmWay>>,
+    pub relations: Vec<EntitySwap<OsmRelation>>,
+}
+
+impl RevertEntityCommand {
+    fn write_node(store: &OsmStore, id: i64, node: &Option<OsmNode>) {
+        match node {
+            Some(node) if store.nodes.contains_key(&id) => {
+                store.update_node_position(id, node.lon, node.lat);
+                if let Some(mut current) = store.nodes.get_mut(&id) {
+                    current.tags = node.tags.clone();
+                }
+            }
+            Some(node) => store.add_node_with_index(node.clone()),
+            None => {
+                store.remove_node_with_index(id);
+            }
+        }
+    }
+
+    /// 写入快照：先创建/更新节点，再写 Way 和 Relation，最后删除节点
+    fn write(&self, store: &OsmStore, applied: bool) {
+        for swap in &self.nodes {
+            if swap.state(applied).is_some() {
+                Self::write_node(store, swap.id, swap.state(applied));
+            }
+        }
+        for swap in &self.ways {
+            store.remove_way_with_index(swap.id);
+            if let Some(way) = swap.state(applied) {
+                store.add_way_with_index(way.clone());
+            }
+        }
+        for swap in &self.relations {
+            match swap.state(applied) {
+                Some(relation) => {
+                    store.relations.insert(swap.id, relation.clone());
+                }
+                None => {
+                    store.relations.remove(&swap.id);
+                }
+            }
+        }
+        for swap in &self.nodes {
+            if swap.state(applied).is_none() {
+                Self::write_node(store, swap.id, &None);
+            }
+        }
+    }
+}
+
+impl Command for RevertEntityCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        self.write(store, true);
+        CommandResult::success(true)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        self.write(store, false);
+        CommandResult::success(true)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: self.nodes.iter().map(|s| s.id).collect(),
+            way_ids: self.ways.iter().map(|s| s.id).collect(),
+            relation_ids: self.relations.iter().map(|s| s.id).collect(),
+        }
+    }
+
+    fn estimated_size(&self) -> usize {
+        fn swap_size<T>(swap: &EntitySwap<T>, size: fn(&T) -> usize) -> usize {
+            size_of::<EntitySwap<T>>()
+                + swap.before.as_ref().map_or(0, size)
+                + swap.after.as_ref().map_or(0, size)
+        }
+        let nodes: usize = self.nodes.iter().map(|s| swap_size(s, node_size)).sum();
+        let ways: usize = self.ways.iter().map(|s| swap_size(s, way_size)).sum();
+        let relations: usize = self
+            .relations
+            .iter()
+            .map(|s| swap_size(s, relation_size))
+            .sum();
+        size_of::<Self>() + nodes + ways + relations
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::RevertEntity(self.clone())
+    }
+
+    fn description(&self) -> String {
+        format!("Revert {:?} #{}", self.member_type, self.id)
+    }
+}
+
+/// 组合命令
+///
+/// 按顺序执行子命令，按逆序撤销。任一子命令失败时回滚已执行的部分，
+/// 保证组合命令整体要么完成、要么不生效。
+pub struct CompositeCommand {
+    /// 操作名称（用于历史记录）
+    pub label: String,
+    pub children: Vec<Box<dyn Command>>,
+}
+
+impl Command for CompositeCommand {
+    fn apply(&self, store: &OsmStore) -> CommandResult {
+        let mut needs_redraw = false;
+        for (i, child) in self.children.iter().enumerate() {
+            let result = child.apply(store);
+            if !result.success {
+                for applied in self.children[..i].iter().rev() {
+                    applied.undo(store);
+                }
+                return result;
+            }
+            needs_redraw |= result.needs_redraw;
+        }
+        CommandResult::success(needs_redraw)
+    }
+
+    fn undo(&self, store: &OsmStore) -> CommandResult {
+        let mut needs_redraw = false;
+        for (i, child) in self.children.iter().enumerate().rev() {
+            let result = child.undo(store);
+            if !result.success {
+                for undone in &self.children[i + 1..] {
+                    undone.apply(store);
+                }
+                return result;
+            }
+            needs_redraw |= result.needs_redraw;
+        }
+        CommandResult::success(needs_redraw)
+    }
+
+    fn affected_entities(&self) -> FeatureSelection {
+        let mut affected = FeatureSelection::default();
+        for child in &self.children {
+            affected.extend(child.affected_entities());
+        }
+        affected.dedup();
+        affected
+    }
+
+    fn estimated_size(&self) -> usize {
+        size_of::<Self>()
+            + self.label.len()
+            + self
+                .children
+                .iter()
+                .map(|c| c.estimated_size())
+                .sum::<usize>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn record(&self) -> CommandRecord {
+        CommandRecord::Composite {
+            label: self.label.clone(),
+            children: self.children.iter().map(|c| c.record()).collect(),
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("{} ({} steps)", self.label, self.children.len())
+    }
+}
+
+/// 可序列化的命令记录
+///
+/// 每种命令对应一个变体；组合命令递归保存子命令。
+/// 用于把历史操作写入崩溃恢复日志，并在重新启动后还原为命令重放。
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum CommandRecord {
+    UpdateWayTags(UpdateWayTagsCommand),
+    UpdateNodeTags(UpdateNodeTagsCommand),
+    BatchUpdateTags(BatchUpdateTagsCommand),
+    MoveNode(MoveNodeCommand),
+    Transform(TransformCommand),
+    Reshape(ReshapeCommand),
+    Straighten(StraightenCommand),
+    AddNode(AddNodeCommand),
+    ExtractNode(ExtractNodeCommand),
+    AttachNode(AttachNodeCommand),
+    MergeNodes(MergeNodesCommand),
+    SplitWay(SplitWayCommand),
+    Paste(PasteCommand),
+    DeleteWay(DeleteWayCommand),
+    DeleteNode(DeleteNodeCommand),
+    CreateRelation(CreateRelationCommand),
+    DeleteRelation(DeleteRelationCommand),
+    AddRelationMember(AddRelationMemberCommand),
+    RemoveRelationMember(RemoveRelationMemberCommand),
+    MoveRelationMember(MoveRelationMemberCommand),
+    UpdateMemberRole(UpdateMemberRoleCommand),
+    SetRelationMembers(SetRelationMembersCommand),
+    UpdateRelationTags(UpdateRelationTagsCommand),
+    RevertEntity(RevertEntityCommand),
+    Composite {
+        label: String,
+        children: Vec<CommandRecord>,
+    },
+}
+
+impl CommandRecord {
+    /// 还原为可执行的命令
+    pub fn into_command(self) -> Box<dyn Command> {
+        match self {
+            Self::UpdateWayTags(command) => Box::new(command),
+            Self::UpdateNodeTags(command) => Box::new(command),
+            Self::BatchUpdateTags(command) => Box::new(command),
+            Self::MoveNode(command) => Box::new(command),
+            Self::Transform(command) => Box::new(command),
+            Self::Reshape(command) => Box::new(command),
+            Self::Straighten(command) => Box::new(command),
+            Self::AddNode(command) => Box::new(command),
+            Self::ExtractNode(command) => Box::new(command),
+            Self::AttachNode(command) => Box::new(command),
+            Self::MergeNodes(command) => Box::new(command),
+            Self::SplitWay(command) => Box::new(command),
+            Self::Paste(command) => Box::new(command),
+            Self::DeleteWay(command) => Box::new(command),
+            Self::DeleteNode(command) => Box::new(command),
+            Self::CreateRelation(command) => Box::new(command),
+            Self::DeleteRelation(command) => Box::new(command),
+            Self::AddRelationMember(command) => Box::new(command),
+            Self::RemoveRelationMember(command) => Box::new(command),
+            Self::MoveRelationMember(command) => Box::new(command),
+            Self::UpdateMemberRole(command) => Box::new(command),
+            Self::SetRelationMembers(command) => Box::new(command),
+            Self::UpdateRelationTags(command) => Box::new(command),
+            Self::RevertEntity(command) => Box::new(command),
+            Self::Composite { label, children } => Box::new(CompositeCommand {
+                label,
+                children: children.into_iter().map(Self::into_command).collect(),
+            }),
+        }
+    }
+}
+
+/// 历史记录条目
+pub struct HistoryEntry {
+    pub command: Box<dyn Command>,
+    /// 执行时间（Unix 毫秒）
+    pub timestamp: u64,
+    /// 估算内存（字节），创建时计算一次
+    pub size: usize,
+}
+
+/// 当前时间（Unix 毫秒）
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl HistoryEntry {
+    fn new(command: Box<dyn Command>) -> Self {
+        let size = command.estimated_size();
+        Self {
+            command,
+            timestamp: now_millis(),
+            size,
+        }
+    }
+
+    /// 合并紧随其后的命令，成功时刷新时间戳和估算内存
+    fn merge(&mut self, next: &dyn Command) -> bool {
+        if !self.command.merge_with(next) {
+            return false;
+        }
+        self.timestamp = now_millis();
+        self.size = self.command.estimated_size();
+        true
+    }
+
+    fn info(&self, index: usize) -> HistoryEntryInfo {
+        HistoryEntryInfo {
+            index,
+            description: self.command.description(),
+            timestamp: self.timestamp,
+            affected: self.command.affected_entities(),
+        }
+    }
+}
+
+/// 多步撤销/重做的结果
+pub struct HistoryJump {
+    pub result: CommandResult,
+    /// 实际完成的步数
+    pub steps: usize,
+    /// 所有已处理条目的受影响要素（已去重）
+    pub affected: FeatureSelection,
+}
+
+/// 历史记录容量限制
+#[derive(Debug, Clone, Copy)]
+pub struct HistoryLimits {
+    /// 最大条目数（撤销 + 重做）
+    pub max_entries: usize,
+    /// 最大估算内存（字节）
+    pub max_bytes: usize,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 500,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// 连续编辑合并的时间窗口（毫秒）
+///
+/// 与上一条历史记录间隔不超过该值的同类编辑（同一节点的移动、同一要素的标签编辑）
+/// 合并为一条历史记录
+const MERGE_WINDOW_MS: u64 = 1000;
+
+/// 进行中的事务：已执行但尚未进入历史记录的命令
+struct Transaction {
+    label: String,
+    commands: Vec<Box<dyn Command>>,
+}
+
+/// 历史记录管理器
+pub struct HistoryManager {
+    undo_stack: Mutex<Vec<HistoryEntry>>,
+    redo_stack: Mutex<Vec<HistoryEntry>>,
+    /// 事务进行期间，执行的命令暂存于此，提交时合并为一个 CompositeCommand
+    transaction: Mutex<Option<Transaction>>,
+    limits: Mutex<HistoryLimits>,
+    /// 因超出容量被裁剪的条目所影响的要素
+    ///
+    /// 这些编辑已无法撤销，但仍属于导出的变更集，因此只丢弃命令本身、保留要素 ID
+    trimmed: Mutex<(usize, FeatureSelection)>,
+    /// 崩溃恢复日志（未启用时为 None）
+    journal: Mutex<Option<Journal>>,
+    /// 存在待处理的遗留日志时锁定编辑，避免新编辑与之后重放的日志冲突
+    locked: AtomicBool,
+    /// 被编辑要素的基准版本
+    changes: ChangeTracker,
+    /// 数据修订号，每次执行、撤销、重做命令后递增
+    revision: AtomicU64,
+    /// 按修订号缓存的变更集
+    changed_cache: Mutex<Option<(u64, Arc<ChangedSet>)>>,
+    /// 按修订号缓存的成员 → 所属 Relation 反查表
+    parents_cache: Mutex<Option<(u64, Arc<RelationParents>)>>,
+    /// 按修订号缓存的 multipolygon/boundary Relation 包围盒索引
+    area_relations_cache: Mutex<Option<(u64, Arc<AreaRelationIndex>)>>,
+}
+
+impl Default for HistoryManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistoryManager {
+    pub fn new() -> Self {
+        Self {
+            undo_stack: Mutex::new(Vec::new()),
+            redo_stack: Mutex::new(Vec::new()),
+            transaction: Mutex::new(None),
+            limits: Mutex::new(HistoryLimits::default()),
+            trimmed: Mutex::new((0, FeatureSelection::default())),
+            journal: Mutex::new(None),
+            locked: AtomicBool::new(false),
+            changes: ChangeTracker::new(),
+            revision: AtomicU64::new(0),
+            changed_cache: Mutex::new(None),
+            parents_cache: Mutex::new(None),
+            area_relations_cache: Mutex::new(None),
+        }
+    }
+
+    /// 变更跟踪（被编辑要素的基准版本）
+    pub fn changes(&self) -> &ChangeTracker {
+        &self.changes
+    }
+
+    /// 已变更要素的 ID 集合
+    ///
+    /// 按修订号缓存：两次编辑之间的视口查询不重复比较全部基准版本
+    pub fn changed_set(&self, store: &OsmStore) -> Arc<ChangedSet> {
+        self.cached(&self.changed_cache, || self.changes.changed_set(store))
+    }
+
+    /// 成员 → 所属 Relation 反查表
+    ///
+    /// 按修订号缓存：拾取要素时不必每次遍历全部 Relation
+    pub fn relation_parents(&self, store: &OsmStore) -> Arc<RelationParents> {
+        self.cached(&self.parents_cache, || store.relation_parents())
+    }
+
+    /// multipolygon/boundary Relation 包围盒索引
+    ///
+    /// 按修订号缓存：查询点所在的面时只组装包围盒包含该点的 Relation
+    pub fn area_relation_index(&self, store: &OsmStore) -> Arc<AreaRelationIndex> {
+        self.cached(&self.area_relations_cache, || store.area_relation_index())
+    }
+
+    /// 取当前修订号下的缓存结果，修订号变化后重新构建
+    fn cached<T>(&self, cache: &Mutex<Option<(u64, Arc<T>)>>, build: impl FnOnce() -> T) -> Arc<T> {
+        let revision = self.revision.load(Ordering::SeqCst);
+        let mut cache = cache.lock().unwrap();
+        match cache.as_ref() {
+            Some((cached, value)) if *cached == revision => Arc::clone(value),
+            _ => {
+                let value = Arc::new(build());
+                *cache = Some((revision, Arc::clone(&value)));
+                value
+            }
+        }
+    }
+
+    /// 执行命令的正向或逆向操作，前后同步受影响要素的标签索引，并递增修订号
+    fn reindexed(
+        &self,
+        store: &OsmStore,
+        affected: &FeatureSelection,
+        op: impl FnOnce() -> CommandResult,
+    ) -> CommandResult {
+        store.unindex_tags(affected);
+        let result = op();
+        store.index_tags(affected);
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        result
+    }
+
+    /// 挂接或移除崩溃恢复日志
+    pub fn set_journal(&self, journal: Option<Journal>) {
+        *self.journal.lock().unwrap() = journal;
+    }
+
+    /// 移除崩溃恢复日志并删除日志文件（正常退出时调用）
+    pub fn close_journal(&self) {
+        if let Some(journal) = self.journal.lock().unwrap().take() {
+            journal.remove();
+        }
+    }
+
+    /// 锁定或解锁编辑
+    ///
+    /// 锁定期间执行、撤销、重做和开始事务都会失败
+    pub fn set_locked(&self, locked: bool) {
+        self.locked.store(locked, Ordering::SeqCst);
+    }
+
+    fn locked_failure(&self) -> Option<CommandResult> {
+        self.locked
+            .load(Ordering::SeqCst)
+            .then(|| CommandResult::failure("Replay or discard the recovery journal first"))
+    }
+
+    /// 写入日志事件
+    ///
+    /// 未启用日志时不构造事件；写入失败时停用日志，不影响编辑本身
+    fn log(&self, event: impl FnOnce() -> JournalEvent) {
+        let mut journal = self.journal.lock().unwrap();
+        if let Some(file) = journal.as_mut() {
+            if file.append(&event()).is_err() {
+                *journal = None;
+            }
+        }
+    }
+
+    /// 执行命令并加入历史记录
+    ///
+    /// 事务进行期间命令暂存在事务中，提交时才进入历史记录；
+    /// 在合并时间窗口内的连续同类编辑合并到上一条历史记录
+    pub fn execute(&self, command: Box<dyn Command>, store: &OsmStore) -> CommandResult {
+        self.execute_with(command, store, None)
+    }
+
+    /// 重放日志时执行命令：是否合并到上一条历史记录由日志决定，而非时间窗口
+    pub fn replay_execute(
+        &self,
+        command: Box<dyn Command>,
+        store: &OsmStore,
+        merged: bool,
+    ) -> CommandResult {
+        self.execute_with(command, store, Some(merged))
+    }
+
+    fn execute_with(
+        &self,
+        command: Box<dyn Command>,
+        store: &OsmStore,
+        merge: Option<bool>,
+    ) -> CommandResult {
+        if let Some(failure) = self.locked_failure() {
+            return failure;
+        }
+        let affected = command.affected_entities();
+        self.changes.capture(store, &affected);
+        let result = self.reindexed(store, &affected, || command.apply(store));
+
+        if result.success {
+            if let Some(transaction) = self.transaction.lock().unwrap().as_mut() {
+                self.log(|| JournalEvent::Execute {
+                    command: command.record(),
+                });
+                transaction.commands.push(command);
+                return result;
+            }
+
+            let mut undo_stack = self.undo_stack.lock().unwrap();
+            let mut redo_stack = self.redo_stack.lock().unwrap();
+
+            // 撤销之后的编辑不与更早的条目合并
+            let merged = redo_stack.is_empty()
+                && match undo_stack.last_mut() {
+                    Some(last) => {
+                        let within_window = merge.unwrap_or_else(|| {
+                            now_millis().saturating_sub(last.timestamp) <= MERGE_WINDOW_MS
+                        });
+                        within_window && last.merge(command.as_ref())
+                    }
+                    None => false,
+                };
+
+            self.log(|| {
+                let command = command.record();
+                if merged {
+                    JournalEvent::Merge { command }
+                } else {
+                    JournalEvent::Execute { command }
+                }
+            });
+
+            if !merged {
+                undo_stack.push(HistoryEntry::new(command));
+            }
+            redo_stack.clear();
+            self.enforce_limits(&mut undo_stack, &mut redo_stack);
+        }
+
+        result
+    }
+
+    /// 裁剪历史记录直到满足容量限制
+    ///
+    /// 先丢弃最旧的撤销条目（至少保留最新一条），再丢弃最远的重做条目。
+    /// 变更集依赖条目触及要素的基准版本，只有基准版本已由 `ChangeTracker`
+    /// 保存的撤销条目才能丢弃（执行前已捕获，正常情况下总是满足）
+    fn enforce_limits(
+        &self,
+        undo_stack: &mut Vec<HistoryEntry>,
+        redo_stack: &mut Vec<HistoryEntry>,
+    ) {
+        let limits = *self.limits.lock().unwrap();
+        let mut bytes: usize = undo_stack.iter().chain(redo_stack.iter()).map(|e| e.size).sum();
+        let over = |entries: usize, bytes: usize| {
+            entries > limits.max_entries || bytes > limits.max_bytes
+        };
+
+        let mut trimmed = self.trimmed.lock().unwrap();
+        let mut drop_count = 0;
+        while undo_stack.len() - drop_count > 1
+            && over(undo_stack.len() - drop_count + redo_stack.len(), bytes)
+        {
+            let entry = &undo_stack[drop_count];
+            let affected = entry.command.affected_entities();
+            if !self.changes.tracks(&affected) {
+                break;
+            }
+            bytes -= entry.size;
+            trimmed.1.extend(affected);
+            drop_count += 1;
+        }
+        if drop_count > 0 {
+            undo_stack.drain(..drop_count);
+            trimmed.0 += drop_count;
+            trimmed.1.dedup();
+        }
+
+        // 重做栈底部是距离当前状态最远的条目
+        let mut redo_drop = 0;
+        while redo_drop < redo_stack.len()
+            && over(undo_stack.len() + redo_stack.len() - redo_drop, bytes)
+        {
+            bytes -= redo_stack[redo_drop].size;
+            redo_drop += 1;
+        }
+        redo_stack.drain(..redo_drop);
+    }
+
+    /// 设置容量限制并立即裁剪
+    pub fn set_limits(&self, limits: HistoryLimits) {
+        *self.limits.lock().unwrap() = limits;
+        let mut undo_stack = self.undo_stack.lock().unwrap();
+        let mut redo_stack = self.redo_stack.lock().unwrap();
+        self.enforce_limits(&mut undo_stack, &mut redo_stack);
+    }
+
+    pub fn limits(&self) -> HistoryLimits {
+        *self.limits.lock().unwrap()
+    }
+
+    /// 历史记录的估算内存（字节）
+    pub fn memory_usage(&self) -> usize {
+        let undo: usize = self.undo_stack.lock().unwrap().iter().map(|e| e.size).sum();
+        let redo: usize = self.redo_stack.lock().unwrap().iter().map(|e| e.size).sum();
+        undo + redo
+    }
+
+    /// 已裁剪的条目数及其影响的要素
+    pub fn trimmed(&self) -> (usize, FeatureSelection) {
+        self.trimmed.lock().unwrap().clone()
+    }
+
+    /// 撤销上一个命令
+    pub fn undo(&self, store: &OsmStore) -> CommandResult {
+        if let Some(failure) = self.locked_failure() {
+            return failure;
+        }
+        if self.in_transaction() {
+            return CommandResult::failure("Transaction in progress");
+        }
+        self.undo_entry(store).0
+    }
+
+    /// 重做上一个撤销的命令
+    pub fn redo(&self, store: &OsmStore) -> CommandResult {
+        if let Some(failure) = self.locked_failure() {
+            return failure;
+        }
+        if self.in_transaction() {
+            return CommandResult::failure("Transaction in progress");
+        }
+        self.redo_entry(store).0
+    }
+
+    fn undo_entry(&self, store: &OsmStore) -> (CommandResult, FeatureSelection) {
+        let entry = {
+            let mut undo_stack = self.undo_stack.lock().unwrap();
+            undo_stack.pop()
+        };
+
+        if let Some(entry) = entry {
+            let affected = entry.command.affected_entities();
+            let result = self.reindexed(store, &affected, || entry.command.undo(store));
+
+            if result.success {
+                self.redo_stack.lock().unwrap().push(entry);
+                self.log(|| JournalEvent::Undo);
+            }
+
+            (result, affected)
+        } else {
+            (
+                CommandResult::failure("Nothing to undo"),
+                FeatureSelection::default(),
+            )
+        }
+    }
+
+    fn redo_entry(&self, store: &OsmStore) -> (CommandResult, FeatureSelection) {
+        let entry = {
+            let mut redo_stack = self.redo_stack.lock().unwrap();
+            redo_stack.pop()
+        };
+
+        if let Some(entry) = entry {
+            let affected = entry.command.affected_entities();
+            let result = self.reindexed(store, &affected, || entry.command.apply(store));
+
+            if result.success {
+                self.undo_stack.lock().unwrap().push(entry);
+                self.log(|| JournalEvent::Redo);
+            }
+
+            (result, affected)
+        } else {
+            (
+                CommandResult::failure("Nothing to redo"),
+                FeatureSelection::default(),
+            )
+        }
+    }
+
+    /// 连续撤销，直到撤销栈只剩 `index` 个条目（即撤销第 index 条及之后的全部条目）
+    pub fn undo_to(&self, index: usize, store: &OsmStore) -> HistoryJump {
+        let count = self.undo_count();
+        if index >= count {
+            return HistoryJump {
+                result: CommandResult::failure("Invalid history index"),
+                steps: 0,
+                affected: FeatureSelection::default(),
+            };
+        }
+        self.walk(count - index, store, Self::undo_entry)
+    }
+
+    /// 连续重做第 0 到第 `index` 条重做条目（第 0 条为下一个将被重做的条目）
+    pub fn redo_to(&self, index: usize, store: &OsmStore) -> HistoryJump {
+        if index >= self.redo_count() {
+            return HistoryJump {
+                result: CommandResult::failure("Invalid history index"),
+                steps: 0,
+                affected: FeatureSelection::default(),
+            };
+        }
+        self.walk(index + 1, store, Self::redo_entry)
+    }
+
+    /// `undo_to(index)` 将要撤销的条目的受影响要素（需在撤销前调用）
+    pub fn undo_to_affected(&self, index: usize) -> FeatureSelection {
+        let undo_stack = self.undo_stack.lock().unwrap();
+        Self::collect_affected(undo_stack.iter().skip(index))
+    }
+
+    /// `redo_to(index)` 将要重做的条目的受影响要素（需在重做前调用）
+    pub fn redo_to_affected(&self, index: usize) -> FeatureSelection {
+        let redo_stack = self.redo_stack.lock().unwrap();
+        Self::collect_affected(redo_stack.iter().rev().take(index + 1))
+    }
+
+    fn collect_affected<'a>(entries: impl Iterator<Item = &'a HistoryEntry>) -> FeatureSelection {
+        let mut affected = FeatureSelection::default();
+        for entry in entries {
+            affected.extend(entry.command.affected_entities());
+        }
+        affected.dedup();
+        affected
+    }
+
+    fn walk(
+        &self,
+        steps: usize,
+        store: &OsmStore,
+        step: fn(&Self, &OsmStore) -> (CommandResult, FeatureSelection),
+    ) -> HistoryJump {
+        let mut jump = HistoryJump {
+            result: CommandResult::success(false),
+            steps: 0,
+            affected: FeatureSelection::default(),
+        };
+        if let Some(failure) = self.locked_failure() {
+            jump.result = failure;
+            return jump;
+        }
+        if self.in_transaction() {
+            jump.result = CommandResult::failure("Transaction in progress");
+            return jump;
+        }
+
+        let mut needs_redraw = false;
+        for _ in 0..steps {
+            let (result, affected) = step(self, store);
+            if !result.success {
+                jump.result = result;
+                break;
+            }
+            needs_redraw |= result.needs_redraw;
+            jump.affected.extend(affected);
+            jump.steps += 1;
+        }
+
+        if jump.result.success {
+            jump.result = CommandResult::success(needs_redraw);
+        }
+        jump.affected.dedup();
+        jump
+    }
+
+    /// 列出全部历史条目
+    ///
+    /// 撤销条目从旧到新排列；重做条目按重做顺序排列（第 0 条为下一个将被重做的条目）
+    pub fn entries(&self) -> (Vec<HistoryEntryInfo>, Vec<HistoryEntryInfo>) {
+        let undo = self
+            .undo_stack
+            .lock()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| entry.info(i))
+            .collect();
+        let redo = self
+            .redo_stack
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, entry)| entry.info(i))
+            .collect();
+        (undo, redo)
+    }
+
+    /// 开始事务：之后执行的命令合并为一个历史记录
+    pub fn begin_transaction(&self, label: &str) -> CommandResult {
+        if let Some(failure) = self.locked_failure() {
+            return failure;
+        }
+        let mut transaction = self.transaction.lock().unwrap();
+        if transaction.is_some() {
+            return CommandResult::failure("Transaction already in progress");
+        }
+        *transaction = Some(Transaction {
+            label: label.to_string(),
+            commands: Vec::new(),
+        });
+        drop(transaction);
+
+        self.log(|| JournalEvent::Begin {
+            label: label.to_string(),
+        });
+        CommandResult::success(false)
+    }
+
+    /// 提交事务：暂存的命令作为一个 CompositeCommand 进入历史记录
+    pub fn commit_transaction(&self) -> CommandResult {
+        let transaction = match self.transaction.lock().unwrap().take() {
+            Some(transaction) => transaction,
+            None => return CommandResult::failure("No transaction in progress"),
+        };
+
+        if !transaction.commands.is_empty() {
+            let mut undo_stack = self.undo_stack.lock().unwrap();
+            let mut redo_stack = self.redo_stack.lock().unwrap();
+
+            undo_stack.push(HistoryEntry::new(Box::new(CompositeCommand {
+                label: transaction.label,
+                children: transaction.commands,
+            })));
+            redo_stack.clear();
+            self.enforce_limits(&mut undo_stack, &mut redo_stack);
+        }
+
+        self.log(|| JournalEvent::Commit);
+        CommandResult::success(false)
+    }
+
+    /// 中止事务：按逆序撤销暂存的命令并丢弃
+    pub fn abort_transaction(&self, store: &OsmStore) -> CommandResult {
+        let transaction = match self.transaction.lock().unwrap().take() {
+            Some(transaction) => transaction,
+            None => return CommandResult::failure("No transaction in progress"),
+        };
+
+        let mut needs_redraw = false;
+        for command in transaction.commands.iter().rev() {
+            let affected = command.affected_entities();
+            needs_redraw |= self
+                .reindexed(store, &affected, || command.undo(store))
+                .needs_redraw;
+        }
+
+        self.log(|| JournalEvent::Abort);
+        CommandResult::success(needs_redraw)
+    }
+
+    /// 是否有进行中的事务
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.lock().unwrap().is_some()
+    }
+
     /// 获取可撤销的命令数量
     pub fn undo_count(&self) -> usize {
         self.undo_stack.lock().unwrap().len()
@@ -355,5 +2849,486 @@ impl HistoryManager {
     pub fn clear(&self) {
         self.undo_stack.lock().unwrap().clear();
         self.redo_stack.lock().unwrap().clear();
+        self.transaction.lock().unwrap().take();
+        *self.trimmed.lock().unwrap() = (0, FeatureSelection::default());
+        self.changes.clear();
+        self.revision.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn way(id: i64, node_refs: Vec<i64>) -> OsmWay {
+        OsmWay {
+            id,
+            node_refs,
+            tags: vec![],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        }
+    }
+
+    fn member(member_type: MemberType, ref_id: i64, role: &str) -> RelationMember {
+        RelationMember {
+            member_type,
+            ref_id,
+            role: role.to_string(),
+        }
+    }
+
+    fn relation(id: i64, tags: &[(&str, &str)], members: Vec<RelationMember>) -> OsmRelation {
+        OsmRelation {
+            id,
+            members,
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_delete_way_removes_relation_member() {
+        let store = OsmStore::new();
+        store.insert_way(way(10, vec![1, 2]));
+        store.insert_way(way(11, vec![2, 3]));
+        store.relations.insert(
+            100,
+            relation(
+                100,
+                &[("type", "route")],
+                vec![
+                    member(MemberType::Way, 11, ""),
+                    member(MemberType::Way, 10, ""),
+                    member(MemberType::Way, 11, ""),
+                ],
+            ),
+        );
+
+        let way = store.ways.get(&11).unwrap().clone();
+        let relation_cascade = RelationCascade::plan(&store, &[(MemberType::Way, 11)]);
+        assert_eq!(relation_cascade.affected_relation_ids(), vec![100]);
+        assert!(relation_cascade.deleted_relations.is_empty());
+
+        let command = DeleteWayCommand {
+            way,
+            relation_cascade,
+        };
+        assert!(command.apply(&store).success);
+        let members = store.relations.get(&100).unwrap().members.clone();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].ref_id, 10);
+
+        assert!(command.undo(&store).success);
+        let ids: Vec<i64> = store
+            .relations
+            .get(&100)
+            .unwrap()
+            .members
+            .iter()
+            .map(|m| m.ref_id)
+            .collect();
+        assert_eq!(ids, vec![11, 10, 11]);
+    }
+
+    #[test]
+    fn test_invalid_relation_cascades_to_parent() {
+        let store = OsmStore::new();
+        store.insert_way(way(10, vec![1, 2, 3, 1]));
+        store.insert_way(way(11, vec![4, 5, 6, 4]));
+        store.relations.insert(
+            100,
+            relation(
+                100,
+                &[("type", "multipolygon")],
+                vec![
+                    member(MemberType::Way, 10, "outer"),
+                    member(MemberType::Way, 11, "inner"),
+                ],
+            ),
+        );
+        store.relations.insert(
+            200,
+            relation(
+                200,
+                &[("type", "site")],
+                vec![
+                    member(MemberType::Relation, 100, ""),
+                    member(MemberType::Node, 7, ""),
+                ],
+            ),
+        );
+
+        let cascade = RelationCascade::plan(&store, &[(MemberType::Way, 10)]);
+        assert_eq!(cascade.deleted_relation_ids(), vec![100]);
+        assert_eq!(cascade.affected_relation_ids(), vec![200]);
+
+        cascade.apply(&store);
+        assert!(!store.relations.contains_key(&100));
+        assert_eq!(store.relations.get(&200).unwrap().members.len(), 1);
+
+        cascade.undo(&store);
+        assert_eq!(store.relations.get(&100).unwrap().members.len(), 2);
+        assert_eq!(
+            store.relations.get(&200).unwrap().members[0].member_type,
+            MemberType::Relation
+        );
+    }
+
+    fn node(id: i64, lon: f64, lat: f64, tags: &[(&str, &str)]) -> OsmNode {
+        OsmNode {
+            id,
+            lon,
+            lat,
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_extract_and_attach_node() {
+        let store = OsmStore::new();
+        store.insert_node(node(1, 0.0, 0.0, &[]));
+        store.insert_node(node(2, 0.001, 0.0, &[("shop", "bakery")]));
+        store.insert_node(node(3, 0.002, 0.0, &[]));
+        store.insert_way(way(10, vec![1, 2, 3]));
+        store.relations.insert(
+            100,
+            relation(100, &[("type", "site")], vec![member(MemberType::Node, 2, "")]),
+        );
+        store.rebuild_indices();
+
+        let extract = ExtractNodeCommand {
+            vertex_id: 2,
+            poi: node(-1, 0.001, 0.0001, &[("shop", "bakery")]),
+            relation_members: vec![(100, vec![0])],
+        };
+        assert!(extract.apply(&store).success);
+        assert!(store.nodes.get(&2).unwrap().tags.is_empty());
+        assert_eq!(store.relations.get(&100).unwrap().members[0].ref_id, -1);
+
+        let attach = AttachNodeCommand {
+            node_id: -1,
+            way_id: 10,
+            index: 2,
+            old_lon: 0.001,
+            old_lat: 0.0001,
+            new_lon: 0.0015,
+            new_lat: 0.0,
+        };
+        assert!(attach.apply(&store).success);
+        assert_eq!(store.ways.get(&10).unwrap().node_refs, vec![1, 2, -1, 3]);
+        assert_eq!(*store.node_ref_count.get(&-1).unwrap(), 1);
+
+        assert!(attach.undo(&store).success);
+        assert_eq!(store.ways.get(&10).unwrap().node_refs, vec![1, 2, 3]);
+        assert_eq!(*store.node_ref_count.get(&-1).unwrap(), 0);
+
+        assert!(extract.undo(&store).success);
+        assert!(!store.nodes.contains_key(&-1));
+        assert_eq!(store.nodes.get(&2).unwrap().tags.len(), 1);
+        assert_eq!(store.relations.get(&100).unwrap().members[0].ref_id, 2);
+    }
+
+    fn move_command(node_id: i64, new_lon: f64) -> Box<dyn Command> {
+        Box::new(MoveNodeCommand {
+            node_id,
+            old_lon: 0.0,
+            old_lat: 0.0,
+            new_lon,
+            new_lat: 0.0,
+        })
+    }
+
+    #[test]
+    fn test_composite_rolls_back_on_failure() {
+        let store = OsmStore::new();
+        store.insert_node(node(1, 0.0, 0.0, &[]));
+        store.rebuild_indices();
+
+        let composite = CompositeCommand {
+            label: "Drag".to_string(),
+            children: vec![move_command(1, 1.0), move_command(404, 1.0)],
+        };
+        assert!(!composite.apply(&store).success);
+        assert_eq!(store.nodes.get(&1).unwrap().lon, 0.0);
+    }
+
+    #[test]
+    fn test_reshape_rolls_back_on_failure() {
+        let store = OsmStore::new();
+        for id in 1..=3 {
+            store.insert_node(node(id, id as f64, 0.0, &[]));
+        }
+        store.insert_way(way(10, vec![1, 2, 3]));
+        store.rebuild_indices();
+
+        // 第二个 Way 不存在：Way 10 的序列与新增节点都必须撤回
+        let reshape = ReshapeCommand {
+            label: "Reshape".to_string(),
+            moves: Vec::new(),
+            added_nodes: vec![node(-1, 1.5, 0.0, &[])],
+            removed_nodes: Vec::new(),
+            way_changes: vec![
+                WayNodesChange {
+                    way_id: 10,
+                    old_refs: vec![1, 2, 3],
+                    new_refs: vec![1, -1, 2, 3],
+                },
+                WayNodesChange {
+                    way_id: 404,
+                    old_refs: vec![],
+                    new_refs: vec![1, 2],
+                },
+            ],
+        };
+        assert!(!reshape.apply(&store).success);
+        assert_eq!(store.ways.get(&10).unwrap().node_refs, vec![1, 2, 3]);
+        assert!(!store.nodes.contains_key(&-1));
+        assert_eq!(*store.node_ref_count.get(&2).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_transaction_groups_commands() {
+        let store = OsmStore::new();
+        store.insert_node(node(1, 0.0, 0.0, &[]));
+        store.rebuild_indices();
+        let history = HistoryManager::new();
+
+        assert!(history.begin_transaction("Drag").success);
+        history.execute(move_command(1, 1.0), &store);
+        history.execute(move_command(1, 2.0), &store);
+        assert!(!history.undo(&store).success);
+        assert!(history.commit_transaction().success);
+        assert_eq!(history.undo_count(), 1);
+
+        assert!(history.undo(&store).success);
+        assert_eq!(store.nodes.get(&1).unwrap().lon, 0.0);
+
+        assert!(history.begin_transaction("Drag").success);
+        history.execute(move_command(1, 3.0), &store);
+        assert!(history.abort_transaction(&store).success);
+        assert_eq!(store.nodes.get(&1).unwrap().lon, 0.0);
+        assert_eq!(history.undo_count(), 0);
+    }
+
+    #[test]
+    fn test_consecutive_moves_merge() {
+        let store = OsmStore::new();
+        store.insert_node(node(1, 0.0, 0.0, &[]));
+        store.insert_node(node(2, 0.0, 0.0, &[]));
+        store.rebuild_indices();
+        let history = HistoryManager::new();
+
+        history.execute(move_command(1, 1.0), &store);
+        history.execute(move_command(1, 2.0), &store);
+        assert_eq!(history.undo_count(), 1);
+
+        // 不同节点不合并
+        history.execute(move_command(2, 1.0), &store);
+        assert_eq!(history.undo_count(), 2);
+
+        // 超出时间窗口不合并
+        history.undo_stack.lock().unwrap().last_mut().unwrap().timestamp = 0;
+        history.execute(move_command(2, 2.0), &store);
+        assert_eq!(history.undo_count(), 3);
+
+        // 撤销之后的编辑不与更早的条目合并
+        assert!(history.undo(&store).success);
+        history.execute(move_command(2, 3.0), &store);
+        assert_eq!(history.undo_count(), 3);
+
+        assert!(history.undo_to(0, &store).result.success);
+        assert_eq!(store.nodes.get(&1).unwrap().lon, 0.0);
+    }
+
+    #[test]
+    fn test_undo_to_and_redo_to() {
+        let store = OsmStore::new();
+        for id in [1, 2, 3] {
+            store.insert_node(node(id, 0.0, 0.0, &[]));
+        }
+        store.rebuild_indices();
+        let history = HistoryManager::new();
+        for (id, lon) in [(1, 1.0), (2, 2.0), (3, 3.0)] {
+            history.execute(move_command(id, lon), &store);
+        }
+
+        let (undo, redo) = history.entries();
+        assert_eq!(undo.len(), 3);
+        assert!(redo.is_empty());
+        assert_eq!(undo[0].affected.node_ids, vec![1]);
+
+        let jump = history.undo_to(1, &store);
+        assert!(jump.result.success);
+        assert_eq!(jump.steps, 2);
+        assert_eq!(jump.affected.node_ids, vec![2, 3]);
+        assert_eq!((history.undo_count(), history.redo_count()), (1, 2));
+
+        let (_, redo) = history.entries();
+        assert!(redo[0].description.contains("to (2.000000"));
+        assert!(history.redo_to(0, &store).result.success);
+        assert_eq!((history.undo_count(), history.redo_count()), (2, 1));
+        assert!(!history.redo_to(1, &store).result.success);
+    }
+
+    #[test]
+    fn test_history_limits_trim_oldest() {
+        let store = OsmStore::new();
+        for id in [1, 2, 3] {
+            store.insert_node(node(id, 0.0, 0.0, &[]));
+        }
+        store.rebuild_indices();
+        let history = HistoryManager::new();
+        history.set_limits(HistoryLimits {
+            max_entries: 2,
+            max_bytes: usize::MAX,
+        });
+
+        for (id, lon) in [(1, 1.0), (2, 2.0), (3, 3.0)] {
+            history.execute(move_command(id, lon), &store);
+        }
+        assert_eq!(history.undo_count(), 2);
+        assert!(history.memory_usage() >= 2 * size_of::<MoveNodeCommand>());
+
+        let (trimmed_count, trimmed_entities) = history.trimmed();
+        assert_eq!(trimmed_count, 1);
+        assert_eq!(trimmed_entities.node_ids, vec![1]);
+    }
+
+    #[test]
+    fn test_locked_history_rejects_edits() {
+        let store = OsmStore::new();
+        store.insert_node(node(1, 0.0, 0.0, &[]));
+        store.rebuild_indices();
+        let history = HistoryManager::new();
+        history.execute(move_command(1, 1.0), &store);
+
+        history.set_locked(true);
+        assert!(!history.execute(move_command(1, 2.0), &store).success);
+        assert!(!history.undo(&store).success);
+        assert!(!history.undo_to(0, &store).result.success);
+        assert!(!history.begin_transaction("Drag").success);
+        assert_eq!(store.nodes.get(&1).unwrap().lon, 1.0);
+
+        history.set_locked(false);
+        assert!(history.undo(&store).success);
+        assert_eq!(store.nodes.get(&1).unwrap().lon, 0.0);
+    }
+
+    #[test]
+    fn test_changes_survive_trimming() {
+        let store = OsmStore::new();
+        for id in [1, 2, 3] {
+            store.insert_node(node(id, 0.0, 0.0, &[]));
+        }
+        store.rebuild_indices();
+        let history = HistoryManager::new();
+        history.set_limits(HistoryLimits {
+            max_entries: 1,
+            max_bytes: usize::MAX,
+        });
+
+        for (id, lon) in [(1, 1.0), (2, 2.0), (3, 3.0)] {
+            history.execute(move_command(id, lon), &store);
+        }
+        assert_eq!(history.undo_count(), 1);
+
+        // 已裁剪条目触及的节点仍以加载时的版本为基准
+        let changed = || -> Vec<i64> {
+            let changes = history.changes().changes(&store);
+            changes.iter().map(|c| c.id).collect()
+        };
+        assert_eq!(changed(), vec![1, 2, 3]);
+        assert_eq!(history.changes().base_node(1).unwrap().lon, 0.0);
+
+        assert!(history.undo(&store).success);
+        assert_eq!(changed(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_relation_parents_follow_revision() {
+        let store = OsmStore::new();
+        store.insert_node(node(1, 0.0, 0.0, &[]));
+        store.rebuild_indices();
+        let history = HistoryManager::new();
+        let parents_of_node = || -> Vec<i64> {
+            let parents = history.relation_parents(&store);
+            parents
+                .get(&(MemberType::Node, 1))
+                .cloned()
+                .unwrap_or_default()
+        };
+        assert!(parents_of_node().is_empty());
+
+        let member = RelationMember {
+            member_type: MemberType::Node,
+            ref_id: 1,
+            role: String::new(),
+        };
+        let command = CreateRelationCommand {
+            relation: OsmRelation {
+                id: -1,
+                members: vec![member.clone()],
+                tags: vec![],
+            },
+        };
+        assert!(history.execute(Box::new(command), &store).success);
+        assert_eq!(parents_of_node(), vec![-1]);
+        assert!(Arc::ptr_eq(
+            &history.relation_parents(&store),
+            &history.relation_parents(&store)
+        ));
+        assert!(history.undo(&store).success);
+        assert!(parents_of_node().is_empty());
+        assert!(history.redo(&store).success);
+        assert_eq!(parents_of_node(), vec![-1]);
+
+        // 历史管理之外的修改（如加载文件）在清空历史后才使缓存失效
+        store.relations.insert(
+            5,
+            OsmRelation {
+                id: 5,
+                members: vec![member],
+                tags: vec![],
+            },
+        );
+        assert_eq!(parents_of_node(), vec![-1]);
+        history.clear();
+        let mut parents = parents_of_node();
+        parents.sort_unstable();
+        assert_eq!(parents, vec![-1, 5]);
+    }
+
+    #[test]
+    fn test_area_relation_index_follows_revision() {
+        let store = OsmStore::new();
+        store.insert_node(node(1, 0.0, 0.0, &[]));
+        store.insert_node(node(2, 2.0, 1.0, &[]));
+        store.insert_way(way(10, vec![1, 2]));
+        store.rebuild_indices();
+        let history = HistoryManager::new();
+        let bounds = || -> Vec<(i64, f64, f64)> {
+            let index = history.area_relation_index(&store);
+            index.iter().map(|e| (e.id, e.max_lon, e.max_lat)).collect()
+        };
+        assert!(bounds().is_empty());
+
+        let command = CreateRelationCommand {
+            relation: OsmRelation {
+                id: -1,
+                members: vec![member(MemberType::Way, 10, "outer")],
+                tags: vec![("type".to_string(), "multipolygon".to_string())],
+            },
+        };
+        assert!(history.execute(Box::new(command), &store).success);
+        assert_eq!(bounds(), vec![(-1, 2.0, 1.0)]);
+        assert!(history.undo(&store).success);
+        assert!(bounds().is_empty());
     }
 }
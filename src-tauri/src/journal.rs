@@ -0,0 +1,385 @@
+//! 崩溃恢复日志
+//!
+//! 每次成功执行、撤销、重做的命令都以 JSON 行追加写入日志文件，
+//! 文件头记录源数据的指纹。程序异常退出后重新加载同一个源文件时，
+//! 按顺序重放日志即可恢复编辑结果以及撤销/重做栈。
+//!
+//! 日志位于源文件旁 (`<源文件>.journal`)，正常退出时删除；
+//! 崩溃时最后一行可能写入不完整，读取时忽略。
+//!
+//! 事件写入后即进入操作系统缓存，程序崩溃不会丢失；`fsync` 只防范系统崩溃，
+//! 因此按时间间隔批量同步，避免拖拽等高频编辑时每个事件都等待磁盘。
+
+use crate::history::{CommandRecord, HistoryManager};
+use crate::osm_store::OsmStore;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 日志格式版本
+const JOURNAL_VERSION: u32 = 1;
+
+/// 两次同步到磁盘的最小间隔
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 计算指纹时读取的源文件首尾长度（字节）
+const FINGERPRINT_SAMPLE_BYTES: u64 = 1024 * 1024;
+
+/// 日志文件头
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalHeader {
+    pub version: u32,
+    /// 源文件路径
+    pub source: String,
+    /// 源文件指纹
+    pub fingerprint: String,
+    /// 创建时间（Unix 毫秒）
+    pub created: u64,
+}
+
+/// 日志事件
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum JournalEvent {
+    Execute {
+        command: CommandRecord,
+    },
+    /// 执行后合并到上一条历史记录
+    Merge {
+        command: CommandRecord,
+    },
+    Undo,
+    Redo,
+    Begin {
+        label: String,
+    },
+    Commit,
+    Abort,
+}
+
+/// 打开的日志文件（只追加）
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+    /// 上次同步到磁盘的时间
+    synced: Instant,
+}
+
+impl Journal {
+    /// 创建日志文件（覆盖已有文件），写入文件头和初始事件
+    fn create(path: &Path, header: &JournalHeader, events: &[JournalEvent]) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        let mut buf = Vec::new();
+        write_line(&mut buf, header)?;
+        for event in events {
+            write_line(&mut buf, event)?;
+        }
+        file.write_all(&buf)?;
+        file.sync_data()?;
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            synced: Instant::now(),
+        })
+    }
+
+    /// 追加一个事件；距上次同步超过 `SYNC_INTERVAL` 时同步到磁盘
+    pub fn append(&mut self, event: &JournalEvent) -> io::Result<()> {
+        let mut buf = Vec::new();
+        write_line(&mut buf, event)?;
+        self.file.write_all(&buf)?;
+        if self.synced.elapsed() >= SYNC_INTERVAL {
+            self.file.sync_data()?;
+            self.synced = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// 关闭并删除日志文件
+    pub fn remove(self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Drop for Journal {
+    /// 关闭前同步尚未落盘的事件
+    fn drop(&mut self) {
+        let _ = self.file.sync_data();
+    }
+}
+
+fn write_line<T: Serialize>(buf: &mut Vec<u8>, value: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *buf, value)?;
+    buf.push(b'\n');
+    Ok(())
+}
+
+/// 读取日志：返回文件头和全部完整的事件
+fn read(path: &Path) -> io::Result<(JournalHeader, Vec<JournalEvent>)> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+    let header: JournalHeader = match lines.next() {
+        Some(line) => serde_json::from_str(&line?)?,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Empty journal")),
+    };
+
+    // 遇到不完整或无法解析的行即停止（崩溃时正在写入的最后一行）
+    let events = lines
+        .map_while(|line| line.ok().and_then(|l| serde_json::from_str(&l).ok()))
+        .collect();
+    Ok((header, events))
+}
+
+/// FNV-1a 哈希（跨版本稳定，用于文件指纹）
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// 源文件指纹：文件长度 + 首尾各 1 MiB 的哈希
+fn fingerprint(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    let mut sample = Vec::new();
+    (&mut file)
+        .take(FINGERPRINT_SAMPLE_BYTES)
+        .read_to_end(&mut sample)?;
+    if len > FINGERPRINT_SAMPLE_BYTES {
+        let tail_start = (len - FINGERPRINT_SAMPLE_BYTES).max(FINGERPRINT_SAMPLE_BYTES);
+        file.seek(SeekFrom::Start(tail_start))?;
+        file.read_to_end(&mut sample)?;
+    }
+
+    Ok(format!(
+        "{:x}-{:016x}",
+        len,
+        fnv1a(0xcbf2_9ce4_8422_2325, &sample)
+    ))
+}
+
+/// 与某个源文件绑定的日志
+#[derive(Debug, Clone)]
+pub struct JournalSource {
+    pub source: PathBuf,
+    pub fingerprint: String,
+}
+
+impl JournalSource {
+    pub fn new(source: &Path) -> io::Result<Self> {
+        Ok(Self {
+            source: source.to_path_buf(),
+            fingerprint: fingerprint(source)?,
+        })
+    }
+
+    /// 日志文件路径
+    pub fn journal_path(&self) -> PathBuf {
+        let mut path = self.source.clone().into_os_string();
+        path.push(".journal");
+        PathBuf::from(path)
+    }
+
+    fn header(&self) -> JournalHeader {
+        JournalHeader {
+            version: JOURNAL_VERSION,
+            source: self.source.to_string_lossy().into_owned(),
+            fingerprint: self.fingerprint.clone(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        }
+    }
+
+    /// 读取与当前源文件匹配、且包含事件的遗留日志
+    ///
+    /// 指纹或版本不匹配的日志视为过期，返回 None
+    pub fn pending(&self) -> Option<(JournalHeader, Vec<JournalEvent>)> {
+        let (header, events) = read(&self.journal_path()).ok()?;
+        let matches = header.version == JOURNAL_VERSION && header.fingerprint == self.fingerprint;
+        (matches && !events.is_empty()).then_some((header, events))
+    }
+
+    /// 开始新的日志（覆盖遗留日志）
+    pub fn start(&self) -> io::Result<Journal> {
+        Journal::create(&self.journal_path(), &self.header(), &[])
+    }
+
+    /// 以已重放的事件重写日志并继续追加
+    ///
+    /// 重写会丢弃不完整的末尾行以及重放失败之后的事件
+    pub fn resume(&self, events: &[JournalEvent]) -> io::Result<Journal> {
+        Journal::create(&self.journal_path(), &self.header(), events)
+    }
+}
+
+/// 日志重放结果
+pub struct Replay {
+    /// 成功重放的事件
+    pub events: Vec<JournalEvent>,
+    /// 第一个失败事件的错误信息（之后的事件不再重放）
+    pub error: Option<String>,
+    pub needs_redraw: bool,
+}
+
+/// 按顺序重放日志事件
+///
+/// 调用时历史记录不应挂接日志，否则重放的事件会被重复写入
+pub fn replay(history: &HistoryManager, store: &OsmStore, events: Vec<JournalEvent>) -> Replay {
+    let mut replay = Replay {
+        events: Vec::with_capacity(events.len()),
+        error: None,
+        needs_redraw: false,
+    };
+
+    for event in events {
+        let result = match &event {
+            JournalEvent::Execute { command } | JournalEvent::Merge { command } => {
+                let merged = matches!(event, JournalEvent::Merge { .. });
+                let command = command.clone().into_command();
+                // 命令中新建要素的本地 ID 已经固定，避免之后生成的 ID 与之冲突
+                let affected = command.affected_entities();
+                for &id in affected
+                    .node_ids
+                    .iter()
+                    .chain(&affected.way_ids)
+                    .chain(&affected.relation_ids)
+                {
+                    store.reserve_local_id(id);
+                }
+                history.replay_execute(command, store, merged)
+            }
+            JournalEvent::Undo => history.undo(store),
+            JournalEvent::Redo => history.redo(store),
+            JournalEvent::Begin { label } => history.begin_transaction(label),
+            JournalEvent::Commit => history.commit_transaction(),
+            JournalEvent::Abort => history.abort_transaction(store),
+        };
+
+        if !result.success {
+            replay.error = result.message;
+            break;
+        }
+        replay.needs_redraw |= result.needs_redraw;
+        replay.events.push(event);
+    }
+
+    replay
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{AddNodeCommand, MoveNodeCommand};
+    use crate::osm_store::OsmNode;
+
+    fn node(id: i64, lon: f64) -> OsmNode {
+        OsmNode {
+            id,
+            lon,
+            lat: 0.0,
+            tags: vec![],
+        }
+    }
+
+    fn temp_source(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("mosm-{}-{}.pbf", name, std::process::id()));
+        std::fs::write(&path, b"source data").unwrap();
+        path
+    }
+
+    #[test]
+    fn test_journal_replay_restores_history() {
+        let source_path = temp_source("replay");
+        let source = JournalSource::new(&source_path).unwrap();
+
+        let store = OsmStore::new();
+        store.add_node_with_index(node(1, 0.0));
+        let history = HistoryManager::new();
+        history.set_journal(Some(source.start().unwrap()));
+
+        let added = node(store.generate_local_id(), 1.0);
+        history.execute(Box::new(AddNodeCommand { node: added }), &store);
+        for new_lon in [0.5, 0.7] {
+            let command = MoveNodeCommand {
+                node_id: 1,
+                old_lon: store.nodes.get(&1).unwrap().lon,
+                old_lat: 0.0,
+                new_lon,
+                new_lat: 0.0,
+            };
+            history.execute(Box::new(command), &store);
+        }
+        history.undo(&store);
+
+        // 模拟崩溃：重新加载原始数据并重放
+        let (_, events) = source.pending().unwrap();
+        assert_eq!(events.len(), 4);
+        // 连续移动同一节点被合并为一条历史记录
+        assert!(matches!(events[2], JournalEvent::Merge { .. }));
+
+        let restored = OsmStore::new();
+        restored.add_node_with_index(node(1, 0.0));
+        let restored_history = HistoryManager::new();
+        let result = replay(&restored_history, &restored, events);
+
+        assert!(result.error.is_none());
+        assert_eq!(restored.nodes.get(&1).unwrap().lon, 0.0);
+        assert!(restored.nodes.contains_key(&-1));
+        assert_eq!(restored_history.undo_count(), 1);
+        assert_eq!(restored_history.redo_count(), 1);
+        assert_eq!(restored.generate_local_id(), -2);
+
+        std::fs::remove_file(source.journal_path()).unwrap();
+        std::fs::remove_file(&source_path).unwrap();
+    }
+
+    #[test]
+    fn test_close_journal_removes_file() {
+        let source_path = temp_source("close");
+        let source = JournalSource::new(&source_path).unwrap();
+        let store = OsmStore::new();
+        let history = HistoryManager::new();
+        history.set_journal(Some(source.start().unwrap()));
+        history.execute(
+            Box::new(AddNodeCommand {
+                node: node(-1, 0.0),
+            }),
+            &store,
+        );
+        assert!(source.pending().is_some());
+
+        history.close_journal();
+        assert!(!source.journal_path().exists());
+        // 关闭后的编辑不再写入日志
+        history.execute(
+            Box::new(AddNodeCommand {
+                node: node(-2, 0.0),
+            }),
+            &store,
+        );
+        assert!(!source.journal_path().exists());
+
+        std::fs::remove_file(&source_path).unwrap();
+    }
+
+    #[test]
+    fn test_stale_journal_is_ignored() {
+        let source_path = temp_source("stale");
+        let source = JournalSource::new(&source_path).unwrap();
+        let mut journal = source.start().unwrap();
+        journal.append(&JournalEvent::Undo).unwrap();
+        assert!(source.pending().is_some());
+
+        // 源文件被修改后指纹不再匹配
+        std::fs::write(&source_path, b"modified source data").unwrap();
+        let modified = JournalSource::new(&source_path).unwrap();
+        assert!(modified.pending().is_none());
+
+        std::fs::remove_file(source.journal_path()).unwrap();
+        std::fs::remove_file(&source_path).unwrap();
+    }
+}
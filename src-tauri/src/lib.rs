@@ -7,33 +7,75 @@
 //! - `osm_store`: OSM 数据存储层 (DashMap + R-Tree)
 //! - `pbf_parser`: PBF 文件解析器
 //! - `spatial_query`: 空间查询引擎
+//! - `tag_index`: 标签倒排索引
+//! - `search`: 按名称/标签搜索要素
+//! - `overpass`: Overpass QL 子集查询引擎
+//! - `geojson`: GeoJSON 输出
 //! - `binary_protocol`: 高效二进制协议
 //! - `polygon_assembler`: 多边形拓扑组装
+//! - `multipolygon`: 由闭合 Way 创建 Multipolygon
+//! - `restriction`: 转向限制校验
 //! - `render_feature`: 渲染特征系统
 //! - `projection`: Web 墨卡托投影
+//! - `orthogonalize`: 建筑物正交化
+//! - `circularize`: 闭合 Way 圆形化
+//! - `simplify`: Way 几何简化
+//! - `straighten`: Way 拉直
+//! - `transform`: 批量几何变换
+//! - `tag_edit`: 批量标签编辑
+//! - `snap`: 节点吸附
+//! - `split`: Way 拆分
 //! - `history`: Undo/Redo 历史记录
+//! - `changes`: 要素变更状态跟踪
+//! - `revert`: 恢复要素到加载时的版本
+//! - `journal`: 崩溃恢复日志
+//! - `clipboard`: 要素复制粘贴
 //! - `types`: 公共类型定义
 //! - `commands`: Tauri IPC 命令处理器
 
 mod binary_protocol;
+mod changes;
+mod circularize;
+mod clipboard;
 mod commands;
+mod geojson;
 mod history;
+mod journal;
+mod multipolygon;
+mod orthogonalize;
 mod osm_store;
+mod overpass;
 mod pbf_parser;
 mod polygon_assembler;
 mod projection;
 mod render_feature;
+mod restriction;
+mod revert;
+mod search;
+mod simplify;
+mod snap;
 mod spatial_query;
+mod split;
+mod straighten;
+mod tag_edit;
+mod tag_index;
+mod transform;
 mod types;
 
+use clipboard::ClipboardContent;
 use history::HistoryManager;
+use journal::JournalSource;
 use osm_store::OsmStore;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 /// 全局应用状态
 pub struct AppState {
     pub store: Arc<OsmStore>,
     pub history: HistoryManager,
+    /// 剪贴板（复制的子图快照）
+    pub clipboard: Mutex<ClipboardContent>,
+    /// 等待用户决定是否重放的遗留日志
+    pub pending_journal: Mutex<Option<JournalSource>>,
 }
 
 impl Default for AppState {
@@ -41,12 +83,16 @@ impl Default for AppState {
         Self {
             store: Arc::new(OsmStore::new()),
             history: HistoryManager::new(),
+            clipboard: Mutex::new(ClipboardContent::default()),
+            pending_journal: Mutex::new(None),
         }
     }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    use tauri::Manager;
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
@@ -61,19 +107,69 @@ pub fn run() {
             commands::query_viewport_coords,
             commands::query_viewport_full,
             commands::pick_feature,
+            commands::query_areas_at_point,
+            commands::search_features,
+            commands::run_overpass_query,
             commands::get_node_details,
             commands::get_way_details,
+            commands::get_relation_details,
             // 编辑命令
             commands::update_way_tags,
             commands::update_node_tags,
+            commands::batch_update_tags,
+            commands::find_replace_tags,
             commands::move_node,
+            commands::transform_features,
+            commands::orthogonalize,
+            commands::circularize,
+            commands::straighten,
+            commands::preview_simplify,
+            commands::simplify_ways,
             commands::add_node,
+            commands::extract_node,
+            commands::attach_node,
             commands::delete_way,
             commands::delete_node,
+            // 剪贴板命令
+            commands::copy_features,
+            commands::paste_features,
+            // Relation 编辑命令
+            commands::create_relation,
+            commands::create_multipolygon,
+            commands::create_turn_restriction,
+            commands::update_turn_restriction,
+            commands::get_junction_restrictions,
+            commands::delete_relation,
+            commands::add_relation_member,
+            commands::remove_relation_member,
+            commands::move_relation_member,
+            commands::update_member_role,
+            commands::update_relation_tags,
+            // 历史命令
             commands::undo,
             commands::redo,
             commands::get_history_state,
+            commands::set_history_limits,
+            commands::get_history_entries,
+            commands::undo_to,
+            commands::redo_to,
+            commands::begin_transaction,
+            commands::commit_transaction,
+            commands::abort_transaction,
+            // 变更集命令
+            commands::get_changes_summary,
+            commands::revert_entity,
+            // 崩溃恢复命令
+            commands::get_journal_recovery,
+            commands::replay_journal,
+            commands::discard_journal,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // 正常退出时删除崩溃恢复日志
+            if let tauri::RunEvent::Exit = event {
+                app.state::<AppState>().history.close_journal();
+            }
+        });
 }
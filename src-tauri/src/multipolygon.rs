@@ -0,0 +1,391 @@
+//! 由闭合 Way 创建 Multipolygon Relation
+//!
+//! 按几何包含关系计算每个环的嵌套深度：
+//! - 被偶数个其他环包含的为 outer（包括湖中岛上的池塘）
+//! - 被奇数个其他环包含的为 inner
+//!
+//! 可选地将 outer Way 共有的标签（`area` 除外）移动到 Relation 上，
+//! 此时 outer Way 上的 `area` 标签失去意义，一并删除。
+//! 任意两个环（或同一环的不相邻线段）相交或接触时拒绝创建。
+
+use crate::history::TagChange;
+use crate::osm_store::{MemberType, OsmRelation, OsmStore, OsmWay, RelationMember};
+use crate::polygon_assembler::{assemble_relation, point_in_ring};
+use crate::projection::lonlat_to_mercator;
+use crate::tag_edit;
+use std::collections::HashSet;
+
+/// Multipolygon 创建计划
+pub struct MultipolygonPlan {
+    pub relation: OsmRelation,
+    /// outer Way 的标签变更（标签移动到 Relation 时）
+    pub tag_changes: Vec<TagChange>,
+}
+
+/// 闭合 Way 及其墨卡托坐标环
+struct Ring {
+    way: OsmWay,
+    coords: Vec<(f64, f64)>,
+}
+
+impl Ring {
+    /// 判断另一个环是否位于本环内
+    ///
+    /// 取另一个环上第一个不属于本环的节点作为代表点（两环可能共享节点）
+    fn contains(&self, other: &Ring) -> Result<bool, String> {
+        let index = other
+            .way
+            .node_refs
+            .iter()
+            .position(|id| !self.way.node_refs.contains(id))
+            .ok_or_else(|| {
+                format!(
+                    "Way #{} and Way #{} share all nodes",
+                    self.way.id, other.way.id
+                )
+            })?;
+        Ok(point_in_ring(other.coords[index], &self.coords))
+    }
+}
+
+fn load_ring(store: &OsmStore, way_id: i64) -> Result<Ring, String> {
+    let way = match store.ways.get(&way_id) {
+        Some(way) => way.clone(),
+        None => return Err(format!("Way #{} not found", way_id)),
+    };
+    if way.node_refs.len() < 4 || way.node_refs.first() != way.node_refs.last() {
+        return Err(format!("Way #{} is not closed", way_id));
+    }
+    let coords = way
+        .node_refs
+        .iter()
+        .map(|id| {
+            store
+                .nodes
+                .get(id)
+                .map(|n| lonlat_to_mercator(n.lon, n.lat))
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| format!("Way #{} has missing nodes", way_id))?;
+    Ok(Ring { way, coords })
+}
+
+/// 计算由闭合 Way 创建 Multipolygon 的 Relation 和标签变更
+pub fn plan_multipolygon(
+    store: &OsmStore,
+    way_ids: &[i64],
+    relation_id: i64,
+    move_tags: bool,
+) -> Result<MultipolygonPlan, String> {
+    let mut seen = HashSet::new();
+    let rings = way_ids
+        .iter()
+        .filter(|id| seen.insert(**id))
+        .map(|&id| load_ring(store, id))
+        .collect::<Result<Vec<_>, _>>()?;
+    if rings.is_empty() {
+        return Err("No ways selected".to_string());
+    }
+    validate(&rings)?;
+
+    // 嵌套深度 = 包含该环的其他环数量
+    let mut outers = Vec::new();
+    let mut inners = Vec::new();
+    for (i, ring) in rings.iter().enumerate() {
+        let mut depth = 0;
+        for (j, other) in rings.iter().enumerate() {
+            if i != j && other.contains(ring)? {
+                depth += 1;
+            }
+        }
+        if depth % 2 == 0 {
+            outers.push(&ring.way);
+        } else {
+            inners.push(&ring.way);
+        }
+    }
+
+    let mut tags = vec![("type".to_string(), "multipolygon".to_string())];
+    let mut tag_changes = Vec::new();
+    if move_tags {
+        let moved: Vec<(String, String)> = outers[0]
+            .tags
+            .iter()
+            .filter(|tag| tag.0 != "area" && tag.0 != "type")
+            .filter(|tag| outers.iter().all(|way| way.tags.contains(tag)))
+            .cloned()
+            .collect();
+        for way in &outers {
+            let change = tag_edit::tag_change(store, MemberType::Way, way.id, |old_tags| {
+                old_tags
+                    .iter()
+                    .filter(|tag| !moved.contains(tag))
+                    .filter(|tag| moved.is_empty() || tag.0 != "area")
+                    .cloned()
+                    .collect()
+            });
+            tag_changes.extend(change);
+        }
+        tags.extend(moved);
+    }
+
+    let member = |way: &&OsmWay, role: &str| RelationMember {
+        member_type: MemberType::Way,
+        ref_id: way.id,
+        role: role.to_string(),
+    };
+    let relation = OsmRelation {
+        id: relation_id,
+        members: outers
+            .iter()
+            .map(|way| member(way, "outer"))
+            .chain(inners.iter().map(|way| member(way, "inner")))
+            .collect(),
+        tags,
+    };
+    verify_assembly(store, &relation)?;
+
+    Ok(MultipolygonPlan {
+        relation,
+        tag_changes,
+    })
+}
+
+/// 确认 Relation 能按渲染时的方式组装出每个成员对应的环
+///
+/// 在执行命令前调用，避免创建出无法渲染的 Multipolygon
+fn verify_assembly(store: &OsmStore, relation: &OsmRelation) -> Result<(), String> {
+    match assemble_relation(store, relation) {
+        Some(polygon) if polygon.rings.len() >= relation.members.len() => Ok(()),
+        _ => Err(format!(
+            "Relation #{} could not be assembled into rings",
+            relation.id
+        )),
+    }
+}
+
+/// 叉积符号：c 位于有向线段 a→b 的左侧为正，右侧为负，共线为 0
+fn orientation(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// 已知 p 与线段 a-b 共线时，p 是否落在线段上
+fn on_segment(a: (f64, f64), b: (f64, f64), p: (f64, f64)) -> bool {
+    p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1)
+}
+
+/// 两条线段是否相交（包括端点接触和共线重叠）
+fn segments_intersect(p1: (f64, f64), p2: (f64, f64), q1: (f64, f64), q2: (f64, f64)) -> bool {
+    let d1 = orientation(q1, q2, p1);
+    let d2 = orientation(q1, q2, p2);
+    let d3 = orientation(p1, p2, q1);
+    let d4 = orientation(p1, p2, q2);
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+    (d1 == 0.0 && on_segment(q1, q2, p1))
+        || (d2 == 0.0 && on_segment(q1, q2, p2))
+        || (d3 == 0.0 && on_segment(p1, p2, q1))
+        || (d4 == 0.0 && on_segment(p1, p2, q2))
+}
+
+/// 环的包围盒 (min_x, min_y, max_x, max_y)
+fn ring_bounds(coords: &[(f64, f64)]) -> (f64, f64, f64, f64) {
+    coords.iter().fold(
+        (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+        |(min_x, min_y, max_x, max_y), &(x, y)| {
+            (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+        },
+    )
+}
+
+/// 同一环的两条线段是否相邻（共享端点是正常的，不算接触）
+fn adjacent(i: usize, j: usize, segment_count: usize) -> bool {
+    j == i + 1 || (i == 0 && j == segment_count - 1)
+}
+
+/// 检查环之间以及环自身的相交和接触
+fn validate(rings: &[Ring]) -> Result<(), String> {
+    let bounds: Vec<_> = rings.iter().map(|ring| ring_bounds(&ring.coords)).collect();
+    for (a, ring) in rings.iter().enumerate() {
+        let segments: Vec<_> = ring.coords.windows(2).collect();
+        for (i, s) in segments.iter().enumerate() {
+            for (j, t) in segments.iter().enumerate().skip(i + 1) {
+                if !adjacent(i, j, segments.len()) && segments_intersect(s[0], s[1], t[0], t[1]) {
+                    return Err(format!("Way #{} intersects itself", ring.way.id));
+                }
+            }
+        }
+
+        for (b, other) in rings.iter().enumerate().skip(a + 1) {
+            let (ax0, ay0, ax1, ay1) = bounds[a];
+            let (bx0, by0, bx1, by1) = bounds[b];
+            if ax1 < bx0 || bx1 < ax0 || ay1 < by0 || by1 < ay0 {
+                continue;
+            }
+            let touches = segments.iter().any(|s| {
+                other
+                    .coords
+                    .windows(2)
+                    .any(|t| segments_intersect(s[0], s[1], t[0], t[1]))
+            });
+            if touches {
+                return Err(format!(
+                    "Way #{} and Way #{} intersect or touch",
+                    ring.way.id, other.way.id
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{
+        BatchUpdateTagsCommand, Command, CompositeCommand, CreateRelationCommand, HistoryManager,
+    };
+    use crate::osm_store::OsmNode;
+    use crate::polygon_assembler::assemble_from_relation;
+
+    /// 以 (0, 0) 为中心、半边长为 size 的正方形闭合 Way
+    fn add_square(store: &OsmStore, way_id: i64, size: f64, tags: &[(&str, &str)]) {
+        let corners = [(-size, -size), (size, -size), (size, size), (-size, size)];
+        let mut node_refs = Vec::new();
+        for (i, (lon, lat)) in corners.into_iter().enumerate() {
+            let id = way_id * 10 + i as i64;
+            store.add_node_with_index(OsmNode {
+                id,
+                lon,
+                lat,
+                tags: vec![],
+            });
+            node_refs.push(id);
+        }
+        node_refs.push(node_refs[0]);
+        let tags: Vec<(String, String)> = tags
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let style = tag_edit::way_style(&tags, &node_refs);
+        store.add_way_with_index(OsmWay {
+            id: way_id,
+            node_refs,
+            tags,
+            render_feature: style.render_feature,
+            layer: style.layer,
+            is_area: style.is_area,
+        });
+    }
+
+    #[test]
+    fn test_lake_with_island_nesting() {
+        let store = OsmStore::new();
+        add_square(&store, 1, 0.01, &[("natural", "water"), ("name", "Lake")]);
+        add_square(&store, 2, 0.005, &[]);
+        add_square(&store, 3, 0.001, &[]);
+
+        let plan = plan_multipolygon(&store, &[2, 3, 1], -1, true).unwrap();
+        let roles: Vec<(i64, &str)> = plan
+            .relation
+            .members
+            .iter()
+            .map(|m| (m.ref_id, m.role.as_str()))
+            .collect();
+        // 岛上的池塘嵌套两层，仍为 outer
+        assert_eq!(roles, vec![(3, "outer"), (1, "outer"), (2, "inner")]);
+        // 两个 outer 没有共同标签
+        assert_eq!(plan.relation.tags.len(), 1);
+        assert!(plan.tag_changes.is_empty());
+    }
+
+    #[test]
+    fn test_create_multipolygon_moves_tags_and_undoes() {
+        let store = OsmStore::new();
+        add_square(&store, 1, 0.01, &[("natural", "water"), ("area", "yes")]);
+        add_square(&store, 2, 0.005, &[]);
+        assert!(plan_multipolygon(&store, &[], -1, false).is_err());
+
+        let plan = plan_multipolygon(&store, &[1, 2], -1, true).unwrap();
+        assert!(plan
+            .relation
+            .tags
+            .contains(&("natural".to_string(), "water".to_string())));
+
+        let history = HistoryManager::new();
+        let command = CompositeCommand {
+            label: "Create multipolygon".to_string(),
+            children: vec![
+                Box::new(CreateRelationCommand {
+                    relation: plan.relation,
+                }) as Box<dyn Command>,
+                Box::new(BatchUpdateTagsCommand {
+                    label: "Move tags".to_string(),
+                    changes: plan.tag_changes,
+                }),
+            ],
+        };
+        let result = history.execute(Box::new(command), &store);
+        assert!(result.success && result.needs_redraw);
+        assert!(assemble_from_relation(&store, -1).is_some());
+        // 标签移走后 area=yes 一并删除
+        assert!(store.ways.get(&1).unwrap().tags.is_empty());
+        assert!(!store.ways.get(&1).unwrap().is_area);
+
+        let result = history.undo(&store);
+        assert!(result.success && result.needs_redraw);
+        assert!(!store.relations.contains_key(&-1));
+        assert_eq!(store.ways.get(&1).unwrap().tags.len(), 2);
+        assert!(store.ways.get(&1).unwrap().is_area);
+    }
+
+    #[test]
+    fn test_rejects_crossing_and_touching_rings() {
+        let store = OsmStore::new();
+        add_square(&store, 1, 0.01, &[("natural", "water")]);
+        // 与 Way 1 的边交叉
+        add_square(&store, 2, 0.015, &[]);
+        let offset = |id: i64, dlon: f64| {
+            let mut node = store.nodes.get(&id).unwrap().clone();
+            node.lon += dlon;
+            store.add_node_with_index(node);
+        };
+        for i in 0..4 {
+            offset(20 + i, 0.012);
+        }
+        assert!(plan_multipolygon(&store, &[1, 2], -1, false).is_err());
+
+        // 内环的一个角落在外环的边上
+        add_square(&store, 3, 0.005, &[]);
+        offset(31, 0.005);
+        offset(32, 0.005);
+        assert!(plan_multipolygon(&store, &[1, 3], -1, false).is_err());
+
+        // 自相交（8 字形）
+        add_square(&store, 4, 0.001, &[]);
+        let mut way = store.ways.get(&4).unwrap().clone();
+        way.node_refs.swap(1, 2);
+        store.ways.insert(4, way);
+        assert!(plan_multipolygon(&store, &[4], -1, false).is_err());
+    }
+
+    #[test]
+    fn test_verify_assembly_rejects_unstitched_members() {
+        let store = OsmStore::new();
+        add_square(&store, 1, 0.01, &[("natural", "water")]);
+        add_square(&store, 2, 0.005, &[]);
+        let plan = plan_multipolygon(&store, &[1, 2], -1, false).unwrap();
+        assert!(verify_assembly(&store, &plan.relation).is_ok());
+
+        // inner 成员断开后无法拼成闭合环
+        let mut way = store.ways.get(&2).unwrap().clone();
+        way.node_refs.pop();
+        store.ways.insert(2, way);
+        assert!(verify_assembly(&store, &plan.relation).is_err());
+
+        // 成员 Way 缺失
+        store.ways.remove(&2);
+        assert!(verify_assembly(&store, &plan.relation).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! 建筑物正交化 (Orthogonalize / "Q" 操作)
+//!
+//! 将手绘的闭合环调整为所有拐角都是直角的形状。
+//!
+//! ## 算法
+//!
+//! 在 Web 墨卡托空间（等角投影，角度不失真）中迭代：
+//! 1. 按顶点夹角分类：接近 90° 的是拐角，接近 180° 的是直线点，其余拒绝处理
+//! 2. 只对拐角迭代：每轮沿两条邻边的角平分线移动顶点，使夹角逼近 90°
+//! 3. 保留得分（Σ dot²）最低的结果，得分低于 epsilon 时提前结束
+//! 4. 直线点：未打标签且未被共享的直接删除，否则投影到相邻拐角的连线上
+
+use crate::history::{NodeMove, WayNodesChange};
+use crate::osm_store::{MemberType, OsmNode, OsmStore};
+use crate::polygon_assembler::stitch_ways_to_node_rings;
+use crate::projection::{lonlat_to_mercator, mercator_to_lonlat};
+use std::collections::{HashMap, HashSet};
+
+/// 默认角度容差（度）：偏离 90° 或 180° 不超过此值的顶点才会被处理
+pub const DEFAULT_THRESHOLD_DEGREES: f64 = 13.0;
+
+/// 最大迭代次数
+const MAX_ITERATIONS: usize = 1000;
+
+/// 收敛阈值
+const EPSILON: f64 = 1e-4;
+
+/// 单个环的正交化结果
+#[derive(Debug, Clone)]
+pub struct OrthogonalizedRing {
+    /// 每个顶点的新坐标（与输入一一对应）
+    pub points: Vec<(f64, f64)>,
+    /// 顶点是否是直线点（接近 180°）
+    pub straight: Vec<bool>,
+}
+
+/// 正交化计划（尚未提交）
+#[derive(Debug, Clone, Default)]
+pub struct OrthogonalizePlan {
+    pub moves: Vec<NodeMove>,
+    pub removed_nodes: Vec<OsmNode>,
+    pub way_changes: Vec<WayNodesChange>,
+}
+
+fn normalize(x: f64, y: f64) -> (f64, f64) {
+    let len = (x * x + y * y).sqrt();
+    if len < 1e-12 {
+        (0.0, 0.0)
+    } else {
+        (x / len, y / len)
+    }
+}
+
+/// 顶点 i 处两条邻边单位向量的点积（cos 夹角）
+fn vertex_dot(points: &[(f64, f64)], i: usize) -> f64 {
+    let n = points.len();
+    let origin = points[i];
+    let a = points[(i + n - 1) % n];
+    let b = points[(i + 1) % n];
+    let p = normalize(a.0 - origin.0, a.1 - origin.1);
+    let q = normalize(b.0 - origin.0, b.1 - origin.1);
+    p.0 * q.0 + p.1 * q.1
+}
+
+/// 正交程度得分：所有拐角 dot² 之和，0 表示全部为直角
+fn score(points: &[(f64, f64)]) -> f64 {
+    (0..points.len())
+        .map(|i| vertex_dot(points, i).powi(2))
+        .sum()
+}
+
+/// 单个拐角的移动向量
+fn corner_motion(points: &[(f64, f64)], i: usize) -> (f64, f64) {
+    let n = points.len();
+    let origin = points[i];
+    let a = points[(i + n - 1) % n];
+    let b = points[(i + 1) % n];
+
+    let p = (a.0 - origin.0, a.1 - origin.1);
+    let q = (b.0 - origin.0, b.1 - origin.1);
+    let scale = 2.0 * (p.0.hypot(p.1)).min(q.0.hypot(q.1));
+
+    let p = normalize(p.0, p.1);
+    let q = normalize(q.0, q.1);
+    let dot = p.0 * q.0 + p.1 * q.1;
+
+    let bisector = normalize(p.0 + q.0, p.1 + q.1);
+    let step = 0.1 * dot * scale;
+    (bisector.0 * step, bisector.1 * step)
+}
+
+/// 将点投影到线段上
+fn project_onto_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let dx = b.0 - a.0;
+    let dy = b.1 - a.1;
+    let len_sq = dx * dx + dy * dy;
+    if len_sq < 1e-12 {
+        return a;
+    }
+    let t = (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len_sq).clamp(0.0, 1.0);
+    (a.0 + t * dx, a.1 + t * dy)
+}
+
+/// 正交化一个闭合环（输入不含重复的闭合点）
+///
+/// 返回 Err 表示形状不适合正交化（拐角偏离直角过多或拐角不足 3 个）
+pub fn orthogonalize_ring(
+    points: &[(f64, f64)],
+    threshold_degrees: f64,
+) -> Result<OrthogonalizedRing, String> {
+    let n = points.len();
+    if n < 3 {
+        return Err("Ring has fewer than 3 vertices".to_string());
+    }
+
+    // |dot| < lower: 接近 90°；dot < -upper: 接近 180°
+    let lower = (90.0 - threshold_degrees).to_radians().cos();
+    let upper = threshold_degrees.to_radians().cos();
+
+    // 1. 顶点分类
+    let mut straight = vec![false; n];
+    for (i, is_straight) in straight.iter_mut().enumerate() {
+        let dot = vertex_dot(points, i);
+        if dot.abs() < lower {
+            continue;
+        }
+        if dot < -upper {
+            *is_straight = true;
+            continue;
+        }
+        return Err(format!(
+            "Corner at vertex {} is too far from 90° ({:.1}°)",
+            i,
+            dot.clamp(-1.0, 1.0).acos().to_degrees()
+        ));
+    }
+
+    let corner_indices: Vec<usize> = (0..n).filter(|&i| !straight[i]).collect();
+    if corner_indices.len() < 3 {
+        return Err("Shape has fewer than 3 corners".to_string());
+    }
+
+    // 2. 只对拐角迭代
+    let mut corners: Vec<(f64, f64)> = corner_indices.iter().map(|&i| points[i]).collect();
+    let mut best = corners.clone();
+    let mut best_score = score(&corners);
+
+    for _ in 0..MAX_ITERATIONS {
+        if best_score < EPSILON {
+            break;
+        }
+
+        let motions: Vec<(f64, f64)> = (0..corners.len())
+            .map(|i| corner_motion(&corners, i))
+            .collect();
+        for (corner, motion) in corners.iter_mut().zip(&motions) {
+            corner.0 += motion.0;
+            corner.1 += motion.1;
+        }
+
+        let new_score = score(&corners);
+        if new_score < best_score {
+            best = corners.clone();
+            best_score = new_score;
+        }
+    }
+
+    // 3. 回填拐角坐标，直线点投影到相邻拐角连线上
+    let mut result = points.to_vec();
+    for (k, &i) in corner_indices.iter().enumerate() {
+        result[i] = best[k];
+    }
+
+    for i in 0..n {
+        if !straight[i] {
+            continue;
+        }
+        let prev = (1..n)
+            .map(|d| (i + n - d) % n)
+            .find(|&j| !straight[j])
+            .unwrap();
+        let next = (1..n).map(|d| (i + d) % n).find(|&j| !straight[j]).unwrap();
+        result[i] = project_onto_segment(points[i], result[prev], result[next]);
+    }
+
+    Ok(OrthogonalizedRing {
+        points: result,
+        straight,
+    })
+}
+
+/// 收集选择集中所有需要正交化的闭合环（节点 ID 序列，首尾相同）
+fn collect_rings(
+    store: &OsmStore,
+    way_ids: &[i64],
+    relation_ids: &[i64],
+) -> Result<Vec<Vec<i64>>, String> {
+    let mut rings = Vec::new();
+
+    for &way_id in way_ids {
+        let way = store
+            .ways
+            .get(&way_id)
+            .ok_or_else(|| format!("Way #{} not found", way_id))?;
+        if way.node_refs.len() < 4 || way.node_refs.first() != way.node_refs.last() {
+            return Err(format!("Way #{} is not closed", way_id));
+        }
+        rings.push(way.node_refs.clone());
+    }
+
+    for &relation_id in relation_ids {
+        let relation = store
+            .relations
+            .get(&relation_id)
+            .ok_or_else(|| format!("Relation #{} not found", relation_id))?;
+        if relation.tag("type") != Some("multipolygon") {
+            return Err(format!("Relation #{} is not a multipolygon", relation_id));
+        }
+
+        let member_ways: Vec<i64> = relation
+            .members
+            .iter()
+            .filter(|m| {
+                m.member_type == MemberType::Way
+                    && matches!(m.role.as_str(), "outer" | "inner" | "")
+            })
+            .map(|m| m.ref_id)
+            .collect();
+        drop(relation);
+
+        let relation_rings = stitch_ways_to_node_rings(store, &member_ways);
+        if relation_rings.is_empty() {
+            return Err(format!("Relation #{} has no closed rings", relation_id));
+        }
+        rings.extend(relation_rings);
+    }
+
+    Ok(rings)
+}
+
+/// 计算选择集的正交化计划
+///
+/// 任意一个环无法正交化时整体拒绝，避免只修改一部分
+pub fn plan_orthogonalize(
+    store: &OsmStore,
+    way_ids: &[i64],
+    relation_ids: &[i64],
+    threshold_degrees: f64,
+) -> Result<OrthogonalizePlan, String> {
+    let rings = collect_rings(store, way_ids, relation_ids)?;
+    if rings.is_empty() {
+        return Err("Nothing to orthogonalize".to_string());
+    }
+
+    let mut plan = OrthogonalizePlan::default();
+    let mut moved: HashSet<i64> = HashSet::new();
+    let mut removed: HashSet<i64> = HashSet::new();
+
+    for ring in rings {
+        // 去掉闭合点
+        let vertex_ids = &ring[..ring.len() - 1];
+        let mut coords = Vec::with_capacity(vertex_ids.len());
+        for node_id in vertex_ids {
+            let node = store
+                .nodes
+                .get(node_id)
+                .ok_or_else(|| format!("Node #{} not found", node_id))?;
+            coords.push(lonlat_to_mercator(node.lon, node.lat));
+        }
+
+        let result = orthogonalize_ring(&coords, threshold_degrees)?;
+
+        for (k, &node_id) in vertex_ids.iter().enumerate() {
+            // 闭合点（环的第一个节点）始终保留
+            if result.straight[k] && k != 0 && store.is_removable_vertex(node_id) {
+                if removed.insert(node_id) {
+                    plan.removed_nodes
+                        .push(store.nodes.get(&node_id).unwrap().clone());
+                }
+                continue;
+            }
+
+            if !moved.insert(node_id) {
+                continue;
+            }
+
+            let node = store.nodes.get(&node_id).unwrap();
+            let (new_lon, new_lat) = mercator_to_lonlat(result.points[k].0, result.points[k].1);
+            plan.moves.push(NodeMove {
+                node_id,
+                old_lon: node.lon,
+                old_lat: node.lat,
+                new_lon,
+                new_lat,
+            });
+        }
+    }
+
+    // 被删除节点只属于一条 Way，逐条更新节点序列
+    let mut way_changes: HashMap<i64, WayNodesChange> = HashMap::new();
+    for node in &plan.removed_nodes {
+        for way_id in store.find_ways_referencing_node(node.id) {
+            let change = way_changes.entry(way_id).or_insert_with(|| {
+                let refs = store.ways.get(&way_id).unwrap().node_refs.clone();
+                WayNodesChange {
+                    way_id,
+                    old_refs: refs.clone(),
+                    new_refs: refs,
+                }
+            });
+            change.new_refs.retain(|id| *id != node.id);
+        }
+    }
+    plan.way_changes = way_changes.into_values().collect();
+
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn max_corner_error_degrees(points: &[(f64, f64)], straight: &[bool]) -> f64 {
+        let corners: Vec<(f64, f64)> = points
+            .iter()
+            .zip(straight)
+            .filter(|(_, s)| !**s)
+            .map(|(p, _)| *p)
+            .collect();
+        (0..corners.len())
+            .map(|i| (vertex_dot(&corners, i).acos().to_degrees() - 90.0).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_squares_skewed_rectangle() {
+        let points = vec![(0.0, 0.0), (10.0, 0.5), (10.3, 6.0), (-0.2, 5.6)];
+        let result = orthogonalize_ring(&points, DEFAULT_THRESHOLD_DEGREES).unwrap();
+        assert!(result.straight.iter().all(|s| !s));
+        assert!(max_corner_error_degrees(&result.points, &result.straight) < 1.0);
+    }
+
+    #[test]
+    fn test_straight_vertex_detected() {
+        // (5, 0.1) 位于底边上，几乎是直线点
+        let points = vec![(0.0, 0.0), (5.0, 0.1), (10.0, 0.0), (10.0, 6.0), (0.0, 6.0)];
+        let result = orthogonalize_ring(&points, DEFAULT_THRESHOLD_DEGREES).unwrap();
+        assert_eq!(result.straight, vec![false, true, false, false, false]);
+        // 直线点被投影到底边上
+        let (x, y) = result.points[1];
+        assert!((x - 5.0).abs() < 0.5);
+        assert!(y.abs() < 0.1);
+    }
+
+    #[test]
+    fn test_rejects_triangle_corner() {
+        let points = vec![(0.0, 0.0), (10.0, 0.0), (5.0, 8.0)];
+        assert!(orthogonalize_ring(&points, DEFAULT_THRESHOLD_DEGREES).is_err());
+    }
+}
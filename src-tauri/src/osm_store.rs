@@ -3,15 +3,19 @@
 //! 架构设计：
 //! - DashMap 存储实体本身 (O(1) 随机访问)
 //! - R-Tree 存储空间索引 (O(log n) 范围查询)
+//! - 标签倒排索引 (按标签搜索)
 //! - 两阶段加载：先收集数据，再批量构建索引
 
+use crate::tag_index::{FeatureRef, TagIndex};
+use crate::types::FeatureSelection;
 use dashmap::DashMap;
 use rstar::{RTree, RTreeObject, AABB};
+use std::collections::HashMap;
 use std::sync::RwLock;
 use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 
 /// OSM 节点 (Node) - 地图上的一个坐标点
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct OsmNode {
     pub id: i64,
     pub lat: f64,
@@ -20,7 +24,7 @@ pub struct OsmNode {
 }
 
 /// OSM 路径 (Way) - 由多个节点组成的线或面
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct OsmWay {
     pub id: i64,
     pub node_refs: Vec<i64>,
@@ -35,27 +39,65 @@ pub struct OsmWay {
 }
 
 /// OSM 关系 (Relation) - 复杂的逻辑组合
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct OsmRelation {
     pub id: i64,
     pub members: Vec<RelationMember>,
     pub tags: Vec<(String, String)>,
 }
 
-#[derive(Debug, Clone)]
+impl OsmRelation {
+    /// 获取指定 key 的标签值
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 检查 Relation 是否仍然有效
+    ///
+    /// - 没有任何成员的 Relation 无效
+    /// - multipolygon/boundary 至少需要一个 outer Way
+    /// - restriction 必须同时具备 from、via、to
+    pub fn is_valid(&self) -> bool {
+        if self.members.is_empty() {
+            return false;
+        }
+
+        let has_role = |role: &str| self.members.iter().any(|m| m.role == role);
+
+        match self.tag("type") {
+            Some("multipolygon") | Some("boundary") => self.members.iter().any(|m| {
+                m.member_type == MemberType::Way && (m.role == "outer" || m.role.is_empty())
+            }),
+            Some("restriction") => has_role("from") && has_role("via") && has_role("to"),
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct RelationMember {
     pub member_type: MemberType,
     pub ref_id: i64,
     pub role: String,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MemberType {
     Node,
     Way,
     Relation,
 }
 
+/// 成员 → 以其为成员的 Relation ID
+pub type RelationParents = HashMap<FeatureRef, Vec<i64>>;
+
+/// multipolygon/boundary Relation 的包围盒索引（按成员 Way 的包围盒合并）
+pub type AreaRelationIndex = RTree<SpatialEntry>;
+
 /// R-Tree 中的空间索引项 (只存 ID 和包围盒)
 #[derive(Debug, Clone, Copy)]
 pub struct SpatialEntry {
@@ -93,6 +135,7 @@ pub struct OsmStore {
     pub node_ref_count: DashMap<i64, u16>,
     node_index: RwLock<RTree<SpatialEntry>>,
     way_index: RwLock<RTree<SpatialEntry>>,
+    tag_index: RwLock<TagIndex>,
     index_dirty: AtomicBool,
     /// 本地 ID 生成器（负数 ID，用于新创建的要素）
     next_local_id: AtomicI64,
@@ -107,6 +150,7 @@ impl OsmStore {
             node_ref_count: DashMap::new(),
             node_index: RwLock::new(RTree::new()),
             way_index: RwLock::new(RTree::new()),
+            tag_index: RwLock::new(TagIndex::default()),
             index_dirty: AtomicBool::new(false),
             next_local_id: AtomicI64::new(-1),
         }
@@ -117,6 +161,13 @@ impl OsmStore {
         self.next_local_id.fetch_sub(1, Ordering::SeqCst)
     }
 
+    /// 标记本地 ID 已被占用（重放日志时恢复的要素），之后生成的 ID 不会与之冲突
+    pub fn reserve_local_id(&self, id: i64) {
+        if id < 0 {
+            self.next_local_id.fetch_min(id - 1, Ordering::SeqCst);
+        }
+    }
+
     /// 插入节点 (不更新索引，需要后续调用 rebuild_indices)
     pub fn insert_node(&self, node: OsmNode) {
         self.nodes.insert(node.id, node);
@@ -135,7 +186,7 @@ impl OsmStore {
         self.index_dirty.store(true, Ordering::Relaxed);
     }
 
-    /// 批量重建空间索引 (O(n log n) 一次性构建，比逐条插入快 100 倍)
+    /// 批量重建空间索引和标签索引 (O(n log n) 一次性构建，比逐条插入快 100 倍)
     pub fn rebuild_indices(&self) {
         let node_entries: Vec<SpatialEntry> = self
             .nodes
@@ -166,11 +217,16 @@ impl OsmStore {
             *index = RTree::bulk_load(way_entries);
         }
 
+        let tag_index = TagIndex::build(self);
+        if let Ok(mut index) = self.tag_index.write() {
+            *index = tag_index;
+        }
+
         self.index_dirty.store(false, Ordering::Relaxed);
     }
 
     /// 计算 Way 的包围盒
-    fn compute_way_bbox(&self, way: &OsmWay) -> Option<SpatialEntry> {
+    pub fn compute_way_bbox(&self, way: &OsmWay) -> Option<SpatialEntry> {
         let mut min_lon = f64::MAX;
         let mut min_lat = f64::MAX;
         let mut max_lon = f64::MIN;
@@ -290,6 +346,62 @@ impl OsmStore {
         self.way_index.read().unwrap()
     }
 
+    /// 获取标签索引的只读访问
+    pub fn tag_index(&self) -> std::sync::RwLockReadGuard<'_, TagIndex> {
+        self.tag_index.read().unwrap()
+    }
+
+    /// 要素当前的标签（要素不存在时为 None）
+    pub fn feature_tags(&self, member_type: MemberType, id: i64) -> Option<Vec<(String, String)>> {
+        match member_type {
+            MemberType::Node => self.nodes.get(&id).map(|n| n.tags.clone()),
+            MemberType::Way => self.ways.get(&id).map(|w| w.tags.clone()),
+            MemberType::Relation => self.relations.get(&id).map(|r| r.tags.clone()),
+        }
+    }
+
+    /// 要素是否在存储中
+    pub fn contains_feature(&self, member_type: MemberType, id: i64) -> bool {
+        match member_type {
+            MemberType::Node => self.nodes.contains_key(&id),
+            MemberType::Way => self.ways.contains_key(&id),
+            MemberType::Relation => self.relations.contains_key(&id),
+        }
+    }
+
+    /// 从标签索引中移除要素的当前标签（在修改要素之前调用）
+    pub fn unindex_tags(&self, features: &FeatureSelection) {
+        self.update_tag_index(features, TagIndex::remove);
+    }
+
+    /// 将要素的当前标签加入标签索引（在修改要素之后调用）
+    pub fn index_tags(&self, features: &FeatureSelection) {
+        self.update_tag_index(features, TagIndex::insert);
+    }
+
+    fn update_tag_index(
+        &self,
+        features: &FeatureSelection,
+        update: fn(&mut TagIndex, FeatureRef, &[(String, String)]),
+    ) {
+        // 先读出标签，避免持有 DashMap 引用时获取索引写锁
+        let tagged: Vec<_> = features
+            .iter()
+            .filter_map(|(member_type, id)| {
+                let tags = self.feature_tags(member_type, id)?;
+                (!tags.is_empty()).then_some(((member_type, id), tags))
+            })
+            .collect();
+        if tagged.is_empty() {
+            return;
+        }
+        if let Ok(mut index) = self.tag_index.write() {
+            for (feature, tags) in &tagged {
+                update(&mut index, *feature, tags);
+            }
+        }
+    }
+
     /// 更新节点坐标并维护 R-Tree 索引
     ///
     /// 这是移动节点的核心操作，必须同时更新：
@@ -367,6 +479,81 @@ impl OsmStore {
         true
     }
 
+    /// 批量更新节点坐标并维护 R-Tree 索引
+    ///
+    /// 与逐个调用 update_node_position 相比：
+    /// - 受影响的 Way 只扫描一次
+    /// - 旧边界框在移动前计算，按精确值删除，无需遍历整个索引
+    /// - 节点索引和路径索引各只加一次写锁
+    ///
+    /// positions: [(node_id, new_lon, new_lat)]，返回实际更新的节点数量
+    pub fn update_node_positions(&self, positions: &[(i64, f64, f64)]) -> usize {
+        if positions.is_empty() {
+            return 0;
+        }
+
+        let moved: std::collections::HashSet<i64> =
+            positions.iter().map(|&(id, _, _)| id).collect();
+
+        // 1. 找出受影响的 Way，并在移动前记录旧边界框
+        let old_way_entries: Vec<SpatialEntry> = self
+            .ways
+            .iter()
+            .filter(|entry| entry.value().node_refs.iter().any(|id| moved.contains(id)))
+            .filter_map(|entry| self.compute_way_bbox(entry.value()))
+            .collect();
+
+        // 2. 更新 DashMap 中的节点坐标
+        let mut node_updates: Vec<(SpatialEntry, SpatialEntry)> =
+            Vec::with_capacity(positions.len());
+
+        for &(node_id, new_lon, new_lat) in positions {
+            if let Some(mut node) = self.nodes.get_mut(&node_id) {
+                let old_entry = SpatialEntry {
+                    id: node_id,
+                    min_lon: node.lon,
+                    min_lat: node.lat,
+                    max_lon: node.lon,
+                    max_lat: node.lat,
+                };
+                node.lon = new_lon;
+                node.lat = new_lat;
+                let new_entry = SpatialEntry {
+                    id: node_id,
+                    min_lon: new_lon,
+                    min_lat: new_lat,
+                    max_lon: new_lon,
+                    max_lat: new_lat,
+                };
+                node_updates.push((old_entry, new_entry));
+            }
+        }
+
+        // 3. 更新节点 R-Tree 索引
+        if let Ok(mut index) = self.node_index.write() {
+            for (old_entry, new_entry) in &node_updates {
+                index.remove(old_entry);
+                index.insert(*new_entry);
+            }
+        }
+
+        // 4. 更新受影响 Way 的边界框
+        if !old_way_entries.is_empty() {
+            if let Ok(mut way_index) = self.way_index.write() {
+                for old_entry in &old_way_entries {
+                    way_index.remove(old_entry);
+                    if let Some(way) = self.ways.get(&old_entry.id) {
+                        if let Some(new_bbox) = self.compute_way_bbox(&way) {
+                            way_index.insert(new_bbox);
+                        }
+                    }
+                }
+            }
+        }
+
+        node_updates.len()
+    }
+
     /// 查找所有引用指定节点的 Way ID
     pub fn find_ways_referencing_node(&self, node_id: i64) -> Vec<i64> {
         self.ways
@@ -522,6 +709,31 @@ impl OsmStore {
         }
     }
 
+    /// 替换 Way 的节点序列，同步维护节点引用计数和 R-Tree 边界框
+    ///
+    /// 返回旧的节点序列；Way 不存在时返回 None
+    pub fn replace_way_node_refs(&self, way_id: i64, new_refs: Vec<i64>) -> Option<Vec<i64>> {
+        let old_refs = {
+            let mut way = self.ways.get_mut(&way_id)?;
+            std::mem::replace(&mut way.node_refs, new_refs.clone())
+        };
+
+        for &node_id in &old_refs {
+            self.node_ref_count.entry(node_id).and_modify(|c| {
+                *c = c.saturating_sub(1);
+            });
+        }
+        for &node_id in &new_refs {
+            self.node_ref_count
+                .entry(node_id)
+                .and_modify(|c| *c = c.saturating_add(1))
+                .or_insert(1);
+        }
+
+        self.update_way_rtree(way_id);
+        Some(old_refs)
+    }
+
     /// 更新 Way 的 R-Tree 边界框
     fn update_way_rtree(&self, way_id: i64) {
         if let Ok(mut index) = self.way_index.write() {
@@ -552,6 +764,119 @@ impl OsmStore {
             .map(|w| w.node_refs.len() >= 2)
             .unwrap_or(false)
     }
+
+    /// 节点是否可以作为冗余顶点安全删除
+    ///
+    /// 条件：无标签、只被一条 Way 引用一次、不属于任何 Relation
+    pub fn is_removable_vertex(&self, node_id: i64) -> bool {
+        let untagged = self
+            .nodes
+            .get(&node_id)
+            .map(|n| n.tags.is_empty())
+            .unwrap_or(false);
+        let ref_count = self.node_ref_count.get(&node_id).map(|r| *r).unwrap_or(0);
+
+        untagged
+            && ref_count <= 1
+            && self
+                .find_relations_referencing(MemberType::Node, node_id)
+                .is_empty()
+    }
+
+    /// 查找所有以指定要素为成员的 Relation ID
+    pub fn find_relations_referencing(&self, member_type: MemberType, ref_id: i64) -> Vec<i64> {
+        self.relations
+            .iter()
+            .filter(|entry| {
+                entry
+                    .value()
+                    .members
+                    .iter()
+                    .any(|m| m.member_type == member_type && m.ref_id == ref_id)
+            })
+            .map(|entry| *entry.key())
+            .collect()
+    }
+
+    /// 成员 → 以其为成员的 Relation ID（遍历一次全部 Relation 构建，供多次查找共用）
+    pub fn relation_parents(&self) -> RelationParents {
+        let mut parents = RelationParents::new();
+        for relation in self.relations.iter() {
+            for member in &relation.members {
+                let ids = parents
+                    .entry((member.member_type, member.ref_id))
+                    .or_default();
+                if ids.last() != Some(&relation.id) {
+                    ids.push(relation.id);
+                }
+            }
+        }
+        parents
+    }
+
+    /// 构建 multipolygon/boundary Relation 的包围盒索引；成员 Way 均未加载的 Relation 不入索引
+    pub fn area_relation_index(&self) -> AreaRelationIndex {
+        let entries = self
+            .relations
+            .iter()
+            .filter(|relation| matches!(relation.tag("type"), Some("multipolygon" | "boundary")))
+            .filter_map(|relation| {
+                relation
+                    .members
+                    .iter()
+                    .filter(|m| m.member_type == MemberType::Way)
+                    .filter_map(|m| {
+                        let way = self.ways.get(&m.ref_id)?;
+                        self.compute_way_bbox(&way)
+                    })
+                    .map(|bbox| SpatialEntry {
+                        id: relation.id,
+                        ..bbox
+                    })
+                    .reduce(|a, b| SpatialEntry {
+                        id: a.id,
+                        min_lon: a.min_lon.min(b.min_lon),
+                        min_lat: a.min_lat.min(b.min_lat),
+                        max_lon: a.max_lon.max(b.max_lon),
+                        max_lat: a.max_lat.max(b.max_lat),
+                    })
+            })
+            .collect();
+        RTree::bulk_load(entries)
+    }
+
+    /// 按位置移除 Relation 成员（indices 为移除前的位置，升序）
+    pub fn remove_relation_members(&self, relation_id: i64, indices: &[usize]) {
+        if let Some(mut relation) = self.relations.get_mut(&relation_id) {
+            // 从后往前删除，避免索引位移问题
+            for &idx in indices.iter().rev() {
+                if idx < relation.members.len() {
+                    relation.members.remove(idx);
+                }
+            }
+        }
+    }
+
+    /// 在 Relation 的指定位置恢复成员（位置升序，与 remove_relation_members 对称）
+    pub fn insert_relation_members(&self, relation_id: i64, members: &[(usize, RelationMember)]) {
+        if let Some(mut relation) = self.relations.get_mut(&relation_id) {
+            for (idx, member) in members {
+                let insert_pos = (*idx).min(relation.members.len());
+                relation.members.insert(insert_pos, member.clone());
+            }
+        }
+    }
+
+    /// 将 Relation 指定位置成员的引用替换为另一个要素（成员类型和角色不变）
+    pub fn replace_relation_member_refs(&self, relation_id: i64, indices: &[usize], new_ref: i64) {
+        if let Some(mut relation) = self.relations.get_mut(&relation_id) {
+            for &idx in indices {
+                if let Some(member) = relation.members.get_mut(idx) {
+                    member.ref_id = new_ref;
+                }
+            }
+        }
+    }
 }
 
 impl Default for OsmStore {
@@ -576,3 +901,39 @@ pub struct DataBounds {
     pub center_lon: f64,
     pub center_lat: f64,
 }
+
+impl DataBounds {
+    /// 由一组经纬度坐标计算边界框（无坐标时返回 None）
+    pub fn from_points(points: impl IntoIterator<Item = (f64, f64)>) -> Option<Self> {
+        let mut bounds: Option<(f64, f64, f64, f64)> = None;
+        for (lon, lat) in points {
+            let (min_lon, min_lat, max_lon, max_lat) = bounds.unwrap_or((lon, lat, lon, lat));
+            bounds = Some((
+                min_lon.min(lon),
+                min_lat.min(lat),
+                max_lon.max(lon),
+                max_lat.max(lat),
+            ));
+        }
+
+        bounds.map(|(min_lon, min_lat, max_lon, max_lat)| DataBounds {
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+            center_lon: (min_lon + max_lon) / 2.0,
+            center_lat: (min_lat + max_lat) / 2.0,
+        })
+    }
+
+    /// 两个边界框的并集
+    pub fn union(&self, other: &DataBounds) -> DataBounds {
+        DataBounds::from_points([
+            (self.min_lon, self.min_lat),
+            (self.max_lon, self.max_lat),
+            (other.min_lon, other.min_lat),
+            (other.max_lon, other.max_lat),
+        ])
+        .unwrap_or_else(|| self.clone())
+    }
+}
@@ -0,0 +1,1438 @@
+//! Overpass QL 子集查询引擎
+//!
+//! 在本地 `OsmStore` 上解析并执行常用的 Overpass QL 语句：
+//! - 设置：`[out:json]`、`[timeout:N]`、`[maxsize:N]`（忽略），`[bbox:南,西,北,东]`（全局范围）
+//! - 查询：`node` / `way` / `rel` / `nwr`，可用 `.a` 限定在输入集合内
+//! - 过滤条件：
+//!   - 标签：`[k]`、`[!k]`、`[k=v]`、`[k!=v]`、`[k~re]`、`[k!~re]`（正则可加 `,i` 忽略大小写）
+//!   - 范围：`(南,西,北,东)`，括号内恰好四个数
+//!   - 距离：`(around:半径,纬度,经度)`、`(around.a:半径)`（米；集合按其节点计算）
+//!   - ID：`(123)`、`(id:1,2,3)`（多个 ID 需用 `id:`）
+//! - 并集 `( ... );`、集合 `.a;`、结果赋值 `->.a`
+//! - 递归：`>`（下一层）、`>>`（全部下层）、`<`（上一层）、`<<`（全部上层）
+//! - 输出：`out [ids|skel|body|tags|meta|geom|center|count] [数量];`
+//!
+//! 空间条件先用 R-Tree 取候选，再按几何精确判断；没有输入集合、ID 和空间条件时
+//! 用标签索引取候选。查询中没有 `out` 语句时输出最终的 `_` 集合。
+
+use crate::osm_store::{MemberType, OsmStore, RelationParents};
+use crate::projection;
+use crate::spatial_query::{self, point_to_segment_distance_sq};
+use crate::types::FeatureSelection;
+use regex::{Regex, RegexBuilder};
+use rstar::AABB;
+use std::cell::OnceCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// 默认集合
+const DEFAULT_SET: &str = "_";
+
+// ==================== 词法分析 ====================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// 标识符（字母、数字、下划线）
+    Ident(String),
+    /// 数字原文
+    Number(String),
+    /// 引号字符串（已处理转义）
+    Str(String),
+    Punct(&'static str),
+}
+
+/// 符号（多字符的在前，优先匹配）
+const PUNCTS: &[&str] = &[
+    "->", ">>", "<<", "!=", "!~", "[", "]", "(", ")", ";", ",", ".", "=", "~", "!", ">", "<", ":",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while let Some(c) = input[i..].chars().next() {
+        let rest = &input[i..];
+        if c.is_whitespace() {
+            i += c.len_utf8();
+        } else if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if rest.starts_with("/*") {
+            i += rest.find("*/").ok_or("Unterminated comment")? + 2;
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            let mut chars = rest.char_indices().skip(1);
+            let end = loop {
+                match chars.next() {
+                    Some((j, ch)) if ch == c => break j,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, 't')) => value.push('\t'),
+                        Some((_, ch)) => value.push(ch),
+                        None => return Err("Unterminated string".to_string()),
+                    },
+                    Some((_, ch)) => value.push(ch),
+                    None => return Err("Unterminated string".to_string()),
+                }
+            };
+            tokens.push(Token::Str(value));
+            i += end + 1;
+        } else if c.is_ascii_digit()
+            || (c == '-' && rest[1..].starts_with(|d: char| d.is_ascii_digit() || d == '.'))
+        {
+            let len = rest[1..]
+                .find(|d: char| !(d.is_ascii_digit() || d == '.'))
+                .map_or(rest.len(), |n| n + 1);
+            tokens.push(Token::Number(rest[..len].to_string()));
+            i += len;
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|d: char| !(d.is_alphanumeric() || d == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            i += len;
+        } else {
+            let punct = PUNCTS
+                .iter()
+                .find(|p| rest.starts_with(**p))
+                .ok_or_else(|| format!("Unexpected character '{}'", c))?;
+            tokens.push(Token::Punct(punct));
+            i += punct.len();
+        }
+    }
+    Ok(tokens)
+}
+
+// ==================== 语法树 ====================
+
+/// 查询的要素类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QueryType {
+    Node,
+    Way,
+    Relation,
+    /// nwr
+    Any,
+}
+
+impl QueryType {
+    fn member_types(self) -> &'static [MemberType] {
+        match self {
+            Self::Node => &[MemberType::Node],
+            Self::Way => &[MemberType::Way],
+            Self::Relation => &[MemberType::Relation],
+            Self::Any => &[MemberType::Node, MemberType::Way, MemberType::Relation],
+        }
+    }
+}
+
+/// 经纬度范围
+#[derive(Debug, Clone, Copy)]
+struct Bbox {
+    south: f64,
+    west: f64,
+    north: f64,
+    east: f64,
+}
+
+impl Bbox {
+    fn envelope(&self) -> AABB<[f64; 2]> {
+        AABB::from_corners([self.west, self.south], [self.east, self.north])
+    }
+
+    fn contains(&self, (lon, lat): (f64, f64)) -> bool {
+        lon >= self.west && lon <= self.east && lat >= self.south && lat <= self.north
+    }
+
+    /// 线段与范围是否相交（Liang–Barsky 裁剪）
+    fn intersects_segment(&self, (x0, y0): (f64, f64), (x1, y1): (f64, f64)) -> bool {
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let (mut t0, mut t1) = (0.0, 1.0);
+        for (p, q) in [
+            (-dx, x0 - self.west),
+            (dx, self.east - x0),
+            (-dy, y0 - self.south),
+            (dy, self.north - y0),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return false;
+                }
+                continue;
+            }
+            let r = q / p;
+            if p < 0.0 {
+                if r > t1 {
+                    return false;
+                }
+                t0 = f64::max(t0, r);
+            } else {
+                if r < t0 {
+                    return false;
+                }
+                t1 = f64::min(t1, r);
+            }
+        }
+        true
+    }
+
+    fn intersects_line(&self, line: &[(f64, f64)]) -> bool {
+        match line {
+            [point] => self.contains(*point),
+            _ => line
+                .windows(2)
+                .any(|pair| self.intersects_segment(pair[0], pair[1])),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum TagFilter {
+    Exists(String),
+    NotExists(String),
+    Equals(String, String),
+    /// 不带该标签或取值不同
+    NotEquals(String, String),
+    /// negated 时匹配不带该标签或取值不匹配的要素
+    Matches {
+        key: String,
+        regex: Regex,
+        negated: bool,
+    },
+}
+
+impl TagFilter {
+    fn matches(&self, tags: &[(String, String)]) -> bool {
+        let has =
+            |key: &str, test: &dyn Fn(&str) -> bool| tags.iter().any(|(k, v)| k == key && test(v));
+        match self {
+            Self::Exists(key) => has(key, &|_| true),
+            Self::NotExists(key) => !has(key, &|_| true),
+            Self::Equals(key, value) => has(key, &|v| v == value),
+            Self::NotEquals(key, value) => !has(key, &|v| v == value),
+            Self::Matches {
+                key,
+                regex,
+                negated,
+            } => has(key, &|v| regex.is_match(v)) != *negated,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum AroundCenter {
+    Point { lat: f64, lon: f64 },
+    Set(String),
+}
+
+#[derive(Debug)]
+enum Filter {
+    Tag(TagFilter),
+    Bbox(Bbox),
+    Around { center: AroundCenter, radius: f64 },
+    Ids(Vec<i64>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Recurse {
+    /// `>`：Way 的节点、Relation 的成员（及成员 Way 的节点）
+    Down,
+    /// `>>`：递归向下直到没有新要素
+    DownAll,
+    /// `<`：引用这些要素的 Way 和 Relation
+    Up,
+    /// `<<`：递归向上直到没有新要素
+    UpAll,
+}
+
+/// 输出模式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutMode {
+    Ids,
+    Skel,
+    /// body / meta
+    Body,
+    Tags,
+    Geom,
+    Center,
+    Count,
+}
+
+#[derive(Debug)]
+enum Statement {
+    Query {
+        query_type: QueryType,
+        input: Option<String>,
+        filters: Vec<Filter>,
+        output: String,
+    },
+    Union {
+        statements: Vec<Statement>,
+        output: String,
+    },
+    Item {
+        input: String,
+        output: String,
+    },
+    Recurse {
+        recurse: Recurse,
+        input: String,
+        output: String,
+    },
+    Out {
+        input: String,
+        mode: OutMode,
+        limit: Option<usize>,
+    },
+}
+
+impl Statement {
+    /// 语句写入的集合
+    fn output(&self) -> Option<&str> {
+        match self {
+            Self::Query { output, .. }
+            | Self::Union { output, .. }
+            | Self::Item { output, .. }
+            | Self::Recurse { output, .. } => Some(output),
+            Self::Out { .. } => None,
+        }
+    }
+}
+
+struct Program {
+    bbox: Option<Bbox>,
+    statements: Vec<Statement>,
+}
+
+// ==================== 语法分析 ====================
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = self.is_punct(punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// 当前位置的描述（用于错误信息）
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(Token::Ident(s)) | Some(Token::Number(s)) => format!("'{}'", s),
+            Some(Token::Str(s)) => format!("\"{}\"", s),
+            Some(Token::Punct(p)) => format!("'{}'", p),
+            None => "end of query".to_string(),
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' but found {}", punct, self.found()))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Ident(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => Err(format!("Expected identifier but found {}", self.found())),
+        }
+    }
+
+    fn number_text(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Number(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => Err(format!("Expected number but found {}", self.found())),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        let text = self.number_text()?;
+        text.parse()
+            .map_err(|_| format!("Invalid number '{}'", text))
+    }
+
+    /// 逗号分隔的数字原文
+    fn number_list(&mut self) -> Result<Vec<String>, String> {
+        let mut numbers = vec![self.number_text()?];
+        while self.eat(",") {
+            numbers.push(self.number_text()?);
+        }
+        Ok(numbers)
+    }
+
+    /// 标签 key 或 value：引号字符串，或由冒号连接的标识符/数字（如 name:en）
+    fn text(&mut self) -> Result<String, String> {
+        let mut text = match self.peek() {
+            Some(Token::Str(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                return Ok(s);
+            }
+            Some(Token::Ident(s)) | Some(Token::Number(s)) => s.clone(),
+            _ => return Err(format!("Expected tag text but found {}", self.found())),
+        };
+        self.pos += 1;
+        while self.is_punct(":") {
+            match self.tokens.get(self.pos + 1) {
+                Some(Token::Ident(s)) | Some(Token::Number(s)) => {
+                    text.push(':');
+                    text.push_str(s);
+                    self.pos += 2;
+                }
+                _ => break,
+            }
+        }
+        Ok(text)
+    }
+
+    fn bbox(&mut self) -> Result<Bbox, String> {
+        let south = self.number()?;
+        self.expect(",")?;
+        let west = self.number()?;
+        self.expect(",")?;
+        let north = self.number()?;
+        self.expect(",")?;
+        let east = self.number()?;
+        if south > north || west > east {
+            return Err("Invalid bbox".to_string());
+        }
+        Ok(Bbox {
+            south,
+            west,
+            north,
+            east,
+        })
+    }
+
+    /// `->.name`，省略时写入默认集合
+    fn output(&mut self) -> Result<String, String> {
+        if self.eat("->") {
+            self.expect(".")?;
+            self.ident()
+        } else {
+            Ok(DEFAULT_SET.to_string())
+        }
+    }
+
+    fn program(&mut self) -> Result<Program, String> {
+        let mut bbox = None;
+        let mut has_settings = false;
+        while self.eat("[") {
+            let name = self.ident()?;
+            self.expect(":")?;
+            match name.as_str() {
+                "bbox" => bbox = Some(self.bbox()?),
+                "out" | "timeout" | "maxsize" => {
+                    self.text()?;
+                }
+                _ => return Err(format!("Unsupported setting '{}'", name)),
+            }
+            self.expect("]")?;
+            has_settings = true;
+        }
+        if has_settings {
+            self.expect(";")?;
+        }
+
+        let mut statements = Vec::new();
+        while self.peek().is_some() {
+            statements.push(self.statement()?);
+        }
+        Ok(Program { bbox, statements })
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.eat("(") {
+            let mut statements = Vec::new();
+            while !self.eat(")") {
+                if self.peek().is_none() {
+                    return Err("Unterminated union".to_string());
+                }
+                statements.push(self.statement()?);
+            }
+            let output = self.output()?;
+            self.expect(";")?;
+            return Ok(Statement::Union { statements, output });
+        }
+
+        let input = if self.eat(".") {
+            Some(self.ident()?)
+        } else {
+            None
+        };
+        let statement = match self.peek().cloned() {
+            Some(Token::Punct(p @ (">" | ">>" | "<" | "<<"))) => {
+                self.pos += 1;
+                let recurse = match p {
+                    ">" => Recurse::Down,
+                    ">>" => Recurse::DownAll,
+                    "<" => Recurse::Up,
+                    _ => Recurse::UpAll,
+                };
+                Statement::Recurse {
+                    recurse,
+                    input: input.unwrap_or_else(|| DEFAULT_SET.to_string()),
+                    output: self.output()?,
+                }
+            }
+            Some(Token::Ident(word)) if word == "out" => {
+                self.pos += 1;
+                self.out(input.unwrap_or_else(|| DEFAULT_SET.to_string()))?
+            }
+            Some(Token::Ident(word)) if input.is_none() => {
+                self.pos += 1;
+                self.query(&word)?
+            }
+            _ => match input {
+                Some(input) => Statement::Item {
+                    input,
+                    output: self.output()?,
+                },
+                None => return Err(format!("Expected statement but found {}", self.found())),
+            },
+        };
+        self.expect(";")?;
+        Ok(statement)
+    }
+
+    fn query(&mut self, word: &str) -> Result<Statement, String> {
+        let query_type = match word {
+            "node" => QueryType::Node,
+            "way" => QueryType::Way,
+            "rel" | "relation" => QueryType::Relation,
+            "nwr" => QueryType::Any,
+            _ => return Err(format!("Unsupported statement '{}'", word)),
+        };
+        let input = if self.eat(".") {
+            Some(self.ident()?)
+        } else {
+            None
+        };
+
+        let mut filters = Vec::new();
+        loop {
+            if self.eat("[") {
+                filters.push(Filter::Tag(self.tag_filter()?));
+            } else if self.eat("(") {
+                filters.push(self.paren_filter()?);
+            } else {
+                break;
+            }
+        }
+
+        Ok(Statement::Query {
+            query_type,
+            input,
+            filters,
+            output: self.output()?,
+        })
+    }
+
+    fn tag_filter(&mut self) -> Result<TagFilter, String> {
+        let key_negated = self.eat("!");
+        let key = self.text()?;
+        let filter = if key_negated {
+            TagFilter::NotExists(key)
+        } else if self.eat("=") {
+            TagFilter::Equals(key, self.text()?)
+        } else if self.eat("!=") {
+            TagFilter::NotEquals(key, self.text()?)
+        } else if self.is_punct("~") || self.is_punct("!~") {
+            let negated = self.eat("!~");
+            if !negated {
+                self.pos += 1;
+            }
+            let pattern = self.text()?;
+            let case_insensitive = if self.eat(",") {
+                match self.ident()?.as_str() {
+                    "i" => true,
+                    flag => return Err(format!("Unsupported regex flag '{}'", flag)),
+                }
+            } else {
+                false
+            };
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(case_insensitive)
+                .build()
+                .map_err(|e| format!("Invalid regex: {}", e))?;
+            TagFilter::Matches {
+                key,
+                regex,
+                negated,
+            }
+        } else {
+            TagFilter::Exists(key)
+        };
+        self.expect("]")?;
+        Ok(filter)
+    }
+
+    fn paren_filter(&mut self) -> Result<Filter, String> {
+        let filter = match self.peek().cloned() {
+            Some(Token::Ident(word)) if word == "around" => {
+                self.pos += 1;
+                let set = if self.eat(".") {
+                    Some(self.ident()?)
+                } else {
+                    None
+                };
+                self.expect(":")?;
+                let radius = self.number()?;
+                if radius < 0.0 {
+                    return Err("Around radius must not be negative".to_string());
+                }
+                let center = match set {
+                    Some(set) => AroundCenter::Set(set),
+                    None if self.eat(",") => {
+                        let lat = self.number()?;
+                        self.expect(",")?;
+                        let lon = self.number()?;
+                        AroundCenter::Point { lat, lon }
+                    }
+                    None => AroundCenter::Set(DEFAULT_SET.to_string()),
+                };
+                Filter::Around { center, radius }
+            }
+            Some(Token::Ident(word)) if word == "id" => {
+                self.pos += 1;
+                self.expect(":")?;
+                Filter::Ids(parse_ids(&self.number_list()?)?)
+            }
+            Some(Token::Number(_)) => {
+                let start = self.pos;
+                let numbers = self.number_list()?;
+                match numbers.len() {
+                    4 => {
+                        self.pos = start;
+                        Filter::Bbox(self.bbox()?)
+                    }
+                    1 => Filter::Ids(parse_ids(&numbers)?),
+                    _ => {
+                        return Err(
+                            "Expected (south,west,north,east) or a single id; use (id:1,2,3) \
+                             for id lists"
+                                .to_string(),
+                        )
+                    }
+                }
+            }
+            _ => return Err(format!("Unsupported filter {}", self.found())),
+        };
+        self.expect(")")?;
+        Ok(filter)
+    }
+
+    fn out(&mut self, input: String) -> Result<Statement, String> {
+        let mut mode = OutMode::Body;
+        let mut limit = None;
+        loop {
+            match self.peek().cloned() {
+                Some(Token::Ident(word)) => {
+                    self.pos += 1;
+                    mode = match word.as_str() {
+                        "ids" => OutMode::Ids,
+                        "skel" => OutMode::Skel,
+                        "body" | "meta" => OutMode::Body,
+                        "tags" => OutMode::Tags,
+                        "geom" => OutMode::Geom,
+                        "center" => OutMode::Center,
+                        "count" => OutMode::Count,
+                        "qt" | "asc" | "noids" => continue,
+                        _ => return Err(format!("Unsupported out mode '{}'", word)),
+                    };
+                }
+                Some(Token::Number(text)) => {
+                    self.pos += 1;
+                    limit = Some(
+                        text.parse()
+                            .map_err(|_| format!("Invalid out limit '{}'", text))?,
+                    );
+                }
+                _ => break,
+            }
+        }
+        Ok(Statement::Out { input, mode, limit })
+    }
+}
+
+fn parse_ids(numbers: &[String]) -> Result<Vec<i64>, String> {
+    numbers
+        .iter()
+        .map(|n| n.parse().map_err(|_| format!("Invalid id '{}'", n)))
+        .collect()
+}
+
+// ==================== 执行 ====================
+
+/// 要素集合（按类型分别有序）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ElementSet {
+    nodes: BTreeSet<i64>,
+    ways: BTreeSet<i64>,
+    relations: BTreeSet<i64>,
+}
+
+impl ElementSet {
+    fn ids(&self, member_type: MemberType) -> &BTreeSet<i64> {
+        match member_type {
+            MemberType::Node => &self.nodes,
+            MemberType::Way => &self.ways,
+            MemberType::Relation => &self.relations,
+        }
+    }
+
+    fn ids_mut(&mut self, member_type: MemberType) -> &mut BTreeSet<i64> {
+        match member_type {
+            MemberType::Node => &mut self.nodes,
+            MemberType::Way => &mut self.ways,
+            MemberType::Relation => &mut self.relations,
+        }
+    }
+
+    fn insert(&mut self, member_type: MemberType, id: i64) -> bool {
+        self.ids_mut(member_type).insert(id)
+    }
+
+    pub fn count(&self) -> usize {
+        self.nodes.len() + self.ways.len() + self.relations.len()
+    }
+
+    /// 按 Node、Way、Relation 顺序遍历
+    pub fn iter(&self) -> impl Iterator<Item = (MemberType, i64)> + '_ {
+        [MemberType::Node, MemberType::Way, MemberType::Relation]
+            .into_iter()
+            .flat_map(move |t| self.ids(t).iter().map(move |&id| (t, id)))
+    }
+
+    pub fn selection(&self) -> FeatureSelection {
+        FeatureSelection {
+            node_ids: self.nodes.iter().copied().collect(),
+            way_ids: self.ways.iter().copied().collect(),
+            relation_ids: self.relations.iter().copied().collect(),
+        }
+    }
+}
+
+/// 一条 `out` 语句的输出
+pub struct OutputBlock {
+    pub mode: OutMode,
+    pub elements: ElementSet,
+}
+
+/// 距离条件：中心点的墨卡托坐标及对应的墨卡托距离上限（平方）
+struct Around {
+    centers: Vec<(f64, f64, f64)>,
+    /// 候选范围（经纬度），没有中心点时为 None
+    bbox: Option<Bbox>,
+}
+
+impl Around {
+    fn new(points: &[(f64, f64)], radius: f64) -> Self {
+        let mut bbox: Option<Bbox> = None;
+        let centers = points
+            .iter()
+            .map(|&(lon, lat)| {
+                let (x, y) = projection::lonlat_to_mercator(lon, lat);
+                let limit = radius * projection::mercator_scale_factor(lat);
+                let (west, south) = projection::mercator_to_lonlat(x - limit, y - limit);
+                let (east, north) = projection::mercator_to_lonlat(x + limit, y + limit);
+                bbox = Some(match bbox {
+                    Some(b) => Bbox {
+                        south: b.south.min(south),
+                        west: b.west.min(west),
+                        north: b.north.max(north),
+                        east: b.east.max(east),
+                    },
+                    None => Bbox {
+                        south,
+                        west,
+                        north,
+                        east,
+                    },
+                });
+                (x, y, limit * limit)
+            })
+            .collect();
+        Self { centers, bbox }
+    }
+
+    fn reaches(&self, line: &[(f64, f64)]) -> bool {
+        let line: Vec<(f64, f64)> = line
+            .iter()
+            .map(|&(lon, lat)| projection::lonlat_to_mercator(lon, lat))
+            .collect();
+        let segments: Vec<((f64, f64), (f64, f64))> = match line.as_slice() {
+            [point] => vec![(*point, *point)],
+            _ => line.windows(2).map(|pair| (pair[0], pair[1])).collect(),
+        };
+        self.centers.iter().any(|&(x, y, limit_sq)| {
+            segments.iter().any(|&((x1, y1), (x2, y2))| {
+                point_to_segment_distance_sq(x, y, x1, y1, x2, y2) <= limit_sq
+            })
+        })
+    }
+}
+
+/// 解析后的过滤条件
+enum Check<'a> {
+    Tag(&'a TagFilter),
+    Bbox(Bbox),
+    Around(Around),
+    Ids(HashSet<i64>),
+}
+
+impl Check<'_> {
+    /// 空间候选范围
+    fn spatial_bbox(&self) -> Option<Option<Bbox>> {
+        match self {
+            Check::Bbox(bbox) => Some(Some(*bbox)),
+            Check::Around(around) => Some(around.bbox),
+            _ => None,
+        }
+    }
+}
+
+struct Executor<'a> {
+    store: &'a OsmStore,
+    bbox: Option<Bbox>,
+    sets: HashMap<String, ElementSet>,
+    outputs: Vec<OutputBlock>,
+    /// 成员 → 所属 Relation，首次向上递归时构建
+    parents: OnceCell<RelationParents>,
+}
+
+impl Executor<'_> {
+    /// 读取集合（未定义的集合为空）
+    fn set(&self, name: &str) -> ElementSet {
+        self.sets.get(name).cloned().unwrap_or_default()
+    }
+
+    fn execute(&mut self, statement: &Statement) {
+        let result = match statement {
+            Statement::Query {
+                query_type,
+                input,
+                filters,
+                output,
+            } => (output, self.query(*query_type, input.as_deref(), filters)),
+            Statement::Union { statements, output } => {
+                let mut result = ElementSet::default();
+                for statement in statements {
+                    self.execute(statement);
+                    if let Some(name) = statement.output() {
+                        for (member_type, id) in self.set(name).iter() {
+                            result.insert(member_type, id);
+                        }
+                    }
+                }
+                (output, result)
+            }
+            Statement::Item { input, output } => (output, self.set(input)),
+            Statement::Recurse {
+                recurse,
+                input,
+                output,
+            } => (output, self.recurse(*recurse, &self.set(input))),
+            Statement::Out { input, mode, limit } => {
+                let set = self.set(input);
+                let elements = match limit {
+                    Some(limit) => {
+                        let mut limited = ElementSet::default();
+                        for (member_type, id) in set.iter().take(*limit) {
+                            limited.insert(member_type, id);
+                        }
+                        limited
+                    }
+                    None => set,
+                };
+                self.outputs.push(OutputBlock {
+                    mode: *mode,
+                    elements,
+                });
+                return;
+            }
+        };
+        self.sets.insert(result.0.clone(), result.1);
+    }
+
+    fn resolve<'f>(&self, filter: &'f Filter) -> Check<'f> {
+        match filter {
+            Filter::Tag(tag) => Check::Tag(tag),
+            Filter::Bbox(bbox) => Check::Bbox(*bbox),
+            Filter::Ids(ids) => Check::Ids(ids.iter().copied().collect()),
+            Filter::Around { center, radius } => {
+                let points = match center {
+                    AroundCenter::Point { lat, lon } => vec![(*lon, *lat)],
+                    AroundCenter::Set(name) => self
+                        .set(name)
+                        .iter()
+                        .filter_map(|(t, id)| spatial_query::feature_lines(self.store, t, id))
+                        .flatten()
+                        .flatten()
+                        .collect(),
+                };
+                Check::Around(Around::new(&points, *radius))
+            }
+        }
+    }
+
+    fn query(&self, query_type: QueryType, input: Option<&str>, filters: &[Filter]) -> ElementSet {
+        let mut checks: Vec<Check> = filters.iter().map(|f| self.resolve(f)).collect();
+        // 全局范围作用于没有显式范围条件的查询
+        if let Some(bbox) = self.bbox {
+            if !checks.iter().any(|c| matches!(c, Check::Bbox(_))) {
+                checks.push(Check::Bbox(bbox));
+            }
+        }
+        let input = input.map(|name| self.set(name));
+
+        let mut result = ElementSet::default();
+        for &member_type in query_type.member_types() {
+            for id in self.candidates(member_type, input.as_ref(), &checks) {
+                if checks.iter().all(|c| self.matches(member_type, id, c)) {
+                    result.insert(member_type, id);
+                }
+            }
+        }
+        result
+    }
+
+    /// 候选要素：依次尝试输入集合、ID、空间索引、标签索引，最后遍历全部要素
+    fn candidates(
+        &self,
+        member_type: MemberType,
+        input: Option<&ElementSet>,
+        checks: &[Check],
+    ) -> Vec<i64> {
+        if let Some(input) = input {
+            return input.ids(member_type).iter().copied().collect();
+        }
+        if let Some(Check::Ids(ids)) = checks.iter().find(|c| matches!(c, Check::Ids(_))) {
+            return ids.iter().copied().collect();
+        }
+        if let Some(bbox) = checks.iter().find_map(Check::spatial_bbox) {
+            return match bbox {
+                Some(bbox) => self.spatial_candidates(member_type, &bbox),
+                None => Vec::new(),
+            };
+        }
+
+        let index = self.store.tag_index();
+        let of_type = |features: &HashSet<(MemberType, i64)>| {
+            features
+                .iter()
+                .filter(|(t, _)| *t == member_type)
+                .map(|&(_, id)| id)
+                .collect::<Vec<_>>()
+        };
+        for check in checks {
+            match check {
+                Check::Tag(TagFilter::Equals(key, value)) => {
+                    return index.features(key, value).map(of_type).unwrap_or_default();
+                }
+                Check::Tag(TagFilter::Exists(key)) => {
+                    return index
+                        .values(key)
+                        .flat_map(|(_, features)| of_type(features))
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        drop(index);
+
+        match member_type {
+            MemberType::Node => self.store.nodes.iter().map(|n| n.id).collect(),
+            MemberType::Way => self.store.ways.iter().map(|w| w.id).collect(),
+            MemberType::Relation => self.store.relations.iter().map(|r| r.id).collect(),
+        }
+    }
+
+    fn spatial_candidates(&self, member_type: MemberType, bbox: &Bbox) -> Vec<i64> {
+        let envelope = bbox.envelope();
+        let nodes = || -> Vec<i64> {
+            self.store
+                .node_index()
+                .locate_in_envelope(&envelope)
+                .map(|e| e.id)
+                .collect()
+        };
+        let ways = || -> Vec<i64> {
+            self.store
+                .way_index()
+                .locate_in_envelope_intersecting(&envelope)
+                .map(|e| e.id)
+                .collect()
+        };
+        match member_type {
+            MemberType::Node => nodes(),
+            MemberType::Way => ways(),
+            MemberType::Relation => {
+                let nodes: HashSet<i64> = nodes().into_iter().collect();
+                let ways: HashSet<i64> = ways().into_iter().collect();
+                self.store
+                    .relations
+                    .iter()
+                    .filter(|r| {
+                        r.members.iter().any(|m| match m.member_type {
+                            MemberType::Node => nodes.contains(&m.ref_id),
+                            MemberType::Way => ways.contains(&m.ref_id),
+                            MemberType::Relation => false,
+                        })
+                    })
+                    .map(|r| r.id)
+                    .collect()
+            }
+        }
+    }
+
+    fn matches(&self, member_type: MemberType, id: i64, check: &Check) -> bool {
+        let store = self.store;
+        match check {
+            Check::Tag(filter) => match member_type {
+                MemberType::Node => store
+                    .nodes
+                    .get(&id)
+                    .is_some_and(|n| filter.matches(&n.tags)),
+                MemberType::Way => store.ways.get(&id).is_some_and(|w| filter.matches(&w.tags)),
+                MemberType::Relation => store
+                    .relations
+                    .get(&id)
+                    .is_some_and(|r| filter.matches(&r.tags)),
+            },
+            Check::Ids(ids) => {
+                ids.contains(&id) && spatial_query::feature_lines(store, member_type, id).is_some()
+            }
+            Check::Bbox(bbox) => spatial_query::feature_lines(store, member_type, id)
+                .is_some_and(|lines| lines.iter().any(|line| bbox.intersects_line(line))),
+            Check::Around(around) => spatial_query::feature_lines(store, member_type, id)
+                .is_some_and(|lines| lines.iter().any(|line| around.reaches(line))),
+        }
+    }
+
+    fn recurse(&self, recurse: Recurse, input: &ElementSet) -> ElementSet {
+        match recurse {
+            Recurse::Down => self.down(input),
+            Recurse::Up => self.up(input),
+            Recurse::DownAll => self.closure(input, Self::down),
+            Recurse::UpAll => self.closure(input, Self::up),
+        }
+    }
+
+    /// 反复递归直到没有新要素，返回所有到达的要素
+    fn closure(
+        &self,
+        input: &ElementSet,
+        step: fn(&Self, &ElementSet) -> ElementSet,
+    ) -> ElementSet {
+        let mut result = ElementSet::default();
+        let mut frontier = input.clone();
+        loop {
+            let mut found = ElementSet::default();
+            for (member_type, id) in step(self, &frontier).iter() {
+                if result.insert(member_type, id) {
+                    found.insert(member_type, id);
+                }
+            }
+            if found.count() == 0 {
+                return result;
+            }
+            frontier = found;
+        }
+    }
+
+    /// 未加载的成员和节点不计入结果
+    fn down(&self, input: &ElementSet) -> ElementSet {
+        let store = self.store;
+        let mut result = ElementSet::default();
+        let add_way_nodes = |result: &mut ElementSet, way_id: i64| {
+            if let Some(way) = store.ways.get(&way_id) {
+                for &node_id in &way.node_refs {
+                    if store.nodes.contains_key(&node_id) {
+                        result.insert(MemberType::Node, node_id);
+                    }
+                }
+            }
+        };
+        for &way_id in &input.ways {
+            add_way_nodes(&mut result, way_id);
+        }
+        for relation_id in &input.relations {
+            let Some(relation) = store.relations.get(relation_id) else {
+                continue;
+            };
+            for member in &relation.members {
+                if !store.contains_feature(member.member_type, member.ref_id) {
+                    continue;
+                }
+                result.insert(member.member_type, member.ref_id);
+                if member.member_type == MemberType::Way {
+                    add_way_nodes(&mut result, member.ref_id);
+                }
+            }
+        }
+        result
+    }
+
+    /// 引用节点的 Way 取路径索引中包含该节点位置的候选，所属 Relation 查成员反查表
+    fn up(&self, input: &ElementSet) -> ElementSet {
+        let store = self.store;
+        let mut result = ElementSet::default();
+        if !input.nodes.is_empty() {
+            let way_index = store.way_index();
+            for node_id in &input.nodes {
+                if store
+                    .node_ref_count
+                    .get(node_id)
+                    .is_none_or(|count| *count == 0)
+                {
+                    continue;
+                }
+                let Some(point) = store.nodes.get(node_id).map(|n| [n.lon, n.lat]) else {
+                    continue;
+                };
+                for entry in way_index.locate_in_envelope_intersecting(&AABB::from_point(point)) {
+                    if store
+                        .ways
+                        .get(&entry.id)
+                        .is_some_and(|way| way.node_refs.contains(node_id))
+                    {
+                        result.insert(MemberType::Way, entry.id);
+                    }
+                }
+            }
+        }
+
+        let parents = self.parents.get_or_init(|| store.relation_parents());
+        for feature in input.iter() {
+            for &relation_id in parents.get(&feature).into_iter().flatten() {
+                result.insert(MemberType::Relation, relation_id);
+            }
+        }
+        result
+    }
+}
+
+/// 解析并执行查询，返回每条 `out` 语句的输出
+pub fn run(store: &OsmStore, query: &str) -> Result<Vec<OutputBlock>, String> {
+    let program = Parser {
+        tokens: tokenize(query)?,
+        pos: 0,
+    }
+    .program()?;
+
+    let mut executor = Executor {
+        store,
+        bbox: program.bbox,
+        sets: HashMap::new(),
+        outputs: Vec::new(),
+        parents: OnceCell::new(),
+    };
+    for statement in &program.statements {
+        executor.execute(statement);
+    }
+    if executor.outputs.is_empty() {
+        executor.execute(&Statement::Out {
+            input: DEFAULT_SET.to_string(),
+            mode: OutMode::Body,
+            limit: None,
+        });
+    }
+    Ok(executor.outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_store::{OsmNode, OsmRelation, OsmWay, RelationMember};
+
+    fn tags(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    /// 咖啡馆节点 1 (0, 0)、酒吧节点 2 (0.01, 0)；道路 Way 10: 3 → 4；
+    /// 公交线路 Relation 100 包含 Way 10 和节点 1
+    fn store() -> OsmStore {
+        let store = OsmStore::new();
+        for (id, lon, lat, node_tags) in [
+            (
+                1,
+                0.0,
+                0.0,
+                tags(&[("amenity", "cafe"), ("name", "Café Central")]),
+            ),
+            (2, 0.01, 0.0, tags(&[("amenity", "bar")])),
+            (3, -0.001, 0.001, vec![]),
+            (4, 0.001, 0.001, vec![]),
+        ] {
+            store.insert_node(OsmNode {
+                id,
+                lon,
+                lat,
+                tags: node_tags,
+            });
+        }
+        store.insert_way(OsmWay {
+            id: 10,
+            node_refs: vec![3, 4],
+            tags: tags(&[("highway", "residential"), ("name:en", "Main St")]),
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        });
+        store.relations.insert(
+            100,
+            OsmRelation {
+                id: 100,
+                members: vec![
+                    RelationMember {
+                        member_type: MemberType::Way,
+                        ref_id: 10,
+                        role: String::new(),
+                    },
+                    RelationMember {
+                        member_type: MemberType::Node,
+                        ref_id: 1,
+                        role: "stop".to_string(),
+                    },
+                ],
+                tags: tags(&[("type", "route"), ("route", "bus")]),
+            },
+        );
+        store.rebuild_indices();
+        store
+    }
+
+    fn ids(store: &OsmStore, query: &str) -> Vec<(MemberType, i64)> {
+        let outputs = run(store, query).unwrap();
+        outputs.iter().flat_map(|o| o.elements.iter()).collect()
+    }
+
+    #[test]
+    fn test_filters_and_recursion() {
+        use MemberType::{Node, Relation, Way};
+        let store = store();
+
+        assert_eq!(
+            ids(
+                &store,
+                "[out:json][timeout:25];node[amenity](around:200,0,0);out;"
+            ),
+            vec![(Node, 1)]
+        );
+        assert_eq!(
+            ids(&store, "node[amenity~\"^(BAR|pub)$\",i];"),
+            vec![(Node, 2)]
+        );
+        assert_eq!(
+            ids(&store, "node[amenity][name!=\"Café Central\"];out ids;"),
+            vec![(Node, 2)]
+        );
+        assert_eq!(
+            ids(
+                &store,
+                "way(-0.0005,-0.0005,0.0015,0.0005)[\"name:en\"=\"Main St\"];"
+            ),
+            vec![(Way, 10)]
+        );
+        // 包围盒相交但线段不经过的范围
+        assert!(ids(&store, "way(0.0015,0.0015,0.002,0.002);").is_empty());
+
+        assert_eq!(
+            ids(
+                &store,
+                "way[highway]->.roads; (.roads; .roads >;); out skel;"
+            ),
+            vec![(Node, 3), (Node, 4), (Way, 10)]
+        );
+        assert_eq!(ids(&store, "node(3); <; out;"), vec![(Way, 10)]);
+        assert_eq!(
+            ids(&store, "node(3); <<;"),
+            vec![(Way, 10), (Relation, 100)]
+        );
+        assert_eq!(
+            ids(&store, "rel[type=route]; >>; out count;"),
+            vec![(Node, 1), (Node, 3), (Node, 4), (Way, 10)]
+        );
+        assert_eq!(
+            ids(
+                &store,
+                "[bbox:-0.01,-0.01,0.01,0.005]; nwr[amenity]; out 1;"
+            ),
+            vec![(Node, 1)]
+        );
+        assert_eq!(
+            ids(&store, "way[highway]; node(around:200)[amenity];"),
+            vec![(Node, 1)]
+        );
+    }
+
+    #[test]
+    fn test_unions_and_named_sets() {
+        use MemberType::{Node, Relation, Way};
+        let store = store();
+
+        assert_eq!(
+            ids(
+                &store,
+                "node[amenity]->.a; way[highway]->.b; (.a; .b;)->.c; .c out ids;"
+            ),
+            vec![(Node, 1), (Node, 2), (Way, 10)]
+        );
+        // 并集默认写入 `_`，命名集合不影响 `_`
+        assert_eq!(
+            ids(&store, "(node(1); way(10);); rel(100)->.r;"),
+            vec![(Node, 1), (Way, 10)]
+        );
+        assert_eq!(
+            ids(&store, "node[amenity]->.a; node.a[amenity=bar];"),
+            vec![(Node, 2)]
+        );
+        assert_eq!(
+            ids(&store, "way(10)->.w; .w <->.up; .up out;"),
+            vec![(Relation, 100)]
+        );
+        // 后写入的同名集合覆盖之前的内容
+        assert_eq!(
+            ids(&store, "node(1)->.a; node(2)->.a; .a out;"),
+            vec![(Node, 2)]
+        );
+        assert!(ids(&store, ".missing out;").is_empty());
+    }
+
+    #[test]
+    fn test_out_modes() {
+        let store = store();
+        let outputs = run(
+            &store,
+            "node(1); out ids; out skel; out meta; out tags; out geom; out center; out count; out qt 0;",
+        )
+        .unwrap();
+        let modes: Vec<OutMode> = outputs.iter().map(|o| o.mode).collect();
+        assert_eq!(
+            modes,
+            vec![
+                OutMode::Ids,
+                OutMode::Skel,
+                OutMode::Body,
+                OutMode::Tags,
+                OutMode::Geom,
+                OutMode::Center,
+                OutMode::Count,
+                OutMode::Body,
+            ]
+        );
+        assert!(outputs[..7].iter().all(|o| o.elements.count() == 1));
+        assert_eq!(outputs[7].elements.count(), 0);
+
+        // 没有 out 语句时以 body 输出 `_`
+        let outputs = run(&store, "nwr[amenity];").unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].mode, OutMode::Body);
+        assert_eq!(outputs[0].elements.count(), 2);
+        assert_eq!(
+            ids(&store, "nwr[amenity]; out tags 1;"),
+            vec![(MemberType::Node, 1)]
+        );
+    }
+
+    #[test]
+    fn test_recursion_with_missing_members() {
+        use MemberType::{Node, Relation, Way};
+        let store = store();
+        // Way 11 的节点 97、Relation 101/102 的成员 Way 99 与节点 98 均未加载
+        store.insert_way(OsmWay {
+            id: 11,
+            node_refs: vec![4, 97],
+            tags: vec![],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        });
+        let member = |member_type, ref_id| RelationMember {
+            member_type,
+            ref_id,
+            role: String::new(),
+        };
+        store.relations.insert(
+            101,
+            OsmRelation {
+                id: 101,
+                members: vec![member(Relation, 100), member(Way, 99), member(Node, 98)],
+                tags: tags(&[("type", "site")]),
+            },
+        );
+        store.relations.insert(
+            102,
+            OsmRelation {
+                id: 102,
+                members: vec![member(Way, 99)],
+                tags: vec![],
+            },
+        );
+        store.rebuild_indices();
+
+        assert_eq!(ids(&store, "rel(101); >;"), vec![(Relation, 100)]);
+        assert_eq!(
+            ids(&store, "rel(101); >>;"),
+            vec![(Node, 1), (Node, 3), (Node, 4), (Way, 10), (Relation, 100)]
+        );
+        assert_eq!(ids(&store, "way(11); >;"), vec![(Node, 4)]);
+        assert!(ids(&store, "rel(102); >;").is_empty());
+
+        assert_eq!(ids(&store, "node(4); <;"), vec![(Way, 10), (Way, 11)]);
+        assert_eq!(
+            ids(&store, "node(4); <<;"),
+            vec![(Way, 10), (Way, 11), (Relation, 100), (Relation, 101)]
+        );
+        assert_eq!(ids(&store, "rel(100); <;"), vec![(Relation, 101)]);
+        assert!(ids(&store, "node(2); <;").is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let store = store();
+        for query in [
+            "node[amenity=cafe",
+            "node[amenity~\"(\"];",
+            "node(3,2,1,4.5); out;",
+            "way out;",
+            "node; out fancy;",
+            "\"unterminated",
+            // 多个 ID 需用 id:
+            "node(1,2,-5);",
+        ] {
+            assert!(run(&store, query).is_err(), "{}", query);
+        }
+        assert_eq!(ids(&store, "node(2);"), vec![(MemberType::Node, 2)]);
+        assert_eq!(
+            ids(&store, "node(id:1,2,-5);"),
+            vec![(MemberType::Node, 1), (MemberType::Node, 2)]
+        );
+        // 不带小数点的四个数也是范围
+        assert!(!ids(&store, "node(-90,-180,90,180);").is_empty());
+    }
+}
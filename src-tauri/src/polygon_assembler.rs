@@ -11,15 +11,17 @@
 //! 2. 通过端点匹配将片段拼接成闭合环
 //! 3. 返回组装好的 Polygon 结构
 
-use crate::osm_store::OsmStore;
+use crate::osm_store::{OsmRelation, OsmStore};
 use crate::projection::lonlat_to_mercator;
 use std::collections::HashMap;
 
 /// 组装好的多边形
 #[derive(Debug, Clone)]
 pub struct AssembledPolygon {
-    /// Way ID（用于选中高亮）
+    /// Way ID（用于选中高亮），Relation 面为 Relation ID
     pub way_id: i64,
+    /// 是否由 Relation 组装（此时 `way_id` 为 Relation ID）
+    pub from_relation: bool,
     /// 渲染特征
     pub render_feature: u16,
     /// 图层值
@@ -60,29 +62,31 @@ pub fn assemble_from_closed_way(
 
     Some(AssembledPolygon {
         way_id,
+        from_relation: false,
         render_feature: way.render_feature,
         layer: way.layer,
         rings: vec![coords],
     })
 }
 
-/// 从 Multipolygon Relation 组装多边形
+/// 从 Multipolygon / Boundary Relation 组装多边形
 ///
 /// 这是核心算法：将散乱的 Way 片段拼接成闭合环
 pub fn assemble_from_relation(
     store: &OsmStore,
     relation_id: i64,
 ) -> Option<AssembledPolygon> {
+    let relation = store.relations.get(&relation_id)?;
+    assemble_relation(store, &relation)
+}
+
+/// 由 Relation 组装多边形（Relation 可以尚未加入存储）
+pub fn assemble_relation(store: &OsmStore, relation: &OsmRelation) -> Option<AssembledPolygon> {
     use crate::osm_store::MemberType;
     use crate::render_feature::parse_tags;
 
-    let relation = store.relations.get(&relation_id)?;
-
-    // 检查是否是 multipolygon 类型
-    let is_multipolygon = relation
-        .tags
-        .iter()
-        .any(|(k, v)| k == "type" && v == "multipolygon");
+    // 检查是否是 multipolygon 或 boundary 类型（两者的 outer/inner 结构相同）
+    let is_multipolygon = matches!(relation.tag("type"), Some("multipolygon" | "boundary"));
 
     if !is_multipolygon {
         return None;
@@ -120,7 +124,8 @@ pub fn assemble_from_relation(
     rings.extend(inner_rings);
 
     Some(AssembledPolygon {
-        way_id: relation_id, // 对于 Relation，使用 relation_id 作为标识
+        way_id: relation.id, // 对于 Relation，使用 relation_id 作为标识
+        from_relation: true,
         render_feature: parsed.feature,
         layer: parsed.layer,
         rings,
@@ -131,6 +136,33 @@ pub fn assemble_from_relation(
 ///
 /// 将多条可能首尾相连的 Way 拼接成闭合环
 fn stitch_ways_to_rings(store: &OsmStore, way_ids: &[i64]) -> Vec<Vec<(f64, f64)>> {
+    stitch_ways_to_node_rings(store, way_ids)
+        .into_iter()
+        .filter_map(|ring| {
+            // 转换为墨卡托坐标
+            let coords: Vec<(f64, f64)> = ring
+                .iter()
+                .filter_map(|node_id| {
+                    store
+                        .nodes
+                        .get(node_id)
+                        .map(|n| lonlat_to_mercator(n.lon, n.lat))
+                })
+                .collect();
+
+            if coords.len() >= 4 {
+                Some(coords)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// 将多条可能首尾相连的 Way 拼接成闭合的节点 ID 环
+///
+/// 每个环首尾节点相同；无法闭合的片段会被丢弃
+pub fn stitch_ways_to_node_rings(store: &OsmStore, way_ids: &[i64]) -> Vec<Vec<i64>> {
     if way_ids.is_empty() {
         return Vec::new();
     }
@@ -160,7 +192,7 @@ fn stitch_ways_to_rings(store: &OsmStore, way_ids: &[i64]) -> Vec<Vec<(f64, f64)
 
     // 标记已使用的片段
     let mut used: Vec<bool> = vec![false; segments.len()];
-    let mut rings: Vec<Vec<(f64, f64)>> = Vec::new();
+    let mut rings: Vec<Vec<i64>> = Vec::new();
 
     // 贪婪拼接
     for start_idx in 0..segments.len() {
@@ -220,20 +252,7 @@ fn stitch_ways_to_rings(store: &OsmStore, way_ids: &[i64]) -> Vec<Vec<(f64, f64)
 
         // 检查是否成功闭合
         if current_ring.len() >= 4 && current_ring.first() == current_ring.last() {
-            // 转换为墨卡托坐标
-            let coords: Vec<(f64, f64)> = current_ring
-                .iter()
-                .filter_map(|node_id| {
-                    store
-                        .nodes
-                        .get(node_id)
-                        .map(|n| lonlat_to_mercator(n.lon, n.lat))
-                })
-                .collect();
-
-            if coords.len() >= 4 {
-                rings.push(coords);
-            }
+            rings.push(current_ring);
         }
     }
 
@@ -280,6 +299,19 @@ pub fn is_area_way(tags: &[(String, String)], node_refs: &[i64]) -> bool {
     false
 }
 
+/// 判断点是否位于环内（射线法，环为首尾相同的坐标序列）
+pub fn point_in_ring(point: (f64, f64), ring: &[(f64, f64)]) -> bool {
+    let (px, py) = point;
+    let mut inside = false;
+    for pair in ring.windows(2) {
+        let ((x1, y1), (x2, y2)) = (pair[0], pair[1]);
+        if (y1 > py) != (y2 > py) && px < (x2 - x1) * (py - y1) / (y2 - y1) + x1 {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -314,4 +346,18 @@ mod tests {
         let refs = vec![1, 2, 3, 4, 1];
         assert!(is_area_way(&tags, &refs)); // 明确标记为 area
     }
+
+    #[test]
+    fn test_point_in_ring() {
+        let ring = vec![
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ];
+        assert!(point_in_ring((5.0, 5.0), &ring));
+        assert!(!point_in_ring((15.0, 5.0), &ring));
+        assert!(!point_in_ring((5.0, -1.0), &ring));
+    }
 }
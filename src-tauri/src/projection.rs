@@ -48,6 +48,15 @@ pub fn mercator_to_lonlat(x: f64, y: f64) -> (f64, f64) {
     (lon, lat)
 }
 
+/// 墨卡托投影在指定纬度的比例因子
+///
+/// 墨卡托坐标中的 1 米对应地面上 1 / scale 米，
+/// 因此地面距离 d 米在墨卡托空间中约为 d × scale。
+#[inline]
+pub fn mercator_scale_factor(lat: f64) -> f64 {
+    1.0 / lat.clamp(-85.051129, 85.051129).to_radians().cos()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,6 +90,10 @@ pub mod flags {
     pub const CONSTRUCTION: RenderFeature = 0x0800;
     /// 单行道 (oneway=yes)
     pub const ONEWAY: RenderFeature = 0x1000;
+    /// 由 Relation 组装的面（仅由二进制协议在传输时设置，用于区分同号的 Way 与 Relation）
+    pub const RELATION: RenderFeature = 0x4000;
+    /// 已编辑（不存储在 Way 上，仅由二进制协议在传输时设置，用于高亮）
+    pub const CHANGED: RenderFeature = 0x8000;
 
     /// 检查是否设置了指定 flag
     #[inline]
@@ -0,0 +1,278 @@
+//! 转向限制 (type=restriction)
+//!
+//! - 校验成员角色、from/to 与 via 的连接以及 restriction 取值
+//! - 查找路口节点处的现有转向限制
+//!
+//! from/to 必须以 via 节点（或 via Way 的端点）为端点；
+//! 经过 via 的 Way 需先在 via 处拆分，见 `split` 模块。
+
+use crate::osm_store::{MemberType, OsmRelation, OsmStore};
+use crate::split::via_endpoints;
+
+/// 已知的 restriction 取值
+pub const KNOWN_RESTRICTIONS: &[&str] = &[
+    "no_right_turn",
+    "no_left_turn",
+    "no_u_turn",
+    "no_straight_on",
+    "no_entry",
+    "no_exit",
+    "only_right_turn",
+    "only_left_turn",
+    "only_straight_on",
+    "only_u_turn",
+];
+
+/// 转向限制存在的问题
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum RestrictionIssue {
+    /// 缺少 restriction 标签
+    MissingRestriction,
+    /// 未知的 restriction 取值
+    UnknownRestriction { value: String },
+    /// 缺少 from/via/to 成员
+    MissingMember { role: String },
+    /// 成员过多（from/to 各一个，no_entry 可有多个 from、no_exit 可有多个 to；
+    /// via 为一个节点或一组 Way）
+    TooManyMembers { role: String },
+    /// 不支持的成员角色
+    UnknownRole { role: String },
+    /// 成员类型错误（from/to 必须为 Way）
+    WrongMemberType { role: String, ref_id: i64 },
+    /// 成员不在当前数据中，无法检查连接
+    MemberNotLoaded { role: String, ref_id: i64 },
+    /// from/to 的端点与 via 不相连
+    NotConnected { role: String, ref_id: i64 },
+}
+
+/// restriction 取值（`restriction` 或带条件后缀的 `restriction:*`）
+pub fn restriction_value(relation: &OsmRelation) -> Option<&str> {
+    relation.tag("restriction").or_else(|| {
+        relation
+            .tags
+            .iter()
+            .find(|(k, _)| k.starts_with("restriction:"))
+            .map(|(_, v)| v.as_str())
+    })
+}
+
+/// 校验转向限制，返回全部问题（为空表示有效）
+pub fn validate(store: &OsmStore, relation: &OsmRelation) -> Vec<RestrictionIssue> {
+    let mut issues = Vec::new();
+    let value = restriction_value(relation);
+    match value {
+        None => issues.push(RestrictionIssue::MissingRestriction),
+        Some(value) if !KNOWN_RESTRICTIONS.contains(&value) => {
+            issues.push(RestrictionIssue::UnknownRestriction {
+                value: value.to_string(),
+            })
+        }
+        Some(_) => {}
+    }
+
+    for member in &relation.members {
+        if !matches!(
+            member.role.as_str(),
+            "from" | "via" | "to" | "location_hint"
+        ) {
+            issues.push(RestrictionIssue::UnknownRole {
+                role: member.role.clone(),
+            });
+        }
+    }
+
+    let with_role = |role: &'static str| relation.members.iter().filter(move |m| m.role == role);
+    let via_nodes = with_role("via")
+        .filter(|m| m.member_type == MemberType::Node)
+        .count();
+    let via_ways = with_role("via")
+        .filter(|m| m.member_type == MemberType::Way)
+        .count();
+    if via_nodes + via_ways == 0 {
+        issues.push(RestrictionIssue::MissingMember {
+            role: "via".to_string(),
+        });
+    } else if via_nodes > 1 || (via_nodes == 1 && via_ways > 0) {
+        issues.push(RestrictionIssue::TooManyMembers {
+            role: "via".to_string(),
+        });
+    }
+    for via in with_role("via").filter(|m| m.member_type == MemberType::Way) {
+        if !store.ways.contains_key(&via.ref_id) {
+            issues.push(RestrictionIssue::MemberNotLoaded {
+                role: via.role.clone(),
+                ref_id: via.ref_id,
+            });
+        }
+    }
+
+    let endpoints = via_endpoints(store, relation);
+    for (role, multiple_allowed) in [
+        ("from", value == Some("no_entry")),
+        ("to", value == Some("no_exit")),
+    ] {
+        match with_role(role).count() {
+            0 => issues.push(RestrictionIssue::MissingMember {
+                role: role.to_string(),
+            }),
+            1 => {}
+            _ if multiple_allowed => {}
+            _ => issues.push(RestrictionIssue::TooManyMembers {
+                role: role.to_string(),
+            }),
+        }
+
+        for member in with_role(role) {
+            let (role, ref_id) = (role.to_string(), member.ref_id);
+            if member.member_type != MemberType::Way {
+                issues.push(RestrictionIssue::WrongMemberType { role, ref_id });
+                continue;
+            }
+            let connected = match store.ways.get(&ref_id) {
+                Some(way) => [way.node_refs.first(), way.node_refs.last()]
+                    .into_iter()
+                    .flatten()
+                    .any(|id| endpoints.contains(id)),
+                None => {
+                    issues.push(RestrictionIssue::MemberNotLoaded { role, ref_id });
+                    continue;
+                }
+            };
+            if !connected && !endpoints.is_empty() {
+                issues.push(RestrictionIssue::NotConnected { role, ref_id });
+            }
+        }
+    }
+
+    issues
+}
+
+/// 以指定节点为 via（或 via Way 端点）的转向限制 ID（升序）
+pub fn restrictions_at_node(store: &OsmStore, node_id: i64) -> Vec<i64> {
+    let mut ids: Vec<i64> = store
+        .relations
+        .iter()
+        .filter(|r| r.tag("type") == Some("restriction"))
+        .filter(|r| via_endpoints(store, r).contains(&node_id))
+        .map(|r| r.id)
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryManager;
+    use crate::osm_store::{OsmNode, OsmWay, RelationMember};
+    use crate::split::plan_split;
+
+    fn member(member_type: MemberType, ref_id: i64, role: &str) -> RelationMember {
+        RelationMember {
+            member_type,
+            ref_id,
+            role: role.to_string(),
+        }
+    }
+
+    /// 十字路口：Way 10 (1 → 2 → 3) 与 Way 20 (4 → 2 → 5) 交于节点 2
+    fn junction() -> OsmStore {
+        let store = OsmStore::new();
+        for (id, lon, lat) in [
+            (1, -0.001, 0.0),
+            (2, 0.0, 0.0),
+            (3, 0.001, 0.0),
+            (4, 0.0, -0.001),
+            (5, 0.0, 0.001),
+        ] {
+            store.add_node_with_index(OsmNode {
+                id,
+                lon,
+                lat,
+                tags: vec![],
+            });
+        }
+        for (id, node_refs) in [(10, vec![1, 2, 3]), (20, vec![4, 2, 5])] {
+            store.add_way_with_index(OsmWay {
+                id,
+                node_refs,
+                tags: vec![("highway".to_string(), "residential".to_string())],
+                render_feature: 0,
+                layer: 0,
+                is_area: false,
+            });
+        }
+        store.relations.insert(
+            100,
+            OsmRelation {
+                id: 100,
+                members: vec![
+                    member(MemberType::Way, 10, "from"),
+                    member(MemberType::Node, 2, "via"),
+                    member(MemberType::Way, 20, "to"),
+                ],
+                tags: vec![
+                    ("type".to_string(), "restriction".to_string()),
+                    ("restriction".to_string(), "no_left_turn".to_string()),
+                ],
+            },
+        );
+        store
+    }
+
+    #[test]
+    fn test_split_at_via_makes_restriction_valid() {
+        let store = junction();
+        let relation = store.relations.get(&100).unwrap().clone();
+        assert_eq!(validate(&store, &relation).len(), 2);
+        assert_eq!(restrictions_at_node(&store, 2), vec![100]);
+
+        let history = HistoryManager::new();
+        for (way_id, new_way_id) in [(10, -1), (20, -2)] {
+            let command = plan_split(&store, way_id, 2, new_way_id).unwrap();
+            assert!(history.execute(Box::new(command), &store).success);
+        }
+        assert_eq!(store.ways.get(&10).unwrap().node_refs, vec![1, 2]);
+        assert_eq!(store.ways.get(&-1).unwrap().node_refs, vec![2, 3]);
+
+        let relation = store.relations.get(&100).unwrap().clone();
+        assert!(validate(&store, &relation).is_empty());
+        // from/to 仍然只有一个成员
+        assert_eq!(relation.members.len(), 3);
+
+        assert!(history.undo(&store).success);
+        assert!(history.undo(&store).success);
+        assert!(!store.ways.contains_key(&-1));
+        assert_eq!(store.ways.get(&10).unwrap().node_refs, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_validate_reports_issues() {
+        let store = junction();
+        let relation = OsmRelation {
+            id: 101,
+            members: vec![
+                member(MemberType::Way, 10, "from"),
+                member(MemberType::Node, 1, "via"),
+                member(MemberType::Node, 2, "via"),
+                member(MemberType::Node, 3, "stop"),
+            ],
+            tags: vec![("restriction".to_string(), "no_parking".to_string())],
+        };
+
+        let issues = validate(&store, &relation);
+        assert!(issues.contains(&RestrictionIssue::UnknownRestriction {
+            value: "no_parking".to_string()
+        }));
+        assert!(issues.contains(&RestrictionIssue::UnknownRole {
+            role: "stop".to_string()
+        }));
+        assert!(issues.contains(&RestrictionIssue::TooManyMembers {
+            role: "via".to_string()
+        }));
+        assert!(issues.contains(&RestrictionIssue::MissingMember {
+            role: "to".to_string()
+        }));
+    }
+}
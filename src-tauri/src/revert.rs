@@ -0,0 +1,352 @@
+//! 恢复要素到基准版本 (Revert)
+//!
+//! 只撤销单个要素的编辑，不影响之后的其他编辑：
+//! - Node: 恢复坐标和标签；被删除的节点同时插回它原来所在的 Way
+//! - Way: 恢复标签和节点序列，并把被移动的节点恢复到原坐标；
+//!   节点序列引用了之后被删除的节点时，按基准版本重新创建这些节点；
+//!   从 Way 中移除后成为孤立的新建节点（无标签、不被其他 Way 或 Relation 引用）一并删除
+//! - Relation: 恢复标签和成员列表
+//! - 恢复要素在父 Relation 中被移除的成员身份
+//!
+//! 本次会话中新建的要素没有基准版本，应直接删除。
+
+use crate::changes::{ChangeStatus, ChangeTracker};
+use crate::history::{EntitySwap, RevertEntityCommand};
+use crate::osm_store::{MemberType, OsmNode, OsmRelation, OsmStore, OsmWay};
+use std::collections::BTreeMap;
+
+/// 各要素恢复后的目标状态（None 表示删除）
+#[derive(Default)]
+struct Targets {
+    nodes: BTreeMap<i64, Option<OsmNode>>,
+    ways: BTreeMap<i64, Option<OsmWay>>,
+    relations: BTreeMap<i64, Option<OsmRelation>>,
+}
+
+/// 计算恢复指定要素所需的快照替换
+pub fn plan_revert(
+    store: &OsmStore,
+    tracker: &ChangeTracker,
+    member_type: MemberType,
+    id: i64,
+) -> Result<RevertEntityCommand, String> {
+    match tracker.status(store, member_type, id) {
+        ChangeStatus::Unchanged => return Err("Feature has no changes".to_string()),
+        ChangeStatus::Created => {
+            return Err("Feature was created in this session; delete it instead".to_string())
+        }
+        ChangeStatus::Modified | ChangeStatus::Deleted => {}
+    }
+
+    let missing = || "Base version not found".to_string();
+    let mut targets = Targets::default();
+    match member_type {
+        MemberType::Node => {
+            let base = tracker.base_node(id).ok_or_else(missing)?;
+            targets.nodes.insert(id, Some(base));
+            restore_way_refs(store, tracker, id, &mut targets);
+        }
+        MemberType::Way => {
+            let base = tracker.base_way(id).ok_or_else(missing)?;
+            plan_way_nodes(store, tracker, &base, &mut targets);
+            targets.ways.insert(id, Some(base));
+        }
+        MemberType::Relation => {
+            let base = tracker.base_relation(id).ok_or_else(missing)?;
+            targets.relations.insert(id, Some(base));
+        }
+    }
+    restore_membership(store, tracker, member_type, id, &mut targets);
+
+    let command = RevertEntityCommand {
+        member_type,
+        id,
+        nodes: swaps(targets.nodes, |id| store.nodes.get(&id).map(|n| n.clone())),
+        ways: swaps(targets.ways, |id| store.ways.get(&id).map(|w| w.clone())),
+        relations: swaps(targets.relations, |id| {
+            store.relations.get(&id).map(|r| r.clone())
+        }),
+    };
+    if command.nodes.is_empty() && command.ways.is_empty() && command.relations.is_empty() {
+        return Err("Feature has no changes".to_string());
+    }
+    Ok(command)
+}
+
+/// Way 节点的级联处理：重新创建被删除的节点、恢复被移动的节点、删除孤立的新建节点
+fn plan_way_nodes(store: &OsmStore, tracker: &ChangeTracker, base: &OsmWay, targets: &mut Targets) {
+    for &node_id in &base.node_refs {
+        if targets.nodes.contains_key(&node_id) {
+            continue;
+        }
+        let base_node = match tracker.base_node(node_id) {
+            Some(node) => node,
+            None => continue,
+        };
+        match store.nodes.get(&node_id) {
+            // 之后被删除的节点：按基准版本重新创建
+            None => {
+                targets.nodes.insert(node_id, Some(base_node));
+            }
+            // 被移动的节点：恢复坐标（节点标签属于节点自身的编辑，保持不变）
+            Some(current) if current.lon != base_node.lon || current.lat != base_node.lat => {
+                let mut node = current.clone();
+                node.lon = base_node.lon;
+                node.lat = base_node.lat;
+                targets.nodes.insert(node_id, Some(node));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let current_refs = match store.ways.get(&base.id) {
+        Some(way) => way.node_refs.clone(),
+        None => return,
+    };
+    for &node_id in &current_refs {
+        if base.node_refs.contains(&node_id) || targets.nodes.contains_key(&node_id) {
+            continue;
+        }
+        let occurrences = current_refs.iter().filter(|&&n| n == node_id).count() as u16;
+        let orphan = store.node_ref_count.get(&node_id).map_or(0, |c| *c) <= occurrences
+            && store.nodes.get(&node_id).is_some_and(|n| n.tags.is_empty())
+            && store
+                .find_relations_referencing(MemberType::Node, node_id)
+                .is_empty();
+        if orphan && tracker.status(store, MemberType::Node, node_id) == ChangeStatus::Created {
+            targets.nodes.insert(node_id, None);
+        }
+    }
+}
+
+/// 将节点插回它在基准版本中所属、但当前已不包含它的 Way
+///
+/// 插入位置以基准序列中的前一个（或后一个）相邻节点为锚点；
+/// 已被删除的 Way 需单独恢复
+fn restore_way_refs(
+    store: &OsmStore,
+    tracker: &ChangeTracker,
+    node_id: i64,
+    targets: &mut Targets,
+) {
+    for base_way in tracker.base_ways_with_node(node_id) {
+        let mut current = match store.ways.get(&base_way.id) {
+            Some(way) => way.clone(),
+            None => continue,
+        };
+        if current.node_refs.contains(&node_id) {
+            continue;
+        }
+
+        let base_refs = &base_way.node_refs;
+        for (index, _) in base_refs.iter().enumerate().filter(|(_, &n)| n == node_id) {
+            let position = |neighbor: Option<&i64>| {
+                neighbor.and_then(|n| current.node_refs.iter().position(|r| r == n))
+            };
+            let previous = index.checked_sub(1).and_then(|i| base_refs.get(i));
+            let insert_at = position(previous)
+                .map(|p| p + 1)
+                .or_else(|| position(base_refs.get(index + 1)))
+                .unwrap_or(index)
+                .min(current.node_refs.len());
+            current.node_refs.insert(insert_at, node_id);
+        }
+        targets.ways.insert(base_way.id, Some(current));
+    }
+}
+
+/// 恢复要素在父 Relation 中被移除的成员身份（按基准位置插回）
+fn restore_membership(
+    store: &OsmStore,
+    tracker: &ChangeTracker,
+    member_type: MemberType,
+    id: i64,
+    targets: &mut Targets,
+) {
+    let is_member =
+        |m: &crate::osm_store::RelationMember| m.member_type == member_type && m.ref_id == id;
+
+    for base_parent in tracker.base_parents(member_type, id) {
+        let mut current = match targets.relations.get(&base_parent.id) {
+            Some(Some(relation)) => relation.clone(),
+            Some(None) => continue,
+            None => match store.relations.get(&base_parent.id) {
+                Some(relation) => relation.clone(),
+                // 父 Relation 已被删除，需单独恢复
+                None => continue,
+            },
+        };
+        if current.members.iter().any(is_member) {
+            continue;
+        }
+
+        for (index, member) in base_parent.members.iter().enumerate() {
+            if is_member(member) {
+                let index = index.min(current.members.len());
+                current.members.insert(index, member.clone());
+            }
+        }
+        targets.relations.insert(base_parent.id, Some(current));
+    }
+}
+
+fn swaps<T: PartialEq>(
+    targets: BTreeMap<i64, Option<T>>,
+    current: impl Fn(i64) -> Option<T>,
+) -> Vec<EntitySwap<T>> {
+    targets
+        .into_iter()
+        .filter_map(|(id, after)| {
+            let before = current(id);
+            (before != after).then_some(EntitySwap { id, before, after })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{
+        AddNodeCommand, AttachNodeCommand, DeleteNodeCommand, DeleteWayCommand, HistoryManager,
+        MoveNodeCommand, RelationCascade,
+    };
+    use crate::osm_store::RelationMember;
+
+    fn node(id: i64, lon: f64) -> OsmNode {
+        OsmNode {
+            id,
+            lon,
+            lat: 0.0,
+            tags: vec![],
+        }
+    }
+
+    fn way(id: i64, node_refs: Vec<i64>) -> OsmWay {
+        OsmWay {
+            id,
+            node_refs,
+            tags: vec![("highway".to_string(), "service".to_string())],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        }
+    }
+
+    fn store_with_way() -> OsmStore {
+        let store = OsmStore::new();
+        for (id, lon) in [(1, 0.0), (2, 0.001), (3, 0.002)] {
+            store.add_node_with_index(node(id, lon));
+        }
+        store.add_way_with_index(way(10, vec![1, 2, 3]));
+        store
+    }
+
+    #[test]
+    fn test_revert_way_restores_deleted_nodes() {
+        let store = store_with_way();
+        let history = HistoryManager::new();
+
+        let command = DeleteNodeCommand {
+            node: node(2, 0.001),
+            way_references: vec![(10, vec![1])],
+            cascaded_ways: Vec::new(),
+            relation_cascade: RelationCascade::default(),
+        };
+        assert!(history.execute(Box::new(command), &store).success);
+        let added = node(store.generate_local_id(), 0.0015);
+        history.execute(Box::new(AddNodeCommand { node: added }), &store);
+        let command = AttachNodeCommand {
+            node_id: -1,
+            way_id: 10,
+            index: 1,
+            old_lon: 0.0015,
+            old_lat: 0.0,
+            new_lon: 0.0015,
+            new_lat: 0.0,
+        };
+        assert!(history.execute(Box::new(command), &store).success);
+        let command = MoveNodeCommand {
+            node_id: 1,
+            old_lon: 0.0,
+            old_lat: 0.0,
+            new_lon: -0.001,
+            new_lat: 0.0,
+        };
+        history.execute(Box::new(command), &store);
+
+        let command = plan_revert(&store, history.changes(), MemberType::Way, 10).unwrap();
+        assert!(history.execute(Box::new(command), &store).success);
+        assert_eq!(store.ways.get(&10).unwrap().node_refs, vec![1, 2, 3]);
+        assert!(store.nodes.contains_key(&2));
+        assert!(!store.nodes.contains_key(&-1));
+        assert_eq!(store.nodes.get(&1).unwrap().lon, 0.0);
+
+        assert!(history.undo(&store).success);
+        assert_eq!(store.ways.get(&10).unwrap().node_refs, vec![1, -1, 3]);
+        assert!(!store.nodes.contains_key(&2));
+        assert!(store.nodes.contains_key(&-1));
+    }
+
+    #[test]
+    fn test_revert_deleted_node_restores_way_refs() {
+        let store = store_with_way();
+        // 闭合 Way：节点 1 同时位于首尾
+        store.add_way_with_index(way(11, vec![1, 3, 2, 1]));
+        let history = HistoryManager::new();
+
+        let command = DeleteNodeCommand {
+            node: node(1, 0.0),
+            way_references: vec![(10, vec![0]), (11, vec![0, 3])],
+            cascaded_ways: Vec::new(),
+            relation_cascade: RelationCascade::default(),
+        };
+        assert!(history.execute(Box::new(command), &store).success);
+        assert_eq!(store.ways.get(&10).unwrap().node_refs, vec![2, 3]);
+        assert_eq!(store.ways.get(&11).unwrap().node_refs, vec![3, 2]);
+
+        let command = plan_revert(&store, history.changes(), MemberType::Node, 1).unwrap();
+        assert!(history.execute(Box::new(command), &store).success);
+        assert!(store.nodes.contains_key(&1));
+        assert_eq!(store.ways.get(&10).unwrap().node_refs, vec![1, 2, 3]);
+        assert_eq!(store.ways.get(&11).unwrap().node_refs, vec![1, 3, 2, 1]);
+        assert_eq!(*store.node_ref_count.get(&1).unwrap(), 3);
+        assert!(history.changes().changes(&store).is_empty());
+    }
+
+    #[test]
+    fn test_revert_deleted_way_restores_membership() {
+        let store = store_with_way();
+        store.add_way_with_index(way(11, vec![3, 1]));
+        let member = |ref_id| RelationMember {
+            member_type: MemberType::Way,
+            ref_id,
+            role: String::new(),
+        };
+        store.relations.insert(
+            100,
+            OsmRelation {
+                id: 100,
+                members: vec![member(10), member(11)],
+                tags: vec![("type".to_string(), "route".to_string())],
+            },
+        );
+        let history = HistoryManager::new();
+
+        let command = DeleteWayCommand {
+            way: store.ways.get(&10).unwrap().clone(),
+            relation_cascade: RelationCascade::plan(&store, &[(MemberType::Way, 10)]),
+        };
+        assert!(history.execute(Box::new(command), &store).success);
+        assert_eq!(store.relations.get(&100).unwrap().members.len(), 1);
+
+        // Way 11 未被编辑，没有可恢复的变更
+        assert!(plan_revert(&store, history.changes(), MemberType::Way, 11).is_err());
+
+        let command = plan_revert(&store, history.changes(), MemberType::Way, 10).unwrap();
+        assert!(history.execute(Box::new(command), &store).success);
+        assert!(store.ways.contains_key(&10));
+        let members = store.relations.get(&100).unwrap().members.clone();
+        assert_eq!(members, vec![member(10), member(11)]);
+        assert!(history.changes().changes(&store).is_empty());
+    }
+}
@@ -0,0 +1,444 @@
+//! 要素搜索
+//!
+//! 查询语法：
+//! - `key=value`：标签精确匹配
+//! - `key=*`：带有该标签
+//! - `key~regex`：标签值匹配正则表达式
+//! - 其他文本：在 `name`、`name:*`、`ref`、`addr:*` 的值中不区分大小写地查找
+//!
+//! 候选要素全部来自标签倒排索引，不遍历要素本身；结果按与视口中心的距离排序。
+//! 位置取路径索引中的包围盒中心，只在有界堆中保留最近的若干个结果。
+
+use crate::osm_store::{DataBounds, MemberType, OsmStore};
+use crate::projection;
+use crate::tag_index::{FeatureRef, TagIndex};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+
+/// 搜索条件
+pub enum SearchQuery {
+    /// 自由文本（已转为小写）
+    Text(String),
+    /// `key=value`
+    Tag { key: String, value: String },
+    /// `key=*`
+    HasKey(String),
+    /// `key~regex`
+    TagRegex { key: String, regex: Regex },
+}
+
+impl SearchQuery {
+    /// 解析查询字符串
+    ///
+    /// 第一个 `=` 或 `~` 之前的部分不含空白时按标签条件解析，否则视为自由文本
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Err("Empty search query".to_string());
+        }
+
+        if let Some(pos) = input.find(['=', '~']) {
+            let key = input[..pos].trim();
+            let value = input[pos + 1..].trim();
+            if !key.is_empty() && !key.contains(char::is_whitespace) {
+                let key = key.to_string();
+                return match &input[pos..=pos] {
+                    "~" => Regex::new(value)
+                        .map(|regex| Self::TagRegex { key, regex })
+                        .map_err(|e| format!("Invalid regex: {}", e)),
+                    _ if value == "*" => Ok(Self::HasKey(key)),
+                    _ => Ok(Self::Tag {
+                        key,
+                        value: value.to_string(),
+                    }),
+                };
+            }
+        }
+        Ok(Self::Text(input.to_lowercase()))
+    }
+}
+
+/// 排序后的搜索结果
+pub struct RankedFeature {
+    pub member_type: MemberType,
+    pub id: i64,
+    /// 命中的标签
+    pub matched_tag: (String, String),
+    /// 代表位置（包围盒中心，经纬度）；成员均未加载的 Relation 为 None
+    pub location: Option<(f64, f64)>,
+    /// 与视口中心的地面距离（米）
+    pub distance: Option<f64>,
+}
+
+/// 搜索结果
+pub struct SearchResults {
+    /// 命中的要素总数
+    pub total: usize,
+    /// 距离最近的若干个要素，按距离升序
+    pub features: Vec<RankedFeature>,
+}
+
+/// 命中的标签（索引中的共享字符串）
+type MatchedTag = (Arc<str>, Arc<str>);
+
+/// 有界堆中的候选结果，按 (距离, 类型, ID) 排序
+struct Candidate {
+    feature: FeatureRef,
+    matched_tag: MatchedTag,
+    location: Option<(f64, f64)>,
+    distance: Option<f64>,
+}
+
+impl Candidate {
+    fn cmp_rank(&self, other: &Self) -> Ordering {
+        let distance = |c: &Candidate| c.distance.unwrap_or(f64::INFINITY);
+        distance(self)
+            .total_cmp(&distance(other))
+            .then_with(|| (self.feature.0 as u8).cmp(&(other.feature.0 as u8)))
+            .then_with(|| self.feature.1.cmp(&other.feature.1))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp_rank(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_rank(other)
+    }
+}
+
+/// 自由文本搜索的 key
+fn is_text_key(key: &str) -> bool {
+    key == "name" || key == "ref" || key.starts_with("name:") || key.starts_with("addr:")
+}
+
+/// 在标签索引中查找匹配的要素及其命中的标签
+fn find_matches(index: &TagIndex, query: &SearchQuery) -> HashMap<FeatureRef, MatchedTag> {
+    let mut matches = HashMap::new();
+    let mut add = |key: &Arc<str>, value: &Arc<str>, features: &HashSet<FeatureRef>| {
+        for feature in features {
+            matches
+                .entry(*feature)
+                .or_insert_with(|| (key.clone(), value.clone()));
+        }
+    };
+
+    match query {
+        SearchQuery::Tag { key, value } => {
+            if let Some((key, value, features)) = index.tag(key, value) {
+                add(key, value, features);
+            }
+        }
+        SearchQuery::HasKey(key) => {
+            let Some(key) = index.key(key) else {
+                return matches;
+            };
+            for (value, features) in index.values(key) {
+                add(key, value, features);
+            }
+        }
+        SearchQuery::TagRegex { key, regex } => {
+            let Some(key) = index.key(key) else {
+                return matches;
+            };
+            for (value, features) in index.values(key).filter(|(v, _)| regex.is_match(v)) {
+                add(key, value, features);
+            }
+        }
+        SearchQuery::Text(text) => {
+            // name 优先作为命中标签，其余 key 按字母顺序
+            let mut keys: Vec<&Arc<str>> = index.keys().filter(|key| is_text_key(key)).collect();
+            keys.sort_unstable_by_key(|&key| (&**key != "name", Arc::clone(key)));
+            for key in keys {
+                for (value, features) in index.values(key) {
+                    if value.to_lowercase().contains(text.as_str()) {
+                        add(key, value, features);
+                    }
+                }
+            }
+        }
+    }
+    matches
+}
+
+/// 命中的路径及命中关系的成员路径的包围盒（两个对角点）
+fn way_envelopes(
+    store: &OsmStore,
+    matches: &[(FeatureRef, MatchedTag)],
+) -> HashMap<i64, [(f64, f64); 2]> {
+    let mut wanted = HashSet::new();
+    for &((member_type, id), _) in matches {
+        match member_type {
+            MemberType::Way => {
+                wanted.insert(id);
+            }
+            MemberType::Relation => {
+                if let Some(relation) = store.relations.get(&id) {
+                    wanted.extend(
+                        relation
+                            .members
+                            .iter()
+                            .filter(|m| m.member_type == MemberType::Way)
+                            .map(|m| m.ref_id),
+                    );
+                }
+            }
+            MemberType::Node => {}
+        }
+    }
+
+    wanted
+        .into_iter()
+        .filter_map(|id| {
+            let way = store.ways.get(&id)?;
+            let entry = store.compute_way_bbox(&way)?;
+            let corners = [
+                (entry.min_lon, entry.min_lat),
+                (entry.max_lon, entry.max_lat),
+            ];
+            Some((id, corners))
+        })
+        .collect()
+}
+
+/// 要素的代表位置（包围盒中心）；成员均未加载的 Relation 为 None
+fn location(
+    store: &OsmStore,
+    (member_type, id): FeatureRef,
+    envelopes: &HashMap<i64, [(f64, f64); 2]>,
+) -> Option<(f64, f64)> {
+    let bounds = match member_type {
+        MemberType::Node => return store.nodes.get(&id).map(|n| (n.lon, n.lat)),
+        MemberType::Way => DataBounds::from_points(envelopes.get(&id)?.iter().copied()),
+        MemberType::Relation => {
+            let relation = store.relations.get(&id)?;
+            let points = relation.members.iter().flat_map(|m| match m.member_type {
+                MemberType::Node => store.nodes.get(&m.ref_id).map(|n| vec![(n.lon, n.lat)]),
+                MemberType::Way => envelopes.get(&m.ref_id).map(|corners| corners.to_vec()),
+                MemberType::Relation => None,
+            });
+            DataBounds::from_points(points.flatten())
+        }
+    };
+    bounds.map(|b| (b.center_lon, b.center_lat))
+}
+
+/// 搜索要素，返回命中总数及与中心点 (lon, lat) 距离最近的 `limit` 个要素
+/// （按距离升序，无位置的排在最后）
+pub fn search(
+    store: &OsmStore,
+    query: &SearchQuery,
+    center: (f64, f64),
+    limit: usize,
+) -> SearchResults {
+    // 先释放索引读锁再读取要素
+    let matches: Vec<(FeatureRef, MatchedTag)> = find_matches(&store.tag_index(), query)
+        .into_iter()
+        .filter(|&((member_type, id), _)| store.contains_feature(member_type, id))
+        .collect();
+    let total = matches.len();
+    if limit == 0 {
+        return SearchResults {
+            total,
+            features: Vec::new(),
+        };
+    }
+    let envelopes = way_envelopes(store, &matches);
+
+    let (center_x, center_y) = projection::lonlat_to_mercator(center.0, center.1);
+    let scale = projection::mercator_scale_factor(center.1);
+    // 大顶堆：堆顶为当前保留结果中最远的一个
+    let mut heap = BinaryHeap::with_capacity(limit.min(total) + 1);
+    for (feature, matched_tag) in matches {
+        let location = location(store, feature, &envelopes);
+        let distance = location.map(|(lon, lat)| {
+            let (x, y) = projection::lonlat_to_mercator(lon, lat);
+            (x - center_x).hypot(y - center_y) / scale
+        });
+        heap.push(Candidate {
+            feature,
+            matched_tag,
+            location,
+            distance,
+        });
+        if heap.len() > limit {
+            heap.pop();
+        }
+    }
+
+    let features = heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|candidate| RankedFeature {
+            member_type: candidate.feature.0,
+            id: candidate.feature.1,
+            matched_tag: (
+                candidate.matched_tag.0.to_string(),
+                candidate.matched_tag.1.to_string(),
+            ),
+            location: candidate.location,
+            distance: candidate.distance,
+        })
+        .collect();
+    SearchResults { total, features }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_store::{OsmNode, OsmRelation, OsmWay, RelationMember};
+
+    fn node(id: i64, lon: f64, tags: &[(&str, &str)]) -> OsmNode {
+        OsmNode {
+            id,
+            lat: 0.0,
+            lon,
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_search_syntax_and_ranking() {
+        let store = OsmStore::new();
+        store.insert_node(node(1, 0.01, &[("amenity", "cafe"), ("name", "Blue Cafe")]));
+        store.insert_node(node(
+            2,
+            0.001,
+            &[("amenity", "cafe"), ("name:en", "Red Cafe")],
+        ));
+        store.insert_node(node(
+            3,
+            0.0,
+            &[("amenity", "bar"), ("addr:street", "Cafe Rd")],
+        ));
+        store.insert_node(node(4, 0.002, &[]));
+        store.insert_way(OsmWay {
+            id: 10,
+            node_refs: vec![3, 4],
+            tags: vec![("highway".to_string(), "residential".to_string())],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        });
+        store.rebuild_indices();
+
+        let ids = |input: &str| -> Vec<(MemberType, i64)> {
+            let query = SearchQuery::parse(input).unwrap();
+            search(&store, &query, (0.0, 0.0), 10)
+                .features
+                .iter()
+                .map(|f| (f.member_type, f.id))
+                .collect()
+        };
+        use MemberType::{Node, Way};
+        assert_eq!(ids("cafe"), vec![(Node, 3), (Node, 2), (Node, 1)]);
+        assert_eq!(ids("amenity=cafe"), vec![(Node, 2), (Node, 1)]);
+        assert_eq!(ids("amenity~^(bar|pub)$"), vec![(Node, 3)]);
+        assert_eq!(ids("highway=*"), vec![(Way, 10)]);
+        assert!(ids("name=Nothing").is_empty());
+
+        // 自由文本优先以 name 作为命中标签
+        let query = SearchQuery::parse("blue").unwrap();
+        let hit = &search(&store, &query, (0.0, 0.0), 10).features[0];
+        assert_eq!(hit.matched_tag.0, "name");
+        assert!((hit.distance.unwrap() - 1113.2).abs() < 1.0);
+
+        assert!(SearchQuery::parse("  ").is_err());
+        assert!(SearchQuery::parse("name~(").is_err());
+        assert!(matches!(
+            SearchQuery::parse("Main St = 1"),
+            Ok(SearchQuery::Text(_))
+        ));
+    }
+
+    #[test]
+    fn test_search_keeps_nearest_within_limit() {
+        let store = OsmStore::new();
+        for id in 1..=5 {
+            store.insert_node(node(id, id as f64 * 0.01, &[("shop", "bakery")]));
+        }
+        store.insert_node(node(6, 0.1, &[]));
+        store.insert_node(node(7, 0.2, &[]));
+        store.insert_way(OsmWay {
+            id: 10,
+            node_refs: vec![6, 7],
+            tags: vec![("shop".to_string(), "bakery".to_string())],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        });
+        store.relations.insert(
+            20,
+            OsmRelation {
+                id: 20,
+                members: vec![
+                    RelationMember {
+                        member_type: MemberType::Way,
+                        ref_id: 10,
+                        role: "outer".to_string(),
+                    },
+                    RelationMember {
+                        member_type: MemberType::Way,
+                        ref_id: 99,
+                        role: "outer".to_string(),
+                    },
+                ],
+                tags: vec![("shop".to_string(), "bakery".to_string())],
+            },
+        );
+        store.relations.insert(
+            21,
+            OsmRelation {
+                id: 21,
+                members: vec![RelationMember {
+                    member_type: MemberType::Way,
+                    ref_id: 99,
+                    role: "outer".to_string(),
+                }],
+                tags: vec![("shop".to_string(), "bakery".to_string())],
+            },
+        );
+        store.rebuild_indices();
+
+        let query = SearchQuery::parse("shop=bakery").unwrap();
+        let results = search(&store, &query, (0.0, 0.0), 3);
+        assert_eq!(results.total, 8);
+        let ids: Vec<i64> = results.features.iter().map(|f| f.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        // 靠近路径时，路径与关系取路径索引包围盒的中心，成员均未加载的关系排在最后
+        let results = search(&store, &query, (0.15, 0.0), 8);
+        let ranked: Vec<(MemberType, i64)> = results
+            .features
+            .iter()
+            .map(|f| (f.member_type, f.id))
+            .collect();
+        assert_eq!(
+            ranked[..2],
+            [(MemberType::Way, 10), (MemberType::Relation, 20)]
+        );
+        assert_eq!(ranked[7], (MemberType::Relation, 21));
+        let way = &results.features[0];
+        assert!((way.location.unwrap().0 - 0.15).abs() < 1e-9);
+        assert!(results.features[7].location.is_none());
+
+        assert!(search(&store, &query, (0.0, 0.0), 0).features.is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+//! Way 几何简化 (Douglas–Peucker / Visvalingam–Whyatt)
+//!
+//! GPS 轨迹和导入数据常带有大量冗余顶点。简化只删除"安全"的顶点：
+//! - 端点、闭合点始终保留
+//! - 带标签、被其他 Way 共享或属于 Relation 的节点视为固定点（通过 node_ref_count 判断）
+//! - 固定点把 Way 切分为若干段，每段独立简化，保证固定点位置不变
+//!
+//! 容差以地面米为单位，按 Way 所在纬度换算为墨卡托距离。
+
+use crate::history::WayNodesChange;
+use crate::osm_store::{OsmNode, OsmStore};
+use crate::projection::{lonlat_to_mercator, mercator_scale_factor};
+use crate::spatial_query::point_to_segment_distance_sq;
+use crate::types::SimplifiedWayPreview;
+use std::collections::HashSet;
+
+/// 简化算法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimplifyAlgorithm {
+    /// 按最大偏离距离保留顶点
+    #[default]
+    DouglasPeucker,
+    /// 按顶点与相邻点构成的三角形面积逐个剔除
+    Visvalingam,
+}
+
+/// 简化计划（尚未提交）
+#[derive(Default)]
+pub struct SimplifyPlan {
+    pub removed_nodes: Vec<OsmNode>,
+    pub way_changes: Vec<WayNodesChange>,
+    pub previews: Vec<SimplifiedWayPreview>,
+}
+
+/// Douglas–Peucker：在 [first, last] 区间内标记需要保留的顶点
+fn douglas_peucker(points: &[(f64, f64)], tolerance_sq: f64, keep: &mut [bool]) {
+    let mut stack = vec![(0, points.len() - 1)];
+
+    while let Some((first, last)) = stack.pop() {
+        if last <= first + 1 {
+            continue;
+        }
+
+        let (x1, y1) = points[first];
+        let (x2, y2) = points[last];
+        let mut max_dist_sq = 0.0;
+        let mut max_idx = first;
+
+        for (i, &(px, py)) in points.iter().enumerate().take(last).skip(first + 1) {
+            let dist_sq = point_to_segment_distance_sq(px, py, x1, y1, x2, y2);
+            if dist_sq > max_dist_sq {
+                max_dist_sq = dist_sq;
+                max_idx = i;
+            }
+        }
+
+        if max_dist_sq > tolerance_sq {
+            keep[max_idx] = true;
+            stack.push((first, max_idx));
+            stack.push((max_idx, last));
+        }
+    }
+}
+
+/// 三角形面积
+fn triangle_area(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.0
+}
+
+/// Visvalingam–Whyatt：反复剔除有效面积最小的顶点，直到最小面积超过阈值
+fn visvalingam(points: &[(f64, f64)], min_area: f64, keep: &mut [bool]) {
+    // 当前仍保留的顶点索引（首尾始终保留）
+    let mut alive: Vec<usize> = (0..points.len()).collect();
+
+    while alive.len() > 2 {
+        let (pos, area) = (1..alive.len() - 1)
+            .map(|k| {
+                let area =
+                    triangle_area(points[alive[k - 1]], points[alive[k]], points[alive[k + 1]]);
+                (k, area)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+
+        if area >= min_area {
+            break;
+        }
+        alive.remove(pos);
+    }
+
+    for idx in alive {
+        keep[idx] = true;
+    }
+}
+
+/// 简化一条折线，返回每个顶点是否保留
+///
+/// - `fixed`: 必须保留的顶点（首尾自动视为固定）
+/// - `tolerance`: 墨卡托距离容差；Visvalingam 使用 tolerance² 作为面积阈值
+pub fn simplify_polyline(
+    points: &[(f64, f64)],
+    fixed: &[bool],
+    tolerance: f64,
+    algorithm: SimplifyAlgorithm,
+) -> Vec<bool> {
+    let n = points.len();
+    let mut keep = fixed.to_vec();
+    if n <= 2 {
+        return vec![true; n];
+    }
+    keep[0] = true;
+    keep[n - 1] = true;
+
+    let anchors: Vec<usize> = (0..n).filter(|&i| keep[i]).collect();
+
+    for pair in anchors.windows(2) {
+        let (first, last) = (pair[0], pair[1]);
+        if last <= first + 1 {
+            continue;
+        }
+
+        let segment = &points[first..=last];
+        let mut segment_keep = vec![false; segment.len()];
+        match algorithm {
+            SimplifyAlgorithm::DouglasPeucker => {
+                douglas_peucker(segment, tolerance * tolerance, &mut segment_keep)
+            }
+            SimplifyAlgorithm::Visvalingam => {
+                visvalingam(segment, tolerance * tolerance, &mut segment_keep)
+            }
+        }
+
+        for (offset, kept) in segment_keep.into_iter().enumerate() {
+            if kept {
+                keep[first + offset] = true;
+            }
+        }
+    }
+
+    keep
+}
+
+/// 计算选中 Way 的简化计划
+///
+/// 无法简化（缺失节点、结果无变化或简化后无效）的 Way 会被跳过
+pub fn plan_simplify(
+    store: &OsmStore,
+    way_ids: &[i64],
+    tolerance_meters: f64,
+    algorithm: SimplifyAlgorithm,
+) -> Result<SimplifyPlan, String> {
+    if tolerance_meters.is_nan() || tolerance_meters <= 0.0 {
+        return Err("Tolerance must be positive".to_string());
+    }
+
+    let mut plan = SimplifyPlan::default();
+    let mut removed: HashSet<i64> = HashSet::new();
+
+    for &way_id in way_ids {
+        let refs = match store.ways.get(&way_id) {
+            Some(way) => way.node_refs.clone(),
+            None => return Err(format!("Way #{} not found", way_id)),
+        };
+        if refs.len() < 3 {
+            continue;
+        }
+
+        let mut coords = Vec::with_capacity(refs.len());
+        let mut first_lat = 0.0;
+        for (i, node_id) in refs.iter().enumerate() {
+            match store.nodes.get(node_id) {
+                Some(node) => {
+                    if i == 0 {
+                        first_lat = node.lat;
+                    }
+                    coords.push(lonlat_to_mercator(node.lon, node.lat));
+                }
+                None => break,
+            }
+        }
+        if coords.len() != refs.len() {
+            continue;
+        }
+
+        let fixed: Vec<bool> = refs
+            .iter()
+            .map(|&id| !store.is_removable_vertex(id))
+            .collect();
+        let tolerance = tolerance_meters * mercator_scale_factor(first_lat);
+        let keep = simplify_polyline(&coords, &fixed, tolerance, algorithm);
+
+        let new_refs: Vec<i64> = refs
+            .iter()
+            .zip(&keep)
+            .filter(|(_, &k)| k)
+            .map(|(&id, _)| id)
+            .collect();
+
+        let is_closed = refs.first() == refs.last();
+        let min_len = if is_closed { 4 } else { 2 };
+        if new_refs.len() == refs.len() || new_refs.len() < min_len {
+            continue;
+        }
+
+        for (&node_id, &kept) in refs.iter().zip(&keep) {
+            if !kept && removed.insert(node_id) {
+                plan.removed_nodes
+                    .push(store.nodes.get(&node_id).unwrap().clone());
+            }
+        }
+
+        plan.previews.push(SimplifiedWayPreview {
+            way_id,
+            original_node_count: refs.len(),
+            simplified_node_count: new_refs.len(),
+            coords: coords
+                .iter()
+                .zip(&keep)
+                .filter(|(_, &k)| k)
+                .map(|(&c, _)| c)
+                .collect(),
+        });
+
+        plan.way_changes.push(WayNodesChange {
+            way_id,
+            old_refs: refs,
+            new_refs,
+        });
+    }
+
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 带轻微抖动的 L 形折线，拐点位于索引 3
+    fn jittered_corner() -> Vec<(f64, f64)> {
+        vec![
+            (0.0, 0.0),
+            (1.0, 0.1),
+            (2.0, -0.1),
+            (3.0, 0.0),
+            (3.1, 1.0),
+            (2.9, 2.0),
+            (3.0, 3.0),
+        ]
+    }
+
+    #[test]
+    fn test_douglas_peucker_keeps_corner() {
+        let points = jittered_corner();
+        let keep = simplify_polyline(&points, &[false; 7], 0.5, SimplifyAlgorithm::DouglasPeucker);
+        assert_eq!(keep, vec![true, false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn test_visvalingam_keeps_corner() {
+        let points = jittered_corner();
+        let keep = simplify_polyline(&points, &[false; 7], 0.5, SimplifyAlgorithm::Visvalingam);
+        assert!(keep[3]);
+        assert!(!keep[1]);
+    }
+
+    #[test]
+    fn test_fixed_vertex_kept() {
+        let points = jittered_corner();
+        let mut fixed = [false; 7];
+        fixed[1] = true;
+        let keep = simplify_polyline(&points, &fixed, 0.5, SimplifyAlgorithm::DouglasPeucker);
+        assert!(keep[1]);
+        assert!(!keep[2]);
+    }
+}
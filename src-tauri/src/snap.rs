@@ -0,0 +1,213 @@
+//! 节点吸附
+//!
+//! 添加或移动节点时，在容差范围内：
+//! 1. 优先吸附到最近的已有节点（合并为同一节点）
+//! 2. 其次吸附到最近的 Way 线段（作为新顶点插入）
+//!
+//! 移动节点时跳过节点自身、与其相邻的节点以及其所在的 Way，
+//! 避免产生重复顶点或退化线段。
+
+use crate::history::{MergeNodesCommand, WayNodesChange};
+use crate::osm_store::{MemberType, OsmStore};
+use crate::projection;
+use crate::spatial_query::{self, NearestSegment};
+use std::collections::HashSet;
+
+/// 吸附目标
+#[derive(Debug, Clone, Copy)]
+pub enum Snap {
+    /// 已有节点
+    Node(i64),
+    /// Way 线段上的投影点
+    Way(NearestSegment),
+}
+
+/// 查找吸附目标
+///
+/// 参数：
+/// - merc_x, merc_y: 节点目标位置的墨卡托坐标（米）
+/// - tolerance_meters: 吸附容差（地面米）
+/// - moving: 被移动的节点（添加节点时为 None）
+pub fn find_snap(
+    store: &OsmStore,
+    merc_x: f64,
+    merc_y: f64,
+    tolerance_meters: f64,
+    moving: Option<i64>,
+) -> Option<Snap> {
+    let (_, lat) = projection::mercator_to_lonlat(merc_x, merc_y);
+    let tolerance = tolerance_meters * projection::mercator_scale_factor(lat);
+
+    let mut excluded = HashSet::new();
+    if let Some(node_id) = moving {
+        excluded.insert(node_id);
+        for way_id in store.find_ways_referencing_node(node_id) {
+            if let Some(way) = store.ways.get(&way_id) {
+                for pair in way.node_refs.windows(2) {
+                    if pair.contains(&node_id) {
+                        excluded.extend(pair);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(node_id) = spatial_query::nearest_node(store, merc_x, merc_y, tolerance, |id| {
+        excluded.contains(&id)
+    }) {
+        return Some(Snap::Node(node_id));
+    }
+    spatial_query::nearest_way_segment(store, merc_x, merc_y, tolerance, None, moving)
+        .map(Snap::Way)
+}
+
+/// 计算将节点合并到目标节点的命令
+pub fn plan_merge_nodes(
+    store: &OsmStore,
+    node_id: i64,
+    target_id: i64,
+) -> Result<MergeNodesCommand, String> {
+    if node_id == target_id {
+        return Err("Cannot merge a node into itself".to_string());
+    }
+    let node = match store.nodes.get(&node_id) {
+        Some(node) => node.clone(),
+        None => return Err("Node not found".to_string()),
+    };
+    let old_target_tags = match store.nodes.get(&target_id) {
+        Some(target) => target.tags.clone(),
+        None => return Err("Target node not found".to_string()),
+    };
+
+    let mut new_target_tags = old_target_tags.clone();
+    for (key, value) in &node.tags {
+        if !new_target_tags.iter().any(|(k, _)| k == key) {
+            new_target_tags.push((key.clone(), value.clone()));
+        }
+    }
+
+    let way_changes = store
+        .find_ways_referencing_node(node_id)
+        .into_iter()
+        .filter_map(|way_id| {
+            let way = store.ways.get(&way_id)?;
+            let new_refs = way
+                .node_refs
+                .iter()
+                .map(|&id| if id == node_id { target_id } else { id })
+                .collect();
+            Some(WayNodesChange {
+                way_id,
+                old_refs: way.node_refs.clone(),
+                new_refs,
+            })
+        })
+        .collect();
+
+    let relation_refs = store
+        .find_relations_referencing(MemberType::Node, node_id)
+        .into_iter()
+        .filter_map(|relation_id| {
+            let relation = store.relations.get(&relation_id)?;
+            let indices = relation
+                .members
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| m.member_type == MemberType::Node && m.ref_id == node_id)
+                .map(|(i, _)| i)
+                .collect();
+            Some((relation_id, indices))
+        })
+        .collect();
+
+    Ok(MergeNodesCommand {
+        node,
+        target_id,
+        old_target_tags,
+        new_target_tags,
+        way_changes,
+        relation_refs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryManager;
+    use crate::osm_store::{OsmNode, OsmWay};
+
+    fn node(id: i64, lon: f64, lat: f64) -> OsmNode {
+        OsmNode {
+            id,
+            lon,
+            lat,
+            tags: vec![],
+        }
+    }
+
+    fn way(id: i64, node_refs: Vec<i64>) -> OsmWay {
+        OsmWay {
+            id,
+            node_refs,
+            tags: vec![],
+            render_feature: 0,
+            layer: 0,
+            is_area: false,
+        }
+    }
+
+    /// Way 10: 1 → 2（沿赤道），Way 20: 3 → 4（北侧平行线）
+    fn store() -> OsmStore {
+        let store = OsmStore::new();
+        for (id, lon, lat) in [
+            (1, 0.0, 0.0),
+            (2, 0.001, 0.0),
+            (3, 0.0, 0.0005),
+            (4, 0.001, 0.0005),
+        ] {
+            store.add_node_with_index(node(id, lon, lat));
+        }
+        store.add_way_with_index(way(10, vec![1, 2]));
+        store.add_way_with_index(way(20, vec![3, 4]));
+        store
+    }
+
+    #[test]
+    fn test_find_snap_prefers_nodes() {
+        let store = store();
+        let (x, y) = projection::lonlat_to_mercator(0.00001, 0.00001);
+        assert!(matches!(
+            find_snap(&store, x, y, 5.0, None),
+            Some(Snap::Node(1))
+        ));
+
+        let (x, y) = projection::lonlat_to_mercator(0.0005, 0.00002);
+        match find_snap(&store, x, y, 5.0, None) {
+            Some(Snap::Way(segment)) => assert_eq!(segment.way_id, 10),
+            _ => panic!("expected way snap"),
+        }
+
+        // 移动节点 3 时不会吸附到自身所在的 Way 和相邻节点
+        let (x, y) = projection::lonlat_to_mercator(0.001, 0.00049);
+        assert!(find_snap(&store, x, y, 5.0, Some(3)).is_none());
+        assert!(find_snap(&store, x, y, 5.0, Some(1)).is_some());
+    }
+
+    #[test]
+    fn test_merge_nodes_undo_restores_connection() {
+        let store = store();
+        let history = HistoryManager::new();
+
+        // 将 Way 20 的起点 3 合并到 Way 10 的起点 1
+        let command = plan_merge_nodes(&store, 3, 1).unwrap();
+        assert!(history.execute(Box::new(command), &store).success);
+        assert!(!store.nodes.contains_key(&3));
+        assert_eq!(store.ways.get(&20).unwrap().node_refs, vec![1, 4]);
+        assert_eq!(store.node_ref_count.get(&1).map(|c| *c), Some(2));
+
+        assert!(history.undo(&store).success);
+        assert_eq!(store.nodes.get(&3).unwrap().lat, 0.0005);
+        assert_eq!(store.ways.get(&20).unwrap().node_refs, vec![3, 4]);
+        assert_eq!(store.node_ref_count.get(&1).map(|c| *c), Some(1));
+    }
+}
@@ -5,12 +5,13 @@
 //! - LOD (Level of Detail) 降级策略
 //! - 瓦片分块计算
 //! - Polygon 组装 (Area + Multipolygon)
+//! - 空间拾取与点所在面查询
 
-use crate::osm_store::OsmStore;
-use crate::polygon_assembler::{assemble_from_closed_way, AssembledPolygon};
-// TODO: 后续添加 Relation 空间索引后启用
-#[allow(unused_imports)]
-use crate::polygon_assembler::assemble_from_relation;
+use crate::osm_store::{AreaRelationIndex, MemberType, OsmStore, RelationParents};
+use crate::polygon_assembler::{
+    assemble_from_closed_way, assemble_from_relation, point_in_ring, AssembledPolygon,
+};
+use std::collections::HashSet;
 
 /// 带有引用计数的节点数据 (用于渲染优先级)
 #[derive(Debug, Clone, Copy)]
@@ -275,17 +276,67 @@ pub fn tiles_in_viewport(viewport: &Viewport) -> Vec<TileCoord> {
 
 use crate::projection::mercator_to_lonlat;
 
-/// 拾取结果类型
+/// 拾取命中方式
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub enum PickHit {
+    /// 节点在容差范围内
+    Vertex,
+    /// Way 的线段在容差范围内
+    Line,
+    /// Relation 的成员被命中
+    Member,
+    /// 点击位置在面内部
+    Interior,
+}
+
+/// 拾取候选
 #[derive(Debug, Clone, serde::Serialize)]
-#[serde(tag = "type", content = "id")]
-pub enum PickedFeature {
-    Node(i64),
-    Way(i64),
-    None,
+pub struct PickCandidate {
+    pub member_type: MemberType,
+    pub id: i64,
+    pub hit: PickHit,
+    /// 到点击位置的墨卡托距离（米），面内部为 0
+    pub distance: f64,
+}
+
+/// 要素的几何（经纬度折线列表）
+///
+/// Node 为单点，Way 为其节点序列；Relation 展开一层成员（不含子 Relation）。
+/// 要素不存在时返回 None，缺失的节点被跳过。
+pub fn feature_lines(
+    store: &OsmStore,
+    member_type: MemberType,
+    id: i64,
+) -> Option<Vec<Vec<(f64, f64)>>> {
+    let line = |node_refs: &[i64]| -> Vec<(f64, f64)> {
+        node_refs
+            .iter()
+            .filter_map(|id| store.nodes.get(id).map(|n| (n.lon, n.lat)))
+            .collect()
+    };
+    let lines = match member_type {
+        MemberType::Node => vec![line(&[id])],
+        MemberType::Way => vec![line(&store.ways.get(&id)?.node_refs)],
+        MemberType::Relation => {
+            let members = store.relations.get(&id)?.members.clone();
+            members
+                .iter()
+                .filter_map(|m| match m.member_type {
+                    MemberType::Node => Some(line(&[m.ref_id])),
+                    MemberType::Way => store.ways.get(&m.ref_id).map(|w| line(&w.node_refs)),
+                    MemberType::Relation => None,
+                })
+                .collect()
+        }
+    };
+    if member_type == MemberType::Node && lines[0].is_empty() {
+        return None;
+    }
+    Some(lines.into_iter().filter(|l| !l.is_empty()).collect())
 }
 
 /// 点到线段的最短距离（平方）
-fn point_to_segment_distance_sq(
+pub fn point_to_segment_distance_sq(
     px: f64,
     py: f64,
     x1: f64,
@@ -317,13 +368,155 @@ fn point_to_segment_distance_sq(
     dx * dx + dy * dy
 }
 
-/// 在点击位置查找最近的要素
+/// Way 上距离某点最近的线段
+#[derive(Debug, Clone, Copy)]
+pub struct NearestSegment {
+    pub way_id: i64,
+    /// 线段起点在 node_refs 中的位置（新顶点插入位置为 index + 1）
+    pub index: usize,
+    /// 投影点的墨卡托坐标
+    pub point: (f64, f64),
+    pub distance_sq: f64,
+}
+
+/// 查找容差范围内距离指定点最近的 Way 线段
 ///
-/// 算法：
-/// 1. 首先查找容差范围内的 Node（优先级最高，根据 zoom 过滤）
-/// 2. 如果没有 Node，查找最近的 Way
+/// 参数：
+/// - merc_x, merc_y: 查询点的墨卡托坐标（米）
+/// - tolerance: 墨卡托距离容差
+/// - only_way: 只在指定 Way 中查找
+/// - exclude_ways_with: 跳过包含该节点的整条 Way，而不只是与它相邻的线段
+///   （拖动顶点时避免吸附到自身所在的 Way）
+pub fn nearest_way_segment(
+    store: &OsmStore,
+    merc_x: f64,
+    merc_y: f64,
+    tolerance: f64,
+    only_way: Option<i64>,
+    exclude_ways_with: Option<i64>,
+) -> Option<NearestSegment> {
+    use crate::projection::lonlat_to_mercator;
+    use rstar::AABB;
+
+    let (lon, lat) = mercator_to_lonlat(merc_x, merc_y);
+    // 纬度方向每度对应的墨卡托距离更大，按赤道换算偏保守
+    let tolerance_deg = tolerance / 111320.0;
+    let search_bbox = AABB::from_corners(
+        [lon - tolerance_deg, lat - tolerance_deg],
+        [lon + tolerance_deg, lat + tolerance_deg],
+    );
+
+    let candidates: Vec<i64> = match only_way {
+        Some(way_id) => vec![way_id],
+        None => store
+            .way_index()
+            .locate_in_envelope_intersecting(&search_bbox)
+            .map(|entry| entry.id)
+            .collect(),
+    };
+
+    let tolerance_sq = tolerance * tolerance;
+    let mut best: Option<NearestSegment> = None;
+
+    for way_id in candidates {
+        let way = match store.ways.get(&way_id) {
+            Some(way) => way,
+            None => continue,
+        };
+
+        if exclude_ways_with.is_some_and(|id| way.node_refs.contains(&id)) {
+            continue;
+        }
+
+        for (index, pair) in way.node_refs.windows(2).enumerate() {
+            let (n1, n2) = match (store.nodes.get(&pair[0]), store.nodes.get(&pair[1])) {
+                (Some(n1), Some(n2)) => (n1, n2),
+                _ => continue,
+            };
+            let (x1, y1) = lonlat_to_mercator(n1.lon, n1.lat);
+            let (x2, y2) = lonlat_to_mercator(n2.lon, n2.lat);
+
+            let distance_sq = point_to_segment_distance_sq(merc_x, merc_y, x1, y1, x2, y2);
+            if distance_sq > tolerance_sq
+                || best.is_some_and(|nearest| distance_sq >= nearest.distance_sq)
+            {
+                continue;
+            }
+
+            // 计算投影点
+            let (dx, dy) = (x2 - x1, y2 - y1);
+            let len_sq = dx * dx + dy * dy;
+            let t = if len_sq < 1e-10 {
+                0.0
+            } else {
+                (((merc_x - x1) * dx + (merc_y - y1) * dy) / len_sq).clamp(0.0, 1.0)
+            };
+
+            best = Some(NearestSegment {
+                way_id,
+                index,
+                point: (x1 + t * dx, y1 + t * dy),
+                distance_sq,
+            });
+        }
+    }
+
+    best
+}
+
+/// 查找容差范围内距离指定点最近的节点
 ///
 /// 参数：
+/// - merc_x, merc_y: 查询点的墨卡托坐标（米）
+/// - tolerance: 墨卡托距离容差
+/// - exclude: 需要跳过的节点
+pub fn nearest_node(
+    store: &OsmStore,
+    merc_x: f64,
+    merc_y: f64,
+    tolerance: f64,
+    exclude: impl Fn(i64) -> bool,
+) -> Option<i64> {
+    use crate::projection::lonlat_to_mercator;
+    use rstar::AABB;
+
+    let (lon, lat) = mercator_to_lonlat(merc_x, merc_y);
+    let tolerance_deg = tolerance / 111320.0;
+    let search_bbox = AABB::from_corners(
+        [lon - tolerance_deg, lat - tolerance_deg],
+        [lon + tolerance_deg, lat + tolerance_deg],
+    );
+
+    let tolerance_sq = tolerance * tolerance;
+    let mut best: Option<(i64, f64)> = None;
+
+    for entry in store.node_index().locate_in_envelope(&search_bbox) {
+        if exclude(entry.id) {
+            continue;
+        }
+        let (x, y) = lonlat_to_mercator(entry.min_lon, entry.min_lat);
+        let distance_sq = (x - merc_x).powi(2) + (y - merc_y).powi(2);
+        if distance_sq <= tolerance_sq && best.is_none_or(|(_, d)| distance_sq < d) {
+            best = Some((entry.id, distance_sq));
+        }
+    }
+
+    best.map(|(id, _)| id)
+}
+
+/// 在点击位置查找全部候选要素，按优先级排列
+///
+/// 排序：
+/// 1. 容差范围内的 Node（根据 zoom 过滤），按距离
+/// 2. 线段在容差范围内的 Way，按距离
+/// 3. 成员被以上命中的 Relation，按最近成员的距离
+/// 4. 包含点击位置的面（闭合 Way、multipolygon/boundary Relation），按面积从小到大
+///
+/// 同一要素只保留排序最靠前的一次；重叠的要素可以通过在同一位置重复点击依次切换。
+///
+/// 参数：
+/// - parents: 成员 → 所属 Relation 反查表
+/// - area_relations: multipolygon/boundary Relation 包围盒索引
 /// - merc_x, merc_y: 点击位置的墨卡托坐标（米）
 /// - tolerance_meters: 拾取容差（米）
 /// - zoom: 当前缩放级别，用于过滤节点显示
@@ -333,123 +526,404 @@ fn point_to_segment_distance_sq(
 /// - 普通节点 (ref_count < 2): zoom >= 20 时显示
 pub fn pick_feature(
     store: &OsmStore,
+    parents: &RelationParents,
+    area_relations: &AreaRelationIndex,
     merc_x: f64,
     merc_y: f64,
     tolerance_meters: f64,
     zoom: f64,
-) -> PickedFeature {
+) -> Vec<PickCandidate> {
     use crate::projection::lonlat_to_mercator;
     use rstar::AABB;
+    use std::collections::HashMap;
 
     // 转换为经纬度用于 R-Tree 查询
     let (click_lon, click_lat) = mercator_to_lonlat(merc_x, merc_y);
 
     // 计算容差对应的经纬度范围（近似）
-    // 在赤道，1度 ≈ 111320米
-    let meters_per_degree = 111320.0;
-    let tolerance_deg = tolerance_meters / meters_per_degree;
-
+    // 在赤道，1度 ≈ 111320米；纬度方向每度对应的墨卡托距离更大，按赤道换算偏保守
+    let tolerance_deg = tolerance_meters / 111320.0;
     let tolerance_sq = tolerance_meters * tolerance_meters;
-
-    // 1. 优先查找 Node
     let search_bbox = AABB::from_corners(
         [click_lon - tolerance_deg, click_lat - tolerance_deg],
         [click_lon + tolerance_deg, click_lat + tolerance_deg],
     );
 
-    let node_index = store.node_index();
-    let mut closest_node: Option<(i64, f64)> = None;
-
-    for entry in node_index.locate_in_envelope(&search_bbox) {
-        if let Some(node) = store.nodes.get(&entry.id) {
-            // 根据 zoom 级别过滤节点（与渲染逻辑一致）
-            let ref_count = store
-                .node_ref_count
-                .get(&entry.id)
-                .map(|r| *r)
-                .unwrap_or(0);
-            let is_high_priority = ref_count >= 2;
-
-            // 优先节点: zoom >= 18 时可见
-            // 普通节点: zoom >= 20 时可见
-            let is_visible = if is_high_priority {
-                zoom >= 18.0
-            } else {
-                zoom >= 20.0
-            };
-
-            if !is_visible {
-                continue;
-            }
+    // 1. Node
+    let mut nodes: Vec<(i64, f64)> = Vec::new();
+    for entry in store.node_index().locate_in_envelope(&search_bbox) {
+        let Some(node) = store.nodes.get(&entry.id) else {
+            continue;
+        };
+        // 根据 zoom 级别过滤节点（与渲染逻辑一致）
+        let ref_count = store.node_ref_count.get(&entry.id).map(|r| *r).unwrap_or(0);
+        let min_zoom = if ref_count >= 2 { 18.0 } else { 20.0 };
+        if zoom < min_zoom {
+            continue;
+        }
 
-            let (node_mx, node_my) = lonlat_to_mercator(node.lon, node.lat);
-            let dx = node_mx - merc_x;
-            let dy = node_my - merc_y;
-            let dist_sq = dx * dx + dy * dy;
+        let (node_mx, node_my) = lonlat_to_mercator(node.lon, node.lat);
+        let dist_sq = (node_mx - merc_x).powi(2) + (node_my - merc_y).powi(2);
+        if dist_sq <= tolerance_sq {
+            nodes.push((entry.id, dist_sq.sqrt()));
+        }
+    }
 
-            if dist_sq <= tolerance_sq {
-                if closest_node.is_none() || dist_sq < closest_node.unwrap().1 {
-                    closest_node = Some((entry.id, dist_sq));
-                }
-            }
+    // 2. Way：包围盒与容差范围相交即为候选，点击点略在包围盒外时也能命中
+    let way_ids: Vec<i64> = store
+        .way_index()
+        .locate_in_envelope_intersecting(&search_bbox)
+        .map(|entry| entry.id)
+        .collect();
+    let mut ways: Vec<(i64, f64)> = Vec::new();
+    for way_id in way_ids {
+        let Some(way) = store.ways.get(&way_id) else {
+            continue;
+        };
+        let min_dist_sq = way
+            .node_refs
+            .windows(2)
+            .filter_map(|pair| {
+                let n1 = store.nodes.get(&pair[0])?;
+                let n2 = store.nodes.get(&pair[1])?;
+                let (mx1, my1) = lonlat_to_mercator(n1.lon, n1.lat);
+                let (mx2, my2) = lonlat_to_mercator(n2.lon, n2.lat);
+                Some(point_to_segment_distance_sq(
+                    merc_x, merc_y, mx1, my1, mx2, my2,
+                ))
+            })
+            .fold(f64::INFINITY, f64::min);
+        if min_dist_sq <= tolerance_sq {
+            ways.push((way_id, min_dist_sq.sqrt()));
         }
     }
 
-    if let Some((node_id, _)) = closest_node {
-        return PickedFeature::Node(node_id);
+    // 3. Relation：取被命中成员的最近距离
+    let hits: HashMap<(MemberType, i64), f64> = nodes
+        .iter()
+        .map(|&(id, distance)| ((MemberType::Node, id), distance))
+        .chain(
+            ways.iter()
+                .map(|&(id, distance)| ((MemberType::Way, id), distance)),
+        )
+        .collect();
+    let mut nearest: HashMap<i64, f64> = HashMap::new();
+    for (member, &distance) in &hits {
+        for &relation_id in parents.get(member).into_iter().flatten() {
+            nearest
+                .entry(relation_id)
+                .and_modify(|d| *d = d.min(distance))
+                .or_insert(distance);
+        }
     }
+    let mut relations: Vec<(i64, f64)> = nearest
+        .into_iter()
+        .filter(|(id, _)| store.relations.contains_key(id))
+        .collect();
+
+    let by_distance = |a: &(i64, f64), b: &(i64, f64)| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0));
+    nodes.sort_by(by_distance);
+    ways.sort_by(by_distance);
+    relations.sort_by(by_distance);
+
+    // 4. 面内部
+    let areas = areas_at_point(store, area_relations, merc_x, merc_y);
+
+    let ranked = nodes
+        .into_iter()
+        .map(|(id, distance)| (MemberType::Node, id, PickHit::Vertex, distance))
+        .chain(
+            ways.into_iter()
+                .map(|(id, distance)| (MemberType::Way, id, PickHit::Line, distance)),
+        )
+        .chain(
+            relations
+                .into_iter()
+                .map(|(id, distance)| (MemberType::Relation, id, PickHit::Member, distance)),
+        )
+        .chain(
+            areas
+                .into_iter()
+                .map(|area| (area.member_type, area.id, PickHit::Interior, 0.0)),
+        );
 
-    // 2. 查找 Way
-    // Way 的 R-Tree 存储的是 Way 的包围盒
-    // 使用一个非常小的查询框来查找"包含点击点"的所有 Way 包围盒
-    let way_index = store.way_index();
-    let mut closest_way: Option<(i64, f64)> = None;
-
-    // 使用一个极小的搜索框来查找包含该点的所有 Way
-    let tiny_eps = 1e-9;
-    let click_box = AABB::from_corners(
-        [click_lon - tiny_eps, click_lat - tiny_eps],
-        [click_lon + tiny_eps, click_lat + tiny_eps],
-    );
+    let mut seen = HashSet::new();
+    ranked
+        .filter(|&(member_type, id, _, _)| seen.insert((member_type, id)))
+        .map(|(member_type, id, hit, distance)| PickCandidate {
+            member_type,
+            id,
+            hit,
+            distance,
+        })
+        .collect()
+}
 
-    for entry in way_index.locate_in_envelope_intersecting(&click_box) {
-        if let Some(way) = store.ways.get(&entry.id) {
+/// 包含某点的面
+pub struct AreaAtPoint {
+    pub member_type: MemberType,
+    pub id: i64,
+    /// 近似面积（平方米，按查询点纬度换算）
+    pub area: f64,
+}
 
-            // 计算点击位置到 Way 的最短距离
-            let node_refs = &way.node_refs;
-            if node_refs.len() < 2 {
-                continue;
+/// 奇偶规则：点被奇数个环包含时位于多边形内
+fn polygon_contains(polygon: &AssembledPolygon, point: (f64, f64)) -> bool {
+    polygon
+        .rings
+        .iter()
+        .filter(|ring| point_in_ring(point, ring))
+        .count()
+        % 2
+        == 1
+}
+
+/// 多边形面积（墨卡托平方米）
+///
+/// 按奇偶规则，被偶数个其他环包含的环计为外环，其余为内环
+fn polygon_area(polygon: &AssembledPolygon) -> f64 {
+    let ring_area = |ring: &[(f64, f64)]| {
+        ring.windows(2)
+            .map(|pair| pair[0].0 * pair[1].1 - pair[1].0 * pair[0].1)
+            .sum::<f64>()
+            .abs()
+            / 2.0
+    };
+    polygon
+        .rings
+        .iter()
+        .enumerate()
+        .map(|(i, ring)| {
+            let depth = polygon
+                .rings
+                .iter()
+                .enumerate()
+                .filter(|(j, other)| *j != i && point_in_ring(ring[0], other))
+                .count();
+            if depth % 2 == 0 {
+                ring_area(ring)
+            } else {
+                -ring_area(ring)
             }
+        })
+        .sum()
+}
 
-            let mut min_dist_sq = f64::MAX;
+/// 查找包含指定点（墨卡托坐标）的全部面，按面积从小到大排列
+///
+/// 包括面状闭合 Way（`is_area` 或带 `boundary` 标签）和 multipolygon/boundary Relation。
+/// 候选为包围盒包含该点者（Relation 取 `area_relations` 中全部成员的合并包围盒），
+/// 组装后再按奇偶规则精确判断。
+pub fn areas_at_point(
+    store: &OsmStore,
+    area_relations: &AreaRelationIndex,
+    merc_x: f64,
+    merc_y: f64,
+) -> Vec<AreaAtPoint> {
+    use rstar::AABB;
 
-            for i in 0..node_refs.len() - 1 {
-                let n1 = store.nodes.get(&node_refs[i]);
-                let n2 = store.nodes.get(&node_refs[i + 1]);
+    let (lon, lat) = mercator_to_lonlat(merc_x, merc_y);
+    let point = AABB::from_point([lon, lat]);
+    let way_ids: Vec<i64> = store
+        .way_index()
+        .locate_in_envelope_intersecting(&point)
+        .map(|entry| entry.id)
+        .collect();
+    let relation_ids: Vec<i64> = area_relations
+        .locate_in_envelope_intersecting(&point)
+        .map(|entry| entry.id)
+        .collect();
+
+    let mut polygons: Vec<(MemberType, AssembledPolygon)> = way_ids
+        .into_iter()
+        .filter(|id| {
+            store
+                .ways
+                .get(id)
+                .is_some_and(|way| way.is_area || way.tags.iter().any(|(k, _)| k == "boundary"))
+        })
+        .filter_map(|id| assemble_from_closed_way(store, id))
+        .map(|polygon| (MemberType::Way, polygon))
+        .collect();
+
+    polygons.extend(
+        relation_ids
+            .into_iter()
+            .filter_map(|id| assemble_from_relation(store, id))
+            .map(|polygon| (MemberType::Relation, polygon)),
+    );
 
-                if let (Some(n1), Some(n2)) = (n1, n2) {
-                    let (mx1, my1) = lonlat_to_mercator(n1.lon, n1.lat);
-                    let (mx2, my2) = lonlat_to_mercator(n2.lon, n2.lat);
+    let scale = crate::projection::mercator_scale_factor(lat);
+    let mut areas: Vec<AreaAtPoint> = polygons
+        .into_iter()
+        .filter(|(_, polygon)| polygon_contains(polygon, (merc_x, merc_y)))
+        .map(|(member_type, polygon)| AreaAtPoint {
+            member_type,
+            id: polygon.way_id,
+            area: polygon_area(&polygon) / (scale * scale),
+        })
+        .collect();
+    areas.sort_by(|a, b| {
+        a.area
+            .total_cmp(&b.area)
+            .then_with(|| (a.member_type as u8).cmp(&(b.member_type as u8)))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    areas
+}
 
-                    let dist_sq = point_to_segment_distance_sq(merc_x, merc_y, mx1, my1, mx2, my2);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_store::{OsmNode, OsmRelation, OsmWay, RelationMember};
+    use crate::projection::lonlat_to_mercator;
 
-                    if dist_sq < min_dist_sq {
-                        min_dist_sq = dist_sq;
-                    }
-                }
-            }
+    fn way(id: i64, node_refs: Vec<i64>, tags: &[(&str, &str)]) -> OsmWay {
+        OsmWay {
+            id,
+            node_refs,
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            render_feature: 0,
+            layer: 0,
+            is_area: !tags.is_empty(),
+        }
+    }
 
-            if min_dist_sq <= tolerance_sq {
-                if closest_way.is_none() || min_dist_sq < closest_way.unwrap().1 {
-                    closest_way = Some((entry.id, min_dist_sq));
-                }
-            }
+    /// 行政区 Relation 100（两段外环，0..1）内有建筑 Way 20 (0.4..0.6)
+    fn boundary_with_building() -> OsmStore {
+        let store = OsmStore::new();
+        // 行政区外环 1-4 (0..1)，建筑 5-8 (0.4..0.6)
+        for (id, lon, lat) in [
+            (1, 0.0, 0.0),
+            (2, 1.0, 0.0),
+            (3, 1.0, 1.0),
+            (4, 0.0, 1.0),
+            (5, 0.4, 0.4),
+            (6, 0.6, 0.4),
+            (7, 0.6, 0.6),
+            (8, 0.4, 0.6),
+        ] {
+            store.insert_node(OsmNode {
+                id,
+                lon,
+                lat,
+                tags: vec![],
+            });
         }
+        // 外环由两段组成，均不包含查询点的包围盒
+        store.insert_way(way(10, vec![1, 2, 3], &[]));
+        store.insert_way(way(11, vec![3, 4, 1], &[]));
+        store.insert_way(way(20, vec![5, 6, 7, 8, 5], &[("building", "yes")]));
+        store.relations.insert(
+            100,
+            OsmRelation {
+                id: 100,
+                members: [10, 11]
+                    .into_iter()
+                    .map(|ref_id| RelationMember {
+                        member_type: MemberType::Way,
+                        ref_id,
+                        role: "outer".to_string(),
+                    })
+                    .collect(),
+                tags: vec![
+                    ("type".to_string(), "boundary".to_string()),
+                    ("admin_level".to_string(), "8".to_string()),
+                ],
+            },
+        );
+        store.rebuild_indices();
+        store
     }
 
-    if let Some((way_id, _)) = closest_way {
-        return PickedFeature::Way(way_id);
+    #[test]
+    fn test_areas_at_point() {
+        let store = boundary_with_building();
+        let area_relations = store.area_relation_index();
+        let at = |lon, lat| -> Vec<(MemberType, i64)> {
+            let (x, y) = lonlat_to_mercator(lon, lat);
+            areas_at_point(&store, &area_relations, x, y)
+                .iter()
+                .map(|a| (a.member_type, a.id))
+                .collect()
+        };
+        assert_eq!(
+            at(0.5, 0.5),
+            vec![(MemberType::Way, 20), (MemberType::Relation, 100)]
+        );
+        assert_eq!(at(0.2, 0.5), vec![(MemberType::Relation, 100)]);
+        assert!(at(1.5, 0.5).is_empty());
+
+        let (x, y) = lonlat_to_mercator(0.5, 0.5);
+        let building = &areas_at_point(&store, &area_relations, x, y)[0];
+        // 0.2° × 0.2° ≈ 22.26 km × 22.26 km
+        assert!((building.area / 4.95e8 - 1.0).abs() < 0.01);
     }
 
-    PickedFeature::None
+    #[test]
+    fn test_pick_feature_ranks_candidates() {
+        let store = boundary_with_building();
+        store.relations.insert(
+            200,
+            OsmRelation {
+                id: 200,
+                members: vec![RelationMember {
+                    member_type: MemberType::Node,
+                    ref_id: 5,
+                    role: String::new(),
+                }],
+                tags: vec![("type".to_string(), "site".to_string())],
+            },
+        );
+        let parents = store.relation_parents();
+        let area_relations = store.area_relation_index();
+        // 只有 multipolygon/boundary Relation 进入包围盒索引
+        assert_eq!(
+            area_relations.iter().map(|e| e.id).collect::<Vec<_>>(),
+            vec![100]
+        );
+        let pick = |lon, lat, zoom| -> Vec<(MemberType, i64, PickHit)> {
+            let (x, y) = lonlat_to_mercator(lon, lat);
+            pick_feature(&store, &parents, &area_relations, x, y, 20.0, zoom)
+                .iter()
+                .map(|c| (c.member_type, c.id, c.hit))
+                .collect()
+        };
+
+        // 点击点在建筑包围盒外，但距其西边约 11 米
+        assert_eq!(
+            pick(0.3999, 0.5, 20.0),
+            vec![
+                (MemberType::Way, 20, PickHit::Line),
+                (MemberType::Relation, 100, PickHit::Interior),
+            ]
+        );
+        assert_eq!(
+            pick(0.4, 0.4, 20.0),
+            vec![
+                (MemberType::Node, 5, PickHit::Vertex),
+                (MemberType::Way, 20, PickHit::Line),
+                (MemberType::Relation, 200, PickHit::Member),
+                (MemberType::Relation, 100, PickHit::Interior),
+            ]
+        );
+        // 低缩放级别下普通节点不可拾取
+        assert_eq!(
+            pick(0.4, 0.4, 16.0),
+            vec![
+                (MemberType::Way, 20, PickHit::Line),
+                (MemberType::Relation, 100, PickHit::Interior),
+            ]
+        );
+        assert_eq!(
+            pick(0.5, 0.5, 16.0),
+            vec![
+                (MemberType::Way, 20, PickHit::Interior),
+                (MemberType::Relation, 100, PickHit::Interior),
+            ]
+        );
+    }
 }
@@ -0,0 +1,136 @@
+//! Way 拆分
+//!
+//! 在内部节点处将 Way 拆分为两段：原 Way 保留到达拆分节点的一段，
+//! 新 Way 从拆分节点开始。所属 Relation 的成员随之更新：
+//! - 转向限制 (type=restriction) 的 from/to 成员指向与 via 相连的一段
+//! - 其他成员（包括 via）在原成员之后插入新 Way，角色相同
+
+use crate::history::{EntitySwap, SplitWayCommand, WayStyle};
+use crate::osm_store::{MemberType, OsmRelation, OsmStore, OsmWay, RelationMember};
+use crate::tag_edit;
+use std::collections::HashSet;
+
+/// 计算在指定节点处拆分 Way 的命令
+pub fn plan_split(
+    store: &OsmStore,
+    way_id: i64,
+    node_id: i64,
+    new_way_id: i64,
+) -> Result<SplitWayCommand, String> {
+    let way = match store.ways.get(&way_id) {
+        Some(way) => way.clone(),
+        None => return Err("Way not found".to_string()),
+    };
+    let last = way.node_refs.len().saturating_sub(1);
+    let index = match way.node_refs.iter().position(|&id| id == node_id) {
+        Some(index) if index > 0 && index < last => index,
+        _ => {
+            return Err(format!(
+                "Node #{} is not an inner vertex of Way #{}",
+                node_id, way_id
+            ))
+        }
+    };
+
+    let new_refs = way.node_refs[..=index].to_vec();
+    let split_refs = way.node_refs[index..].to_vec();
+    let split_style = tag_edit::way_style(&way.tags, &split_refs);
+    let new_way = OsmWay {
+        id: new_way_id,
+        node_refs: split_refs,
+        tags: way.tags.clone(),
+        render_feature: split_style.render_feature,
+        layer: split_style.layer,
+        is_area: split_style.is_area,
+    };
+
+    let relations = store
+        .find_relations_referencing(MemberType::Way, way_id)
+        .into_iter()
+        .filter_map(|relation_id| {
+            let before = store.relations.get(&relation_id)?.clone();
+            let after = split_members(store, &before, way_id, &new_refs, &new_way);
+            Some(EntitySwap {
+                id: relation_id,
+                before: Some(before),
+                after: Some(after),
+            })
+        })
+        .collect();
+
+    Ok(SplitWayCommand {
+        way_id,
+        old_style: WayStyle {
+            render_feature: way.render_feature,
+            layer: way.layer,
+            is_area: way.is_area,
+        },
+        new_style: tag_edit::way_style(&way.tags, &new_refs),
+        old_refs: way.node_refs,
+        new_refs,
+        new_way,
+        relations,
+    })
+}
+
+/// 转向限制中 via 成员的端点节点（via 节点本身或 via Way 的首尾节点）
+pub fn via_endpoints(store: &OsmStore, relation: &OsmRelation) -> HashSet<i64> {
+    let mut endpoints = HashSet::new();
+    for member in relation.members.iter().filter(|m| m.role == "via") {
+        match member.member_type {
+            MemberType::Node => {
+                endpoints.insert(member.ref_id);
+            }
+            MemberType::Way => {
+                if let Some(way) = store.ways.get(&member.ref_id) {
+                    endpoints.extend(way.node_refs.first());
+                    endpoints.extend(way.node_refs.last());
+                }
+            }
+            MemberType::Relation => {}
+        }
+    }
+    endpoints
+}
+
+/// 拆分后的 Relation 成员列表
+fn split_members(
+    store: &OsmStore,
+    relation: &OsmRelation,
+    way_id: i64,
+    kept_refs: &[i64],
+    new_way: &OsmWay,
+) -> OsmRelation {
+    let is_restriction = relation.tag("type") == Some("restriction");
+    let via = if is_restriction {
+        via_endpoints(store, relation)
+    } else {
+        HashSet::new()
+    };
+    // 原 Way 保留的一段起点不与 via 相连、而新 Way 终点相连时，改为引用新 Way
+    let use_new_way =
+        !via.contains(&kept_refs[0]) && new_way.node_refs.last().is_some_and(|id| via.contains(id));
+
+    let mut after = relation.clone();
+    after.members.clear();
+    for member in &relation.members {
+        if member.member_type != MemberType::Way || member.ref_id != way_id {
+            after.members.push(member.clone());
+            continue;
+        }
+        if is_restriction && (member.role == "from" || member.role == "to") {
+            let ref_id = if use_new_way { new_way.id } else { way_id };
+            after.members.push(RelationMember {
+                ref_id,
+                ..member.clone()
+            });
+            continue;
+        }
+        after.members.push(member.clone());
+        after.members.push(RelationMember {
+            ref_id: new_way.id,
+            ..member.clone()
+        });
+    }
+    after
+}
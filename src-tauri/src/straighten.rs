@@ -0,0 +1,156 @@
+//! Way 拉直 (Straighten)
+//!
+//! 用于从影像手绘、略有抖动的直线道路或铁路。
+//!
+//! ## 算法
+//!
+//! 在 Web 墨卡托空间中：
+//! 1. 以区间首尾节点连线为目标直线
+//! 2. 内部顶点沿垂线投影到直线上
+//! 3. 无标签、未被共享的内部顶点投影后冗余，直接删除；其余顶点保留并移动
+//! 4. 任一顶点偏离直线超过 `首尾距离 × max_deviation_ratio` 时拒绝操作，
+//!    避免把明显弯曲的 Way 误拉直
+
+use crate::history::NodeMove;
+use crate::osm_store::{OsmNode, OsmStore};
+use crate::projection::{lonlat_to_mercator, mercator_to_lonlat};
+
+/// 默认最大偏离比例（相对首尾距离）
+pub const DEFAULT_MAX_DEVIATION_RATIO: f64 = 0.2;
+
+/// 拉直计划（尚未提交）
+#[derive(Debug, Clone)]
+pub struct StraightenPlan {
+    pub moves: Vec<NodeMove>,
+    /// 被删除的顶点及其在 Way 中的原始位置（位置升序）
+    pub removed_vertices: Vec<(OsmNode, usize)>,
+}
+
+/// 将内部顶点投影到首尾连线上
+///
+/// 返回所有顶点的新坐标（首尾不变）；偏离过大时返回错误
+pub fn straighten_points(
+    points: &[(f64, f64)],
+    max_deviation_ratio: f64,
+) -> Result<Vec<(f64, f64)>, String> {
+    if points.len() < 3 {
+        return Err("Nothing to straighten".to_string());
+    }
+    if max_deviation_ratio.is_nan() || max_deviation_ratio <= 0.0 {
+        return Err("Maximum deviation must be positive".to_string());
+    }
+
+    let (x1, y1) = points[0];
+    let (x2, y2) = points[points.len() - 1];
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let length_sq = dx * dx + dy * dy;
+    if length_sq < 1e-18 {
+        return Err("Endpoints coincide".to_string());
+    }
+
+    let max_deviation = length_sq.sqrt() * max_deviation_ratio;
+    let mut result = Vec::with_capacity(points.len());
+    result.push(points[0]);
+
+    for &(px, py) in &points[1..points.len() - 1] {
+        let t = (((px - x1) * dx + (py - y1) * dy) / length_sq).clamp(0.0, 1.0);
+        let (qx, qy) = (x1 + t * dx, y1 + t * dy);
+        if (px - qx).hypot(py - qy) > max_deviation {
+            return Err("Way is too bendy to straighten".to_string());
+        }
+        result.push((qx, qy));
+    }
+
+    result.push(points[points.len() - 1]);
+    Ok(result)
+}
+
+/// 计算 Way（或其中 [from_index, to_index] 顶点区间）的拉直计划
+///
+/// 区间为空时拉直整条 Way；闭合 Way 必须指定区间
+pub fn plan_straighten(
+    store: &OsmStore,
+    way_id: i64,
+    range: Option<(usize, usize)>,
+    max_deviation_ratio: f64,
+) -> Result<StraightenPlan, String> {
+    let refs = store
+        .ways
+        .get(&way_id)
+        .map(|w| w.node_refs.clone())
+        .ok_or_else(|| "Way not found".to_string())?;
+
+    let is_closed = refs.len() > 2 && refs.first() == refs.last();
+    let (from, to) = match range {
+        Some((from, to)) if from < to && to < refs.len() => (from, to),
+        Some(_) => return Err("Invalid vertex range".to_string()),
+        None if is_closed => {
+            return Err("Select a vertex range to straighten a closed way".to_string())
+        }
+        None => (0, refs.len().saturating_sub(1)),
+    };
+    if to - from < 2 {
+        return Err("Nothing to straighten".to_string());
+    }
+
+    let mut nodes = Vec::with_capacity(to - from + 1);
+    for node_id in &refs[from..=to] {
+        let node = store
+            .nodes
+            .get(node_id)
+            .ok_or_else(|| format!("Node #{} not found", node_id))?;
+        nodes.push(node.clone());
+    }
+
+    let coords: Vec<(f64, f64)> = nodes
+        .iter()
+        .map(|n| lonlat_to_mercator(n.lon, n.lat))
+        .collect();
+    let straightened = straighten_points(&coords, max_deviation_ratio)?;
+
+    let mut moves = Vec::new();
+    let mut removed_vertices = Vec::new();
+
+    for (offset, node) in nodes.iter().enumerate().take(nodes.len() - 1).skip(1) {
+        if store.is_removable_vertex(node.id) {
+            removed_vertices.push((node.clone(), from + offset));
+            continue;
+        }
+
+        let (new_lon, new_lat) = mercator_to_lonlat(straightened[offset].0, straightened[offset].1);
+        moves.push(NodeMove {
+            node_id: node.id,
+            old_lon: node.lon,
+            old_lat: node.lat,
+            new_lon,
+            new_lat,
+        });
+    }
+
+    Ok(StraightenPlan {
+        moves,
+        removed_vertices,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inner_points_projected() {
+        let points = vec![(0.0, 0.0), (3.0, 0.5), (6.0, -0.4), (10.0, 0.0)];
+        let result = straighten_points(&points, DEFAULT_MAX_DEVIATION_RATIO).unwrap();
+
+        assert_eq!(result[0], (0.0, 0.0));
+        assert_eq!(result[3], (10.0, 0.0));
+        assert!((result[1].0 - 3.0).abs() < 1e-9 && result[1].1.abs() < 1e-9);
+        assert!((result[2].0 - 6.0).abs() < 1e-9 && result[2].1.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rejects_bendy_way() {
+        let points = vec![(0.0, 0.0), (5.0, 4.0), (10.0, 0.0)];
+        assert!(straighten_points(&points, DEFAULT_MAX_DEVIATION_RATIO).is_err());
+    }
+}
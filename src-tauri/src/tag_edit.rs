@@ -0,0 +1,528 @@
+//! 批量标签编辑
+//!
+//! 对多选集合中的 Node/Way/Relation 应用一组标签操作：
+//! - 设置 key=value
+//! - 删除 key
+//! - 重命名 key
+//! - 按值替换
+//!
+//! 以及全数据集范围的标签查找替换（键/值支持精确、前缀、正则匹配）。
+//!
+//! 只有标签实际发生变化的要素才会生成 TagChange；
+//! Way 的渲染属性 (render_feature/layer/is_area) 随新标签重新计算。
+
+use crate::history::{TagChange, WayStyle};
+use crate::osm_store::{MemberType, OsmStore};
+use crate::polygon_assembler;
+use crate::render_feature;
+use crate::types::FeatureSelection;
+use regex::Regex;
+use rstar::AABB;
+use std::collections::HashSet;
+
+/// 标签操作
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum TagOperation {
+    /// 设置标签（已存在则覆盖值）
+    Set { key: String, value: String },
+    /// 删除标签
+    Delete { key: String },
+    /// 重命名键（目标键已存在时被覆盖）
+    Rename { from: String, to: String },
+    /// 值等于 `from` 时替换为 `to`
+    ReplaceValue {
+        key: String,
+        from: String,
+        to: String,
+    },
+}
+
+impl TagOperation {
+    /// 对标签列表应用操作
+    pub fn apply(&self, tags: &mut Vec<(String, String)>) {
+        match self {
+            Self::Set { key, value } => match tags.iter_mut().find(|(k, _)| k == key) {
+                Some(tag) => tag.1 = value.clone(),
+                None => tags.push((key.clone(), value.clone())),
+            },
+            Self::Delete { key } => tags.retain(|(k, _)| k != key),
+            Self::Rename { from, to } => {
+                if from == to || !tags.iter().any(|(k, _)| k == from) {
+                    return;
+                }
+                tags.retain(|(k, _)| k != to);
+                if let Some(tag) = tags.iter_mut().find(|(k, _)| k == from) {
+                    tag.0 = to.clone();
+                }
+            }
+            Self::ReplaceValue { key, from, to } => {
+                if let Some(tag) = tags.iter_mut().find(|(k, v)| k == key && v == from) {
+                    tag.1 = to.clone();
+                }
+            }
+        }
+    }
+}
+
+/// 依次应用全部操作，返回新标签列表
+pub fn apply_operations(
+    tags: &[(String, String)],
+    operations: &[TagOperation],
+) -> Vec<(String, String)> {
+    let mut new_tags = tags.to_vec();
+    for operation in operations {
+        operation.apply(&mut new_tags);
+    }
+    new_tags
+}
+
+/// 根据标签计算 Way 的渲染属性
+pub fn way_style(tags: &[(String, String)], node_refs: &[i64]) -> WayStyle {
+    let parsed = render_feature::parse_tags(tags);
+    WayStyle {
+        render_feature: parsed.feature,
+        layer: parsed.layer,
+        is_area: polygon_assembler::is_area_way(tags, node_refs),
+    }
+}
+
+/// 为单个要素生成标签变更（标签未变化或要素不存在时返回 None）
+pub fn tag_change(
+    store: &OsmStore,
+    member_type: MemberType,
+    id: i64,
+    new_tags: impl FnOnce(&[(String, String)]) -> Vec<(String, String)>,
+) -> Option<TagChange> {
+    let (old_tags, node_refs, old_style) = match member_type {
+        MemberType::Node => (store.nodes.get(&id)?.tags.clone(), None, None),
+        MemberType::Way => {
+            let way = store.ways.get(&id)?;
+            let style = WayStyle {
+                render_feature: way.render_feature,
+                layer: way.layer,
+                is_area: way.is_area,
+            };
+            (way.tags.clone(), Some(way.node_refs.clone()), Some(style))
+        }
+        MemberType::Relation => (store.relations.get(&id)?.tags.clone(), None, None),
+    };
+
+    let new_tags = new_tags(&old_tags);
+    if new_tags == old_tags {
+        return None;
+    }
+    let new_style = node_refs.map(|refs| way_style(&new_tags, &refs));
+
+    Some(TagChange {
+        member_type,
+        id,
+        old_tags,
+        new_tags,
+        old_style,
+        new_style,
+    })
+}
+
+/// 计算选择集的批量标签变更
+pub fn plan_tag_changes(
+    store: &OsmStore,
+    selection: &FeatureSelection,
+    operations: &[TagOperation],
+) -> Vec<TagChange> {
+    let targets = selection
+        .node_ids
+        .iter()
+        .map(|&id| (MemberType::Node, id))
+        .chain(selection.way_ids.iter().map(|&id| (MemberType::Way, id)))
+        .chain(
+            selection
+                .relation_ids
+                .iter()
+                .map(|&id| (MemberType::Relation, id)),
+        );
+
+    let mut seen = HashSet::new();
+    targets
+        .filter(|target| seen.insert(*target))
+        .filter_map(|(member_type, id)| {
+            tag_change(store, member_type, id, |tags| {
+                apply_operations(tags, operations)
+            })
+        })
+        .collect()
+}
+
+/// 文本匹配方式
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+pub enum MatchMode {
+    Exact,
+    Prefix,
+    Regex,
+}
+
+/// 文本匹配条件
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct TextPattern {
+    pub mode: MatchMode,
+    pub pattern: String,
+}
+
+/// 查找替换条件
+///
+/// 替换文本的含义随匹配方式而定：精确匹配替换整个文本，前缀匹配只替换前缀，
+/// 正则匹配替换全部匹配部分（可使用 `$1` 等捕获组）
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FindReplace {
+    pub key: TextPattern,
+    /// 值匹配条件（None 表示任意值）
+    #[serde(default)]
+    pub value: Option<TextPattern>,
+    /// 新键（None 表示保留原键）
+    #[serde(default)]
+    pub replace_key: Option<String>,
+    /// 新值（None 表示保留原值）
+    #[serde(default)]
+    pub replace_value: Option<String>,
+}
+
+/// 查找范围
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum SearchScope {
+    /// 全部数据
+    All,
+    /// 与经纬度边界框相交的要素（Relation 需有成员位于框内）
+    Bounds {
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    },
+    /// 选择集
+    Selection { selection: FeatureSelection },
+}
+
+enum Matcher {
+    Exact(String),
+    Prefix(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn compile(pattern: &TextPattern) -> Result<Self, String> {
+        Ok(match pattern.mode {
+            MatchMode::Exact => Self::Exact(pattern.pattern.clone()),
+            MatchMode::Prefix => Self::Prefix(pattern.pattern.clone()),
+            MatchMode::Regex => Self::Regex(
+                Regex::new(&pattern.pattern).map_err(|e| format!("Invalid regex: {}", e))?,
+            ),
+        })
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            Self::Exact(pattern) => text == pattern,
+            Self::Prefix(prefix) => text.starts_with(prefix.as_str()),
+            Self::Regex(regex) => regex.is_match(text),
+        }
+    }
+
+    /// 替换文本中匹配的部分（调用前需确认已匹配）
+    fn replace(&self, text: &str, replacement: &str) -> String {
+        match self {
+            Self::Exact(_) => replacement.to_string(),
+            Self::Prefix(prefix) => format!("{}{}", replacement, &text[prefix.len()..]),
+            Self::Regex(regex) => regex.replace_all(text, replacement).into_owned(),
+        }
+    }
+}
+
+/// 编译后的查找替换条件
+pub struct TagReplacer {
+    key: Matcher,
+    value: Option<Matcher>,
+    replace_key: Option<String>,
+    replace_value: Option<String>,
+}
+
+impl TagReplacer {
+    pub fn new(find: &FindReplace) -> Result<Self, String> {
+        Ok(Self {
+            key: Matcher::compile(&find.key)?,
+            value: find.value.as_ref().map(Matcher::compile).transpose()?,
+            replace_key: find.replace_key.clone(),
+            replace_value: find.replace_value.clone(),
+        })
+    }
+
+    fn is_match(&self, key: &str, value: &str) -> bool {
+        self.key.is_match(key) && self.value.as_ref().is_none_or(|m| m.is_match(value))
+    }
+
+    /// 匹配的标签数
+    pub fn match_count(&self, tags: &[(String, String)]) -> usize {
+        tags.iter().filter(|(k, v)| self.is_match(k, v)).count()
+    }
+
+    /// 替换全部匹配的标签，返回新标签列表
+    ///
+    /// 替换后的键与未匹配的标签重名时覆盖后者；替换后键或值为空的标签被删除，
+    /// 未匹配的标签原样保留
+    pub fn apply(&self, tags: &[(String, String)]) -> Vec<(String, String)> {
+        let replaced: Vec<(String, String, bool)> = tags
+            .iter()
+            .map(|(k, v)| {
+                if !self.is_match(k, v) {
+                    return (k.clone(), v.clone(), false);
+                }
+                let key = match &self.replace_key {
+                    Some(replacement) => self.key.replace(k, replacement),
+                    None => k.clone(),
+                };
+                let value = match (&self.replace_value, &self.value) {
+                    (Some(replacement), Some(matcher)) => matcher.replace(v, replacement),
+                    (Some(replacement), None) => replacement.clone(),
+                    (None, _) => v.clone(),
+                };
+                (key, value, true)
+            })
+            .collect();
+
+        let renamed: HashSet<String> = replaced
+            .iter()
+            .filter(|(_, _, matched)| *matched)
+            .map(|(k, _, _)| k.clone())
+            .collect();
+        let mut seen = HashSet::new();
+        replaced
+            .into_iter()
+            .filter(|(k, v, matched)| {
+                (!*matched || (!k.is_empty() && !v.is_empty()))
+                    && (*matched || !renamed.contains(k))
+                    && seen.insert(k.clone())
+            })
+            .map(|(k, v, _)| (k, v))
+            .collect()
+    }
+}
+
+/// 查找替换计划
+pub struct FindReplacePlan {
+    /// 含有匹配标签的要素
+    pub matched: FeatureSelection,
+    /// 匹配的标签总数
+    pub tag_count: usize,
+    /// 标签实际发生变化的要素
+    pub changes: Vec<TagChange>,
+}
+
+/// 查找范围内的候选要素
+fn scope_targets(store: &OsmStore, scope: &SearchScope) -> Vec<(MemberType, i64)> {
+    let mut targets: Vec<(MemberType, i64)> = match scope {
+        SearchScope::All => store
+            .nodes
+            .iter()
+            .filter(|n| !n.tags.is_empty())
+            .map(|n| (MemberType::Node, n.id))
+            .chain(store.ways.iter().map(|w| (MemberType::Way, w.id)))
+            .chain(store.relations.iter().map(|r| (MemberType::Relation, r.id)))
+            .collect(),
+        SearchScope::Bounds {
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+        } => {
+            let node_ids: HashSet<i64> = store
+                .node_index()
+                .locate_in_envelope_intersecting(&AABB::from_corners(
+                    [*min_lon, *min_lat],
+                    [*max_lon, *max_lat],
+                ))
+                .map(|entry| entry.id)
+                .collect();
+            let way_ids: HashSet<i64> = store
+                .query_way_ids_in_viewport(*min_lon, *min_lat, *max_lon, *max_lat)
+                .into_iter()
+                .collect();
+            let relation_ids: Vec<_> = store
+                .relations
+                .iter()
+                .filter_map(|r| {
+                    r.members
+                        .iter()
+                        .any(|m| match m.member_type {
+                            MemberType::Node => node_ids.contains(&m.ref_id),
+                            MemberType::Way => way_ids.contains(&m.ref_id),
+                            MemberType::Relation => false,
+                        })
+                        .then_some((MemberType::Relation, r.id))
+                })
+                .collect();
+            node_ids
+                .iter()
+                .map(|&id| (MemberType::Node, id))
+                .chain(way_ids.iter().map(|&id| (MemberType::Way, id)))
+                .chain(relation_ids)
+                .collect()
+        }
+        SearchScope::Selection { selection } => selection
+            .node_ids
+            .iter()
+            .map(|&id| (MemberType::Node, id))
+            .chain(selection.way_ids.iter().map(|&id| (MemberType::Way, id)))
+            .chain(
+                selection
+                    .relation_ids
+                    .iter()
+                    .map(|&id| (MemberType::Relation, id)),
+            )
+            .collect(),
+    };
+    targets.sort_unstable_by_key(|&(member_type, id)| (member_type as u8, id));
+    targets.dedup();
+    targets
+}
+
+/// 在查找范围内查找匹配的标签并计算替换后的标签变更
+pub fn plan_find_replace(
+    store: &OsmStore,
+    find: &FindReplace,
+    scope: &SearchScope,
+) -> Result<FindReplacePlan, String> {
+    let replacer = TagReplacer::new(find)?;
+    let mut plan = FindReplacePlan {
+        matched: FeatureSelection::default(),
+        tag_count: 0,
+        changes: Vec::new(),
+    };
+
+    for (member_type, id) in scope_targets(store, scope) {
+        let count = match store.feature_tags(member_type, id) {
+            Some(tags) => replacer.match_count(&tags),
+            None => continue,
+        };
+        if count == 0 {
+            continue;
+        }
+        plan.matched.push(member_type, id);
+        plan.tag_count += count;
+        if let Some(change) = tag_change(store, member_type, id, |tags| replacer.apply(tags)) {
+            plan.changes.push(change);
+        }
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_operations() {
+        let original = tags(&[
+            ("highway", "residential"),
+            ("name", "Main"),
+            ("old_ref", "A1"),
+        ]);
+        let operations = vec![
+            TagOperation::Set {
+                key: "surface".to_string(),
+                value: "asphalt".to_string(),
+            },
+            TagOperation::Delete {
+                key: "name".to_string(),
+            },
+            TagOperation::Rename {
+                from: "old_ref".to_string(),
+                to: "ref".to_string(),
+            },
+            TagOperation::ReplaceValue {
+                key: "highway".to_string(),
+                from: "residential".to_string(),
+                to: "tertiary".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            apply_operations(&original, &operations),
+            tags(&[
+                ("highway", "tertiary"),
+                ("ref", "A1"),
+                ("surface", "asphalt")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_replace_value_requires_match() {
+        let original = tags(&[("highway", "primary")]);
+        let operation = TagOperation::ReplaceValue {
+            key: "highway".to_string(),
+            from: "residential".to_string(),
+            to: "tertiary".to_string(),
+        };
+        assert_eq!(apply_operations(&original, &[operation]), original);
+    }
+
+    #[test]
+    fn test_find_replace_modes() {
+        let original = tags(&[
+            ("highway", "residental"),
+            ("addr:street", "Old Street"),
+            ("addr:city", "Town"),
+        ]);
+        let find = |key: (MatchMode, &str), value: Option<(MatchMode, &str)>| FindReplace {
+            key: TextPattern {
+                mode: key.0,
+                pattern: key.1.to_string(),
+            },
+            value: value.map(|(mode, pattern)| TextPattern {
+                mode,
+                pattern: pattern.to_string(),
+            }),
+            replace_key: None,
+            replace_value: None,
+        };
+
+        let mut typo = find(
+            (MatchMode::Exact, "highway"),
+            Some((MatchMode::Exact, "residental")),
+        );
+        typo.replace_value = Some("residential".to_string());
+        let replacer = TagReplacer::new(&typo).unwrap();
+        assert_eq!(replacer.apply(&original)[0].1, "residential");
+
+        let mut prefix = find((MatchMode::Prefix, "addr:"), None);
+        prefix.replace_key = Some("contact:".to_string());
+        let replacer = TagReplacer::new(&prefix).unwrap();
+        assert_eq!(replacer.match_count(&original), 2);
+        assert_eq!(replacer.apply(&original)[2].0, "contact:city");
+
+        let mut rename = find(
+            (MatchMode::Exact, "addr:street"),
+            Some((MatchMode::Regex, "^Old (.+)$")),
+        );
+        rename.replace_value = Some("New $1".to_string());
+        let replacer = TagReplacer::new(&rename).unwrap();
+        assert_eq!(replacer.apply(&original)[1].1, "New Street");
+
+        // 替换为空值的匹配标签被删除，其他空值标签不受影响
+        let with_empty = tags(&[("note", ""), ("fixme", "check")]);
+        let mut clear = find((MatchMode::Exact, "fixme"), None);
+        clear.replace_value = Some(String::new());
+        let replacer = TagReplacer::new(&clear).unwrap();
+        assert_eq!(replacer.apply(&with_empty), tags(&[("note", "")]));
+
+        let invalid = find((MatchMode::Regex, "("), None);
+        assert!(TagReplacer::new(&invalid).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! 标签倒排索引
+//!
+//! key → value → 要素集合，用于按标签快速搜索。
+//! 随 `OsmStore::rebuild_indices` 全量构建；编辑命令执行、撤销、重做前后
+//! 由历史管理器移除并重新加入受影响要素的标签，保持索引与数据一致。
+//! key 与 value 字符串经驻留池共享，同一字符串（如 `yes`）在索引中只保存一份。
+
+use crate::osm_store::{MemberType, OsmStore};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// 索引中的要素
+pub type FeatureRef = (MemberType, i64);
+
+/// 标签倒排索引
+#[derive(Default)]
+pub struct TagIndex {
+    keys: HashMap<Arc<str>, HashMap<Arc<str>, HashSet<FeatureRef>>>,
+    /// 字符串驻留池
+    strings: HashSet<Arc<str>>,
+}
+
+impl TagIndex {
+    /// 由存储中的全部要素构建
+    pub fn build(store: &OsmStore) -> Self {
+        let mut index = Self::default();
+        for node in store.nodes.iter() {
+            index.insert((MemberType::Node, node.id), &node.tags);
+        }
+        for way in store.ways.iter() {
+            index.insert((MemberType::Way, way.id), &way.tags);
+        }
+        for relation in store.relations.iter() {
+            index.insert((MemberType::Relation, relation.id), &relation.tags);
+        }
+        index
+    }
+
+    pub fn insert(&mut self, feature: FeatureRef, tags: &[(String, String)]) {
+        for (key, value) in tags {
+            if let Some(features) = self
+                .keys
+                .get_mut(key.as_str())
+                .and_then(|values| values.get_mut(value.as_str()))
+            {
+                features.insert(feature);
+                continue;
+            }
+            let key = Self::intern(&mut self.strings, key);
+            let value = Self::intern(&mut self.strings, value);
+            self.keys
+                .entry(key)
+                .or_default()
+                .entry(value)
+                .or_default()
+                .insert(feature);
+        }
+    }
+
+    /// 取驻留池中的共享字符串，不存在时加入
+    fn intern(strings: &mut HashSet<Arc<str>>, s: &str) -> Arc<str> {
+        if let Some(shared) = strings.get(s) {
+            return shared.clone();
+        }
+        let shared: Arc<str> = Arc::from(s);
+        strings.insert(shared.clone());
+        shared
+    }
+
+    /// 字符串已不被索引引用时移出驻留池
+    fn release(strings: &mut HashSet<Arc<str>>, s: &str) {
+        if strings
+            .get(s)
+            .is_some_and(|shared| Arc::strong_count(shared) == 1)
+        {
+            strings.remove(s);
+        }
+    }
+
+    /// 移除要素的标签条目，并清理空的 value/key
+    pub fn remove(&mut self, feature: FeatureRef, tags: &[(String, String)]) {
+        for (key, value) in tags {
+            let Some(values) = self.keys.get_mut(key.as_str()) else {
+                continue;
+            };
+            if let Some(features) = values.get_mut(value.as_str()) {
+                features.remove(&feature);
+                if features.is_empty() {
+                    values.remove(value.as_str());
+                    Self::release(&mut self.strings, value);
+                }
+            }
+            if values.is_empty() {
+                self.keys.remove(key.as_str());
+                Self::release(&mut self.strings, key);
+            }
+        }
+    }
+
+    /// 全部已索引的 key
+    pub fn keys(&self) -> impl Iterator<Item = &Arc<str>> {
+        self.keys.keys()
+    }
+
+    /// 指定 key 的全部 value 及对应要素
+    pub fn values(&self, key: &str) -> impl Iterator<Item = (&Arc<str>, &HashSet<FeatureRef>)> {
+        self.keys.get(key).into_iter().flatten()
+    }
+
+    /// 索引中的 key（共享字符串）
+    pub fn key(&self, key: &str) -> Option<&Arc<str>> {
+        self.keys.get_key_value(key).map(|(key, _)| key)
+    }
+
+    /// 指定标签在索引中的共享 key、value 及对应要素
+    pub fn tag(
+        &self,
+        key: &str,
+        value: &str,
+    ) -> Option<(&Arc<str>, &Arc<str>, &HashSet<FeatureRef>)> {
+        let (key, values) = self.keys.get_key_value(key)?;
+        let (value, features) = values.get_key_value(value)?;
+        Some((key, value, features))
+    }
+
+    /// 带有指定标签的要素
+    pub fn features(&self, key: &str, value: &str) -> Option<&HashSet<FeatureRef>> {
+        self.keys.get(key)?.get(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{HistoryManager, UpdateNodeTagsCommand};
+    use crate::osm_store::OsmNode;
+
+    fn tags(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_index_follows_tag_edits() {
+        let store = OsmStore::new();
+        store.insert_node(OsmNode {
+            id: 1,
+            lat: 0.0,
+            lon: 0.0,
+            tags: tags(&[("amenity", "cafe"), ("name", "Corner")]),
+        });
+        store.rebuild_indices();
+        let cafe = (MemberType::Node, 1);
+        assert!(store
+            .tag_index()
+            .features("amenity", "cafe")
+            .unwrap()
+            .contains(&cafe));
+
+        let history = HistoryManager::new();
+        let command = UpdateNodeTagsCommand {
+            node_id: 1,
+            old_tags: tags(&[("amenity", "cafe"), ("name", "Corner")]),
+            new_tags: tags(&[("amenity", "restaurant")]),
+        };
+        assert!(history.execute(Box::new(command), &store).success);
+        {
+            let index = store.tag_index();
+            assert!(index.features("amenity", "cafe").is_none());
+            assert!(index
+                .features("amenity", "restaurant")
+                .unwrap()
+                .contains(&cafe));
+            assert!(!index.keys().any(|key| &**key == "name"));
+        }
+
+        assert!(history.undo(&store).success);
+        let index = store.tag_index();
+        assert!(index.features("amenity", "restaurant").is_none());
+        assert_eq!(index.values("name").count(), 1);
+    }
+
+    #[test]
+    fn test_strings_are_shared() {
+        let mut index = TagIndex::default();
+        let node = (MemberType::Node, 1);
+        let way = (MemberType::Way, 2);
+        index.insert(node, &tags(&[("building", "yes"), ("oneway", "yes")]));
+        index.insert(way, &tags(&[("building", "yes")]));
+
+        let building = index.values("building").next().unwrap().0;
+        let oneway = index.values("oneway").next().unwrap().0;
+        assert!(Arc::ptr_eq(building, oneway));
+        assert_eq!(index.strings.len(), 3);
+
+        index.remove(node, &tags(&[("building", "yes"), ("oneway", "yes")]));
+        assert_eq!(index.strings.len(), 2);
+        index.remove(way, &tags(&[("building", "yes")]));
+        assert!(index.strings.is_empty());
+        assert!(index.keys.is_empty());
+    }
+}
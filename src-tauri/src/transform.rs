@@ -0,0 +1,198 @@
+//! 批量几何变换 (Translate / Rotate / Scale)
+//!
+//! 对一组 Node/Way/Relation 进行整体变换：
+//! 1. 将选择集解析为去重后的节点集合（Relation 递归展开）
+//! 2. 在 Web 墨卡托空间（米）中计算变换，保证角度和比例不失真
+//! 3. 生成 NodeMove 列表，由 TransformCommand 批量提交
+
+use crate::history::NodeMove;
+use crate::osm_store::{MemberType, OsmStore};
+use crate::projection::{lonlat_to_mercator, mercator_to_lonlat};
+use crate::types::FeatureSelection;
+use std::collections::HashSet;
+
+/// 变换操作
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum TransformOperation {
+    /// 平移（墨卡托米）
+    Translate { dx: f64, dy: f64 },
+    /// 绕质心旋转（弧度，逆时针为正）
+    Rotate { angle: f64 },
+    /// 以质心为中心缩放
+    Scale { factor: f64 },
+}
+
+impl TransformOperation {
+    /// 操作描述（用于历史记录）
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Translate { dx, dy } => format!("Translate by ({:.2}m, {:.2}m)", dx, dy),
+            Self::Rotate { angle } => format!("Rotate by {:.2}°", angle.to_degrees()),
+            Self::Scale { factor } => format!("Scale by {:.3}", factor),
+        }
+    }
+}
+
+/// 将选择集解析为去重后的节点 ID 列表（保持首次出现顺序）
+///
+/// Relation 成员递归展开，已访问的 Relation 不会重复处理（避免环引用）
+pub fn resolve_selection_nodes(store: &OsmStore, selection: &FeatureSelection) -> Vec<i64> {
+    let mut seen: HashSet<i64> = HashSet::new();
+    let mut result: Vec<i64> = Vec::new();
+
+    let mut push_node = |node_id: i64, result: &mut Vec<i64>| {
+        if seen.insert(node_id) && store.nodes.contains_key(&node_id) {
+            result.push(node_id);
+        }
+    };
+
+    let mut way_ids: Vec<i64> = selection.way_ids.clone();
+    let mut visited_relations: HashSet<i64> = HashSet::new();
+    let mut relation_queue: Vec<i64> = selection.relation_ids.clone();
+
+    for &node_id in &selection.node_ids {
+        push_node(node_id, &mut result);
+    }
+
+    while let Some(relation_id) = relation_queue.pop() {
+        if !visited_relations.insert(relation_id) {
+            continue;
+        }
+        if let Some(relation) = store.relations.get(&relation_id) {
+            for member in &relation.members {
+                match member.member_type {
+                    MemberType::Node => push_node(member.ref_id, &mut result),
+                    MemberType::Way => way_ids.push(member.ref_id),
+                    MemberType::Relation => relation_queue.push(member.ref_id),
+                }
+            }
+        }
+    }
+
+    for way_id in way_ids {
+        if let Some(way) = store.ways.get(&way_id) {
+            for &node_id in &way.node_refs {
+                push_node(node_id, &mut result);
+            }
+        }
+    }
+
+    result
+}
+
+/// 计算节点集合在墨卡托空间的质心
+pub fn mercator_centroid(store: &OsmStore, node_ids: &[i64]) -> Option<(f64, f64)> {
+    let mut sum_x = 0.0;
+    let mut sum_y = 0.0;
+    let mut count = 0usize;
+
+    for node_id in node_ids {
+        if let Some(node) = store.nodes.get(node_id) {
+            let (x, y) = lonlat_to_mercator(node.lon, node.lat);
+            sum_x += x;
+            sum_y += y;
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        None
+    } else {
+        Some((sum_x / count as f64, sum_y / count as f64))
+    }
+}
+
+/// 对单个墨卡托坐标应用变换
+fn transform_point(op: &TransformOperation, center: (f64, f64), x: f64, y: f64) -> (f64, f64) {
+    match *op {
+        TransformOperation::Translate { dx, dy } => (x + dx, y + dy),
+        TransformOperation::Rotate { angle } => {
+            let (sin, cos) = angle.sin_cos();
+            let rx = x - center.0;
+            let ry = y - center.1;
+            (
+                center.0 + rx * cos - ry * sin,
+                center.1 + rx * sin + ry * cos,
+            )
+        }
+        TransformOperation::Scale { factor } => (
+            center.0 + (x - center.0) * factor,
+            center.1 + (y - center.1) * factor,
+        ),
+    }
+}
+
+/// 计算变换后的节点坐标
+///
+/// 返回 None 表示变换参数无效（缩放因子非正）或节点集合为空
+pub fn plan_transform(
+    store: &OsmStore,
+    node_ids: &[i64],
+    op: &TransformOperation,
+) -> Option<Vec<NodeMove>> {
+    if let TransformOperation::Scale { factor } = op {
+        if *factor <= 0.0 || !factor.is_finite() {
+            return None;
+        }
+    }
+
+    let center = mercator_centroid(store, node_ids)?;
+
+    let moves = node_ids
+        .iter()
+        .filter_map(|node_id| {
+            let node = store.nodes.get(node_id)?;
+            let (x, y) = lonlat_to_mercator(node.lon, node.lat);
+            let (nx, ny) = transform_point(op, center, x, y);
+            let (new_lon, new_lat) = mercator_to_lonlat(nx, ny);
+            Some(NodeMove {
+                node_id: *node_id,
+                old_lon: node.lon,
+                old_lat: node.lat,
+                new_lon,
+                new_lat,
+            })
+        })
+        .collect();
+
+    Some(moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_quarter_turn() {
+        let (x, y) = transform_point(
+            &TransformOperation::Rotate {
+                angle: std::f64::consts::FRAC_PI_2,
+            },
+            (10.0, 10.0),
+            20.0,
+            10.0,
+        );
+        assert!((x - 10.0).abs() < 1e-9);
+        assert!((y - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_scale_about_center() {
+        let (x, y) = transform_point(
+            &TransformOperation::Scale { factor: 2.0 },
+            (10.0, 10.0),
+            12.0,
+            7.0,
+        );
+        assert!((x - 14.0).abs() < 1e-9);
+        assert!((y - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_invalid_scale_rejected() {
+        let store = OsmStore::new();
+        let op = TransformOperation::Scale { factor: 0.0 };
+        assert!(plan_transform(&store, &[1], &op).is_none());
+    }
+}
@@ -2,7 +2,10 @@
 //!
 //! 集中管理跨模块共享的数据传输对象 (DTO)
 
-use serde::Serialize;
+use crate::changes::ChangeStatus;
+use crate::osm_store::{DataBounds, MemberType};
+use crate::restriction::RestrictionIssue;
+use serde::{Deserialize, Serialize};
 
 /// 所属关系信息
 #[derive(Serialize, Clone)]
@@ -36,12 +39,34 @@ pub struct WayDetails {
     pub parent_relations: Vec<ParentRelation>,
 }
 
+/// Relation 成员详情
+#[derive(Serialize)]
+pub struct RelationMemberDetails {
+    pub member_type: MemberType,
+    pub ref_id: i64,
+    pub role: String,
+    /// 解析后的显示名称 (name > ref > "Type #id")
+    pub display_name: String,
+    /// 成员要素是否存在于当前数据中（可能位于加载范围之外）
+    pub loaded: bool,
+}
+
+/// Relation 详情
+#[derive(Serialize)]
+pub struct RelationDetails {
+    pub id: i64,
+    pub tags: Vec<(String, String)>,
+    pub members: Vec<RelationMemberDetails>,
+    pub parent_relations: Vec<ParentRelation>,
+}
+
 /// 要素详情
 #[derive(Serialize)]
 #[serde(tag = "type")]
 pub enum FeatureDetails {
     Node(NodeDetails),
     Way(WayDetails),
+    Relation(RelationDetails),
     NotFound,
 }
 
@@ -54,6 +79,41 @@ pub struct UpdateTagsResult {
     pub is_area: bool,
 }
 
+/// 批量标签编辑中单个要素的结果
+#[derive(Serialize)]
+pub struct TagEditChange {
+    pub member_type: MemberType,
+    pub id: i64,
+    /// Node/Relation 固定为 0
+    pub render_feature: u16,
+    pub layer: i8,
+    pub is_area: bool,
+    /// 渲染属性是否改变（前端据此选择性重绘）
+    pub render_changed: bool,
+}
+
+/// 批量标签编辑结果
+#[derive(Serialize)]
+pub struct BatchTagEditResult {
+    pub success: bool,
+    pub message: Option<String>,
+    /// 标签实际发生变化的要素
+    pub changes: Vec<TagEditChange>,
+}
+
+/// 标签查找替换结果
+#[derive(Serialize)]
+pub struct FindReplaceResult {
+    pub success: bool,
+    pub message: Option<String>,
+    /// 含有匹配标签的要素
+    pub matched: FeatureSelection,
+    /// 匹配的标签总数
+    pub tag_count: usize,
+    /// 标签将要（dry run）或已经发生变化的要素
+    pub changes: Vec<TagEditChange>,
+}
+
 /// Undo/Redo 操作结果
 #[derive(Serialize)]
 pub struct UndoRedoResult {
@@ -64,11 +124,139 @@ pub struct UndoRedoResult {
     pub redo_count: usize,
 }
 
+/// 历史记录状态
+#[derive(Serialize)]
+pub struct HistoryState {
+    pub undo_count: usize,
+    pub redo_count: usize,
+    /// 历史记录的估算内存（字节）
+    pub memory_bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    /// 因超出容量被裁剪的条目数
+    pub trimmed_count: usize,
+    /// 被裁剪条目影响的要素（无法再撤销，但仍属于变更集）
+    pub trimmed_entities: FeatureSelection,
+}
+
+/// 历史记录条目信息
+#[derive(Serialize)]
+pub struct HistoryEntryInfo {
+    /// 在所属列表中的位置（用于 undo_to / redo_to）
+    pub index: usize,
+    pub description: String,
+    /// 执行时间（Unix 毫秒）
+    pub timestamp: u64,
+    /// 受影响的要素
+    pub affected: FeatureSelection,
+}
+
+/// 历史记录列表
+#[derive(Serialize)]
+pub struct HistoryEntries {
+    /// 可撤销条目（从旧到新）
+    pub undo: Vec<HistoryEntryInfo>,
+    /// 可重做条目（第 0 条为下一个将被重做的条目）
+    pub redo: Vec<HistoryEntryInfo>,
+}
+
+/// 多步撤销/重做结果
+#[derive(Serialize)]
+pub struct HistoryJumpResult {
+    pub success: bool,
+    pub needs_redraw: bool,
+    pub message: Option<String>,
+    pub undo_count: usize,
+    pub redo_count: usize,
+    /// 实际完成的步数
+    pub steps: usize,
+    /// 受影响要素的合并边界框（用于定位变更）
+    pub bounds: Option<DataBounds>,
+}
+
+/// 可恢复的遗留日志信息
+#[derive(Serialize)]
+pub struct JournalRecovery {
+    /// 源文件路径
+    pub source: String,
+    /// 日志创建时间（Unix 毫秒）
+    pub created: u64,
+    /// 日志中的事件数（执行、撤销、重做等）
+    pub event_count: usize,
+}
+
+/// 日志重放结果
+#[derive(Serialize)]
+pub struct JournalReplayResult {
+    pub success: bool,
+    pub needs_redraw: bool,
+    pub message: Option<String>,
+    /// 成功重放的事件数
+    pub replayed: usize,
+    pub undo_count: usize,
+    pub redo_count: usize,
+}
+
+/// 单类要素的变更计数
+#[derive(Serialize, Default)]
+pub struct ChangeCounts {
+    pub created: usize,
+    pub modified: usize,
+    pub deleted: usize,
+}
+
+/// 变更集中的单个要素
+#[derive(Serialize)]
+pub struct ChangeEntry {
+    pub member_type: MemberType,
+    pub id: i64,
+    pub status: ChangeStatus,
+    pub tags_changed: bool,
+    /// Node 坐标或 Way 节点序列被修改
+    pub geometry_changed: bool,
+    pub members_changed: bool,
+    /// 边界框（已删除的要素使用删除前的几何）
+    pub bounds: Option<DataBounds>,
+}
+
+/// 变更集摘要（相对于加载时的数据）
+#[derive(Serialize)]
+pub struct ChangesSummary {
+    pub nodes: ChangeCounts,
+    pub ways: ChangeCounts,
+    pub relations: ChangeCounts,
+    /// 已变更要素总数
+    pub total: usize,
+    /// 当前页的要素（按 Node/Way/Relation、ID 排序）
+    pub entries: Vec<ChangeEntry>,
+}
+
+/// 恢复要素结果
+#[derive(Serialize)]
+pub struct RevertResult {
+    pub success: bool,
+    pub message: Option<String>,
+    /// 被恢复、重新创建或删除的要素（包括级联的节点和父 Relation）
+    pub affected: FeatureSelection,
+}
+
+/// 节点吸附目标
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub enum SnapTarget {
+    /// 合并到已有节点
+    Node { node_id: i64 },
+    /// 作为顶点插入 Way（index 为在节点序列中的插入位置）
+    Way { way_id: i64, index: usize },
+}
+
 /// 移动节点结果
 #[derive(Serialize)]
 pub struct MoveNodeResult {
     pub success: bool,
     pub message: Option<String>,
+    /// 吸附目标（吸附到节点时被移动的节点已合并到该节点）
+    pub snapped: Option<SnapTarget>,
 }
 
 /// 添加节点结果
@@ -77,6 +265,17 @@ pub struct AddNodeResult {
     pub success: bool,
     pub node_id: i64,
     pub message: Option<String>,
+    /// 吸附目标（吸附到节点时 node_id 为该已有节点）
+    pub snapped: Option<SnapTarget>,
+}
+
+/// 附着节点结果
+#[derive(Serialize)]
+pub struct AttachNodeResult {
+    pub success: bool,
+    pub message: Option<String>,
+    /// 节点被插入的 Way
+    pub way_id: Option<i64>,
 }
 
 /// 删除要素结果
@@ -86,4 +285,191 @@ pub struct DeleteFeatureResult {
     pub message: Option<String>,
     /// 级联删除的 Way ID 列表（删除 Node 时可能级联删除 Way）
     pub cascaded_way_ids: Vec<i64>,
+    /// 成员被移除的 Relation ID 列表
+    pub affected_relation_ids: Vec<i64>,
+    /// 因成员为空或无效而级联删除的 Relation ID 列表
+    pub cascaded_relation_ids: Vec<i64>,
+}
+
+/// Relation 编辑结果
+#[derive(Serialize)]
+pub struct RelationEditResult {
+    pub success: bool,
+    pub relation_id: i64,
+    pub message: Option<String>,
+}
+
+/// 转向限制的 from/via/to
+///
+/// `from_side` / `to_side` 为 from/to Way 上 via 以外的任一节点，
+/// 仅在 Way 穿过 via、需要拆分时用于确定转向的一侧
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct TurnRestrictionLegs {
+    pub from_way: i64,
+    pub from_side: Option<i64>,
+    pub via_type: MemberType,
+    pub via_id: i64,
+    pub to_way: i64,
+    pub to_side: Option<i64>,
+}
+
+/// 路口处的转向限制
+#[derive(Serialize)]
+pub struct TurnRestrictionInfo {
+    pub relation_id: i64,
+    /// restriction 取值（含 `restriction:*` 条件限制）
+    pub restriction: Option<String>,
+    pub from_way_ids: Vec<i64>,
+    pub via_node_id: Option<i64>,
+    pub via_way_ids: Vec<i64>,
+    pub to_way_ids: Vec<i64>,
+    /// 是否有效（issues 为空）
+    pub valid: bool,
+    pub issues: Vec<RestrictionIssue>,
+}
+
+/// 搜索命中的要素
+#[derive(Serialize)]
+pub struct SearchHit {
+    pub member_type: MemberType,
+    pub id: i64,
+    pub name: Option<String>,
+    /// 命中的标签
+    pub matched_tag: (String, String),
+    /// 代表位置（包围盒中心）
+    pub lon: Option<f64>,
+    pub lat: Option<f64>,
+    /// 与视口中心的距离（米）
+    pub distance_meters: Option<f64>,
+}
+
+/// 要素搜索结果
+#[derive(Serialize)]
+pub struct SearchFeaturesResult {
+    pub success: bool,
+    pub message: Option<String>,
+    /// 匹配的要素总数（分页前）
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+}
+
+/// 包含某点的面
+#[derive(Serialize)]
+pub struct AreaAtPointInfo {
+    pub member_type: MemberType,
+    pub id: i64,
+    pub tags: Vec<(String, String)>,
+    /// 行政级别（`admin_level` 标签）
+    pub admin_level: Option<u8>,
+    /// 近似面积（平方米）
+    pub area_sq_meters: f64,
+}
+
+/// Overpass 查询结果
+#[derive(Serialize)]
+pub struct OverpassQueryResult {
+    pub success: bool,
+    pub message: Option<String>,
+    /// 全部 `out` 语句输出的要素数（含 `out count`）
+    pub count: usize,
+    /// 输出的要素（`out count` 不计入），可直接用于选择
+    pub selection: FeatureSelection,
+    /// 请求时按各 `out` 语句的输出模式生成的 FeatureCollection
+    pub geojson: Option<serde_json::Value>,
+}
+
+/// 要素选择集（用于批量操作）
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct FeatureSelection {
+    pub node_ids: Vec<i64>,
+    pub way_ids: Vec<i64>,
+    pub relation_ids: Vec<i64>,
+}
+
+impl FeatureSelection {
+    pub fn push(&mut self, member_type: MemberType, id: i64) {
+        match member_type {
+            MemberType::Node => self.node_ids.push(id),
+            MemberType::Way => self.way_ids.push(id),
+            MemberType::Relation => self.relation_ids.push(id),
+        }
+    }
+
+    /// 按 Node、Way、Relation 顺序遍历全部要素
+    pub fn iter(&self) -> impl Iterator<Item = (MemberType, i64)> + '_ {
+        let nodes = self.node_ids.iter().map(|&id| (MemberType::Node, id));
+        let ways = self.way_ids.iter().map(|&id| (MemberType::Way, id));
+        let relations = self
+            .relation_ids
+            .iter()
+            .map(|&id| (MemberType::Relation, id));
+        nodes.chain(ways).chain(relations)
+    }
+
+    pub fn extend(&mut self, other: FeatureSelection) {
+        self.node_ids.extend(other.node_ids);
+        self.way_ids.extend(other.way_ids);
+        self.relation_ids.extend(other.relation_ids);
+    }
+
+    /// 排序并去重
+    pub fn dedup(&mut self) {
+        for ids in [&mut self.node_ids, &mut self.way_ids, &mut self.relation_ids] {
+            ids.sort_unstable();
+            ids.dedup();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.node_ids.is_empty() && self.way_ids.is_empty() && self.relation_ids.is_empty()
+    }
+}
+
+/// 复制结果
+#[derive(Serialize)]
+pub struct CopyResult {
+    pub success: bool,
+    pub message: Option<String>,
+    pub node_count: usize,
+    pub way_count: usize,
+    pub relation_count: usize,
+}
+
+/// 粘贴结果（新要素的本地 ID）
+#[derive(Serialize)]
+pub struct PasteResult {
+    pub success: bool,
+    pub message: Option<String>,
+    pub node_ids: Vec<i64>,
+    pub way_ids: Vec<i64>,
+    pub relation_ids: Vec<i64>,
+}
+
+/// 批量几何编辑结果
+#[derive(Serialize)]
+pub struct GeometryEditResult {
+    pub success: bool,
+    pub message: Option<String>,
+    /// 被移动的节点数量
+    pub moved_node_count: usize,
+    /// 被删除的冗余节点数量
+    pub removed_node_count: usize,
+}
+
+/// 简化后单条 Way 的预览几何
+#[derive(Serialize)]
+pub struct SimplifiedWayPreview {
+    pub way_id: i64,
+    pub original_node_count: usize,
+    pub simplified_node_count: usize,
+    /// 简化后的墨卡托坐标序列
+    pub coords: Vec<(f64, f64)>,
+}
+
+/// 简化预览结果（不提交）
+#[derive(Serialize)]
+pub struct SimplifyPreview {
+    pub ways: Vec<SimplifiedWayPreview>,
+    pub removed_node_count: usize,
 }
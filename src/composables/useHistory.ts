@@ -79,9 +79,9 @@ export function useHistory(options: UseHistoryOptions = {}) {
 
   const refreshHistoryState = async () => {
     try {
-      const [undo, redo] = await getHistoryState()
-      undoCount.value = undo
-      redoCount.value = redo
+      const state = await getHistoryState()
+      undoCount.value = state.undo_count
+      redoCount.value = state.redo_count
     } catch (error) {
       console.error('获取历史状态失败:', error)
     }
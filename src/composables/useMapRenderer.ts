@@ -16,9 +16,10 @@ import {
   queryViewportFull,
   decodeViewportResponseV2,
   pickFeature,
+  getRelationDetails,
   moveNode,
   type Viewport,
-  type PickedFeature,
+  type PickCandidate,
 } from '../core/ipc-bridge'
 
 export function useMapRenderer(canvasRef: () => HTMLCanvasElement | null) {
@@ -45,6 +46,14 @@ export function useMapRenderer(canvasRef: () => HTMLCanvasElement | null) {
   const drawMode = ref<'none' | 'node'>('none')
   let onDrawClickCallback: ((mercX: number, mercY: number) => void) | null = null
 
+  // 上一次拾取的位置和候选，用于在同一位置重复点击时切换重叠的要素
+  let lastPick: {
+    mercX: number
+    mercY: number
+    candidates: PickCandidate[]
+    index: number
+  } | null = null
+
   let statsInterval: ReturnType<typeof setInterval> | null = null
   let debounceTimer: ReturnType<typeof setTimeout> | null = null
 
@@ -67,8 +76,8 @@ export function useMapRenderer(canvasRef: () => HTMLCanvasElement | null) {
 
       isPicking.value = true
       try {
-        const result = await pickFeature(mercX, mercY, toleranceMeters, zoom)
-        handlePickResult(result)
+        const candidates = await pickFeature(mercX, mercY, toleranceMeters, zoom)
+        handlePickResult(mercX, mercY, toleranceMeters, candidates)
       } catch (error) {
         console.error('拾取要素失败:', error)
       } finally {
@@ -111,23 +120,67 @@ export function useMapRenderer(canvasRef: () => HTMLCanvasElement | null) {
     }, 200)
   }
 
-  const handlePickResult = (result: PickedFeature) => {
-    if (result.type === 'None') {
+  const sameCandidates = (a: PickCandidate[], b: PickCandidate[]) =>
+    a.length === b.length &&
+    a.every((c, i) => c.member_type === b[i].member_type && c.id === b[i].id)
+
+  /** 在同一位置（容差范围内）重复点击且候选不变时，依次选中下一个候选 */
+  const handlePickResult = (
+    mercX: number,
+    mercY: number,
+    toleranceMeters: number,
+    candidates: PickCandidate[],
+  ) => {
+    const isRepeat =
+      lastPick !== null &&
+      Math.hypot(mercX - lastPick.mercX, mercY - lastPick.mercY) <= toleranceMeters &&
+      sameCandidates(lastPick.candidates, candidates)
+    const index = isRepeat ? (lastPick!.index + 1) % candidates.length : 0
+    const candidate = candidates[index]
+
+    if (!candidate) {
+      lastPick = null
       selectedFeature.value = null
       renderer.value?.clearSelection()
       console.log('未选中任何要素')
     } else {
+      lastPick = { mercX, mercY, candidates, index }
       const feature: SelectedFeature = {
-        type: result.type === 'Node' ? 'node' : 'way',
-        id: result.id!,
+        type: candidate.member_type,
+        id: candidate.id,
       }
       selectedFeature.value = feature
       renderer.value?.setSelectedFeature(feature)
-      console.log(`选中 ${feature.type}: ${feature.id}`)
+      console.log(`选中 ${feature.type}: ${feature.id} (${index + 1}/${candidates.length})`)
+      if (feature.type === 'relation') {
+        highlightRelationMembers(feature)
+      }
+    }
+  }
+
+  /** 取 Relation 的直接成员 Way 交给渲染器高亮（只更新渲染器，不改变选中状态） */
+  const highlightRelationMembers = async (feature: SelectedFeature) => {
+    try {
+      const details = await getRelationDetails(feature.id)
+      const current = selectedFeature.value
+      if (
+        details.type !== 'Relation' ||
+        current?.type !== 'relation' ||
+        current.id !== feature.id
+      ) {
+        return
+      }
+      const memberWayIds = details.members
+        .filter((m) => m.member_type === 'way')
+        .map((m) => m.ref_id)
+      renderer.value?.setSelectedFeature({ ...feature, memberWayIds })
+    } catch (error) {
+      console.error('获取关系成员失败:', error)
     }
   }
 
   const clearSelection = () => {
+    lastPick = null
     selectedFeature.value = null
     renderer.value?.clearSelection()
   }
@@ -7,7 +7,10 @@
 import type { NodeData, ResponseHeader, ViewportData } from './types'
 
 const HEADER_SIZE = 16
-const NODE_SIZE = 32 // node_id(8) + x(8) + y(8) + ref_count(2) + pad(2) + pad2(4)
+const NODE_SIZE = 32 // node_id(8) + x(8) + y(8) + ref_count(2) + flags(2) + pad2(4)
+
+/** 节点标记：已编辑 (与 Rust binary_protocol.rs 同步) */
+const NODE_FLAG_CHANGED = 0x0001
 
 /** 解码响应头 */
 export function decodeHeader(buffer: ArrayBuffer): ResponseHeader {
@@ -25,7 +28,7 @@ export function decodeHeader(buffer: ArrayBuffer): ResponseHeader {
  *
  * V4 响应格式:
  * - Header (16 bytes): node_count, way_count, polygon_count, truncated
- * - Nodes: node_count * 32 bytes (node_id: i64, x: f64, y: f64, ref_count: u16, padding: 6 bytes)
+ * - Nodes: node_count * 32 bytes (node_id: i64, x: f64, y: f64, ref_count: u16, flags: u16, padding: 4 bytes)
  * - Way geometry: [total_ways: u32][way_id: i64][render_feature: u16][point_count: u32][coords...]...
  * - Polygon geometry: [total_polygons: u32][render_feature: u16][ring_count: u16][point_count: u32][coords...]...
  */
@@ -36,7 +39,7 @@ export function decodeViewportResponse(buffer: ArrayBuffer): ViewportData {
   const nodes: NodeData[] = []
   let offset = HEADER_SIZE
 
-  // Node 格式: [node_id: i64][x: f64][y: f64][ref_count: u16][padding: 6 bytes]
+  // Node 格式: [node_id: i64][x: f64][y: f64][ref_count: u16][flags: u16][padding: 4 bytes]
   for (let i = 0; i < header.nodeCount; i++) {
     const nodeIdLow = view.getUint32(offset, true)
     const nodeIdHigh = view.getInt32(offset + 4, true)
@@ -47,6 +50,7 @@ export function decodeViewportResponse(buffer: ArrayBuffer): ViewportData {
       x: view.getFloat64(offset + 8, true),
       y: view.getFloat64(offset + 16, true),
       refCount: view.getUint16(offset + 24, true),
+      changed: (view.getUint16(offset + 26, true) & NODE_FLAG_CHANGED) !== 0,
     })
     offset += NODE_SIZE
   }
@@ -12,14 +12,17 @@ export type {
   DataBounds,
   DeleteFeatureResult,
   FeatureDetails,
+  HistoryState,
   MoveNodeResult,
   NodeData,
   NodeDetails,
   NotFound,
   ParentRelation,
   ParseProgress,
-  PickedFeature,
+  PickCandidate,
+  PickHit,
   ResponseHeader,
+  SnapTarget,
   StoreStats,
   UndoRedoResult,
   UpdateTagsResult,
@@ -42,9 +45,10 @@ import type {
   DataBounds,
   DeleteFeatureResult,
   FeatureDetails,
+  HistoryState,
   MoveNodeResult,
   ParseProgress,
-  PickedFeature,
+  PickCandidate,
   StoreStats,
   UndoRedoResult,
   UpdateTagsResult,
@@ -93,7 +97,7 @@ export async function queryViewportFull(viewport: Viewport): Promise<Uint8Array>
 }
 
 /**
- * 在指定墨卡托坐标位置拾取最近的要素
+ * 在指定墨卡托坐标位置拾取候选要素（按优先级排列）
  *
  * @param mercX 墨卡托 X 坐标（米）
  * @param mercY 墨卡托 Y 坐标（米）
@@ -105,8 +109,8 @@ export async function pickFeature(
   mercY: number,
   toleranceMeters: number,
   zoom: number,
-): Promise<PickedFeature> {
-  return await invoke<PickedFeature>('pick_feature', {
+): Promise<PickCandidate[]> {
+  return await invoke<PickCandidate[]>('pick_feature', {
     mercX,
     mercY,
     toleranceMeters,
@@ -128,6 +132,11 @@ export async function getWayDetails(wayId: number): Promise<FeatureDetails> {
   return await invoke<FeatureDetails>('get_way_details', { wayId })
 }
 
+/** 获取关系详情 */
+export async function getRelationDetails(relationId: number): Promise<FeatureDetails> {
+  return await invoke<FeatureDetails>('get_relation_details', { relationId })
+}
+
 // ============================================================================
 // 编辑命令
 // ============================================================================
@@ -160,20 +169,41 @@ export async function updateNodeTags(
   return await invoke<UpdateTagsResult>('update_node_tags', { nodeId, newTags })
 }
 
+/**
+ * 更新关系标签
+ *
+ * @param relationId 关系 ID
+ * @param newTags 新的标签数组
+ * @returns 更新结果
+ */
+export async function updateRelationTags(
+  relationId: number,
+  newTags: [string, string][],
+): Promise<UpdateTagsResult> {
+  return await invoke<UpdateTagsResult>('update_relation_tags', { relationId, newTags })
+}
+
 /**
  * 移动节点
  *
  * @param nodeId 节点 ID
  * @param newMercX 新的墨卡托 X 坐标（米）
  * @param newMercY 新的墨卡托 Y 坐标（米）
- * @returns 移动结果
+ * @param snapToleranceMeters 吸附容差（米），为空时不吸附
+ * @returns 移动结果，包含吸附目标
  */
 export async function moveNode(
   nodeId: number,
   newMercX: number,
   newMercY: number,
+  snapToleranceMeters?: number,
 ): Promise<MoveNodeResult> {
-  return await invoke<MoveNodeResult>('move_node', { nodeId, newMercX, newMercY })
+  return await invoke<MoveNodeResult>('move_node', {
+    nodeId,
+    newMercX,
+    newMercY,
+    snapToleranceMeters: snapToleranceMeters ?? null,
+  })
 }
 
 /**
@@ -181,10 +211,19 @@ export async function moveNode(
  *
  * @param mercX 墨卡托 X 坐标（米）
  * @param mercY 墨卡托 Y 坐标（米）
- * @returns 添加结果，包含新节点 ID
+ * @param snapToleranceMeters 吸附容差（米），为空时不吸附
+ * @returns 添加结果，包含新节点 ID 和吸附目标
  */
-export async function addNode(mercX: number, mercY: number): Promise<AddNodeResult> {
-  return await invoke<AddNodeResult>('add_node', { mercX, mercY })
+export async function addNode(
+  mercX: number,
+  mercY: number,
+  snapToleranceMeters?: number,
+): Promise<AddNodeResult> {
+  return await invoke<AddNodeResult>('add_node', {
+    mercX,
+    mercY,
+    snapToleranceMeters: snapToleranceMeters ?? null,
+  })
 }
 
 /**
@@ -207,6 +246,16 @@ export async function deleteNode(nodeId: number): Promise<DeleteFeatureResult> {
   return await invoke<DeleteFeatureResult>('delete_node', { nodeId })
 }
 
+/**
+ * 删除关系（失去全部成员的上级关系随之删除）
+ *
+ * @param relationId 关系 ID
+ * @returns 删除结果，包含级联删除的关系 ID 列表
+ */
+export async function deleteRelation(relationId: number): Promise<DeleteFeatureResult> {
+  return await invoke<DeleteFeatureResult>('delete_relation', { relationId })
+}
+
 // ============================================================================
 // 历史命令
 // ============================================================================
@@ -229,8 +278,8 @@ export async function redo(): Promise<UndoRedoResult> {
 
 /**
  * 获取历史记录状态
- * @returns [undo_count, redo_count]
+ * @returns 条目数、内存占用和容量限制
  */
-export async function getHistoryState(): Promise<[number, number]> {
-  return await invoke<[number, number]>('get_history_state')
+export async function getHistoryState(): Promise<HistoryState> {
+  return await invoke<HistoryState>('get_history_state')
 }
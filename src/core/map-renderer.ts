@@ -30,8 +30,10 @@ export interface RenderStats {
 
 /** 选中的要素 */
 export interface SelectedFeature {
-  type: 'node' | 'way'
+  type: 'node' | 'way' | 'relation'
   id: number
+  /** 选中 Relation 时其直接成员 Way 的 ID，用于高亮成员 */
+  memberWayIds?: number[]
 }
 
 /** 样式配置 */
@@ -51,7 +53,7 @@ const DEFAULT_STYLE: RenderStyle = {
   backgroundColor: '#1e1e1e',
 }
 
-import { resolveStyle, type ResolvedStyle } from './render-styles'
+import { Flags, hasFlag, resolveStyle, type ResolvedStyle } from './render-styles'
 
 const DEG_TO_RAD = Math.PI / 180
 
@@ -505,7 +507,9 @@ export class MapRenderer {
 
     // 收集所有 Polygon 数据用于后续高亮
     interface PolygonData {
+      /** Way ID，Relation 组装的面为 Relation ID */
       wayId: number
+      fromRelation: boolean
       renderFeature: number
       rings: Array<Array<{ x: number; y: number }>>
     }
@@ -554,7 +558,12 @@ export class MapRenderer {
       }
 
       // 保存 Polygon 数据
-      polygons.push({ wayId, renderFeature, rings })
+      polygons.push({
+        wayId,
+        fromRelation: hasFlag(renderFeature, Flags.RELATION),
+        renderFeature,
+        rings,
+      })
 
       // === Clip 魔法渲染 ===
       ctx.save()
@@ -603,9 +612,13 @@ export class MapRenderer {
       ctx.restore() // 清除裁剪区域
     }
 
-    // 高亮选中的 Polygon
-    if (this.selectedFeature?.type === 'way') {
-      const selectedPolygon = polygons.find((p) => p.wayId === this.selectedFeature!.id)
+    // 高亮选中的 Polygon（Way 与 Relation 的 ID 可能相同，按来源区分）
+    const selected = this.selectedFeature
+    if (selected?.type === 'way' || selected?.type === 'relation') {
+      const fromRelation = selected.type === 'relation'
+      const selectedPolygon = polygons.find(
+        (p) => p.wayId === selected.id && p.fromRelation === fromRelation,
+      )
       if (selectedPolygon) {
         ctx.save()
 
@@ -904,39 +917,46 @@ export class MapRenderer {
       ctx.stroke()
     }
 
-    // 第四遍：高亮选中的 Way（最后绘制，确保在顶层）
-    if (this.selectedFeature?.type === 'way') {
-      const selectedWay = ways.find((w) => w.wayId === this.selectedFeature!.id)
-      if (selectedWay) {
-        // 绘制高亮边框
-        ctx.beginPath()
-        ctx.strokeStyle = '#00ffff' // 亮青色
-        ctx.lineWidth = Math.max(3, (selectedWay.style.width + 4) * zoomFactor)
-        ctx.lineCap = 'round'
-        ctx.lineJoin = 'round'
-        ctx.setLineDash([])
-        ctx.globalAlpha = 0.7
+    // 第四遍：高亮选中的 Way 或 Relation 的成员 Way（最后绘制，确保在顶层）
+    const selected = this.selectedFeature
+    let selectedWayIds: Set<number> | null = null
+    if (selected?.type === 'way') {
+      selectedWayIds = new Set([selected.id])
+    } else if (selected?.type === 'relation') {
+      selectedWayIds = new Set(selected.memberWayIds ?? [])
+    }
+    const selectedWays = selectedWayIds
+      ? ways.filter((w) => selectedWayIds!.has(w.wayId))
+      : []
+    for (const selectedWay of selectedWays) {
+      // 绘制高亮边框
+      ctx.beginPath()
+      ctx.strokeStyle = '#00ffff' // 亮青色
+      ctx.lineWidth = Math.max(3, (selectedWay.style.width + 4) * zoomFactor)
+      ctx.lineCap = 'round'
+      ctx.lineJoin = 'round'
+      ctx.setLineDash([])
+      ctx.globalAlpha = 0.7
 
-        for (let i = 0; i < selectedWay.points.length; i++) {
-          const { x, y } = selectedWay.points[i]
-          if (i === 0) ctx.moveTo(x, y)
-          else ctx.lineTo(x, y)
-        }
-        ctx.stroke()
+      for (let i = 0; i < selectedWay.points.length; i++) {
+        const { x, y } = selectedWay.points[i]
+        if (i === 0) ctx.moveTo(x, y)
+        else ctx.lineTo(x, y)
+      }
+      ctx.stroke()
 
-        // 绘制内线（保持原始样式）
-        ctx.beginPath()
-        ctx.strokeStyle = selectedWay.style.color
-        ctx.lineWidth = Math.max(1, selectedWay.style.width * zoomFactor)
-        ctx.globalAlpha = 1.0
-
-        for (let i = 0; i < selectedWay.points.length; i++) {
-          const { x, y } = selectedWay.points[i]
-          if (i === 0) ctx.moveTo(x, y)
-          else ctx.lineTo(x, y)
-        }
-        ctx.stroke()
+      // 绘制内线（保持原始样式）
+      ctx.beginPath()
+      ctx.strokeStyle = selectedWay.style.color
+      ctx.lineWidth = Math.max(1, selectedWay.style.width * zoomFactor)
+      ctx.globalAlpha = 1.0
+
+      for (let i = 0; i < selectedWay.points.length; i++) {
+        const { x, y } = selectedWay.points[i]
+        if (i === 0) ctx.moveTo(x, y)
+        else ctx.lineTo(x, y)
       }
+      ctx.stroke()
     }
 
     // 重置状态
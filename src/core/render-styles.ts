@@ -55,6 +55,10 @@ export const Flags = {
   INTERMITTENT: 0x0400,
   CONSTRUCTION: 0x0800,
   ONEWAY: 0x1000,
+  /** 由 Relation 组装的面（仅由视口响应设置，此时 ID 为 Relation ID） */
+  RELATION: 0x4000,
+  /** 已编辑（仅由视口响应设置，用于高亮） */
+  CHANGED: 0x8000,
 } as const
 
 // ============================================================================
@@ -65,6 +65,8 @@ export interface NodeData {
   x: number
   y: number
   refCount: number
+  /** 是否已编辑（相对于加载时的数据） */
+  changed: boolean
 }
 
 /** V4 视口响应解码结果 */
@@ -79,10 +81,16 @@ export interface ViewportData {
 // 拾取与详情
 // ============================================================================
 
-/** 拾取结果类型 */
-export interface PickedFeature {
-  type: 'Node' | 'Way' | 'None'
-  id?: number
+/** 拾取命中方式 */
+export type PickHit = 'Vertex' | 'Line' | 'Member' | 'Interior'
+
+/** 拾取候选（按优先级排列：节点、线、Relation 成员、面内部） */
+export interface PickCandidate {
+  member_type: 'node' | 'way' | 'relation'
+  id: number
+  hit: PickHit
+  /** 到点击位置的墨卡托距离（米），面内部为 0 */
+  distance: number
 }
 
 /** 所属关系信息 */
@@ -116,12 +124,30 @@ export interface WayDetails {
   parent_relations: ParentRelation[]
 }
 
+/** Relation 成员详情 */
+export interface RelationMemberDetails {
+  member_type: 'node' | 'way' | 'relation'
+  ref_id: number
+  role: string
+  display_name: string
+  loaded: boolean
+}
+
+/** Relation 详情 */
+export interface RelationDetails {
+  type: 'Relation'
+  id: number
+  tags: [string, string][]
+  members: RelationMemberDetails[]
+  parent_relations: ParentRelation[]
+}
+
 /** 未找到 */
 export interface NotFound {
   type: 'NotFound'
 }
 
-export type FeatureDetails = NodeDetails | WayDetails | NotFound
+export type FeatureDetails = NodeDetails | WayDetails | RelationDetails | NotFound
 
 // ============================================================================
 // 编辑操作
@@ -144,10 +170,37 @@ export interface UndoRedoResult {
   redo_count: number
 }
 
+/** 历史记录状态 */
+export interface HistoryState {
+  undo_count: number
+  redo_count: number
+  /** 历史记录的估算内存（字节） */
+  memory_bytes: number
+  max_entries: number
+  max_bytes: number
+  /** 因超出容量被裁剪的条目数 */
+  trimmed_count: number
+  /** 被裁剪条目影响的要素 */
+  trimmed_entities: {
+    node_ids: number[]
+    way_ids: number[]
+    relation_ids: number[]
+  }
+}
+
+/** 节点吸附目标 */
+export type SnapTarget =
+  /** 合并到已有节点 */
+  | { type: 'Node'; node_id: number }
+  /** 作为顶点插入 Way（index 为插入位置） */
+  | { type: 'Way'; way_id: number; index: number }
+
 /** 移动节点结果 */
 export interface MoveNodeResult {
   success: boolean
   message: string | null
+  /** 吸附目标（吸附到节点时被移动的节点已合并到该节点） */
+  snapped: SnapTarget | null
 }
 
 /** 添加节点结果 */
@@ -155,6 +208,8 @@ export interface AddNodeResult {
   success: boolean
   node_id: number
   message: string | null
+  /** 吸附目标（吸附到节点时 node_id 为该已有节点） */
+  snapped: SnapTarget | null
 }
 
 /** 删除要素结果 */
@@ -162,4 +217,6 @@ export interface DeleteFeatureResult {
   success: boolean
   message: string | null
   cascaded_way_ids: number[]
+  affected_relation_ids: number[]
+  cascaded_relation_ids: number[]
 }